@@ -283,6 +283,14 @@ fn main() {
         .write(&vals_a)
         .expect("Failed to write back to H5parm."); //.unwrap_or_else(|_err| panic!("Failed to read values for SolTab {}", stringify!(full_st_name)));
 
+    let cli_args: Vec<String> = std::env::args().skip(1).collect();
+    for st_name in ["phase000", "amplitude000"] {
+        if let Ok(st) = solset1.get_soltab(st_name.to_string()) {
+            st.record_history("polconv", env!("CARGO_PKG_VERSION"), &cli_args)
+                .expect("Failed to write history to H5parm.");
+        }
+    }
+
     h5parm1.file.flush().expect("Failed to write data to file.");
     h5parm1.file.close().expect("Failed to close H5parm.");
 }
@@ -0,0 +1,262 @@
+// Averaging of SolTabs along the time and frequency axes.
+
+use anyhow::bail;
+use ndarray::{Array1, Array2};
+use thiserror::Error;
+
+use crate::fit::{move_axis_last, restore_axis};
+use crate::{AxisValues, SolSet, SolTab, SolTabData, SolTabKind};
+
+#[derive(Debug, Error)]
+#[error("Invalid binning for axis {0}: {1}")]
+struct InvalidBinningError(String, String);
+
+/// How to group samples along an axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binning {
+    /// Average every `n` consecutive samples. The last bin may hold fewer samples.
+    Factor(usize),
+    /// Average samples into bins of the given width, in seconds for time and Hz for frequency.
+    Resolution(f64),
+}
+
+/// Settings for averaging. Axes without a binning are left untouched.
+#[derive(Debug, Clone, Default)]
+pub struct AverageOptions {
+    pub time: Option<Binning>,
+    pub freq: Option<Binning>,
+}
+
+/// Indices of the samples along `coords` that fall in each bin.
+fn bins(
+    axis: &str,
+    coords: &Array1<f64>,
+    binning: Binning,
+) -> Result<Vec<Vec<usize>>, anyhow::Error> {
+    match binning {
+        Binning::Factor(0) => bail!(InvalidBinningError(
+            axis.to_string(),
+            "factor must be at least 1".to_string()
+        )),
+        Binning::Factor(n) => Ok((0..coords.len())
+            .collect::<Vec<usize>>()
+            .chunks(n)
+            .map(|c| c.to_vec())
+            .collect()),
+        Binning::Resolution(width) => {
+            if width.is_nan() || width <= 0.0 {
+                bail!(InvalidBinningError(
+                    axis.to_string(),
+                    "resolution must be positive".to_string()
+                ));
+            }
+            let mut bins: Vec<Vec<usize>> = vec![];
+            let mut current = None;
+            for (i, x) in coords.iter().enumerate() {
+                // Small offset to keep samples on a bin edge in the bin they start.
+                let key = ((x - coords[0]) / width + 1e-9).floor() as i64;
+                if current != Some(key) {
+                    bins.push(vec![]);
+                    current = Some(key);
+                }
+                bins.last_mut().unwrap().push(i);
+            }
+            Ok(bins)
+        }
+    }
+}
+
+/// Weighted average of one bin and its output weight.
+///
+/// Phases are averaged as a circular mean and amplitudes as a geometric mean. Non-finite values
+/// (and non-positive amplitudes) count as flagged. The output weight is the mean input weight of
+/// the bin, which is the unflagged fraction for the usual 0/1 weights.
+fn average_bin(values: &[f64], weights: &[f64], kind: &SolTabKind) -> (f64, f64) {
+    let geometric = matches!(kind, SolTabKind::Amplitude | SolTabKind::ScalarAmplitude);
+    let valid: Vec<(f64, f64)> = values
+        .iter()
+        .zip(weights.iter())
+        .filter(|(v, w)| v.is_finite() && **w > 0.0 && (!geometric || **v > 0.0))
+        .map(|(v, w)| (*v, *w))
+        .collect();
+    let wsum: f64 = valid.iter().map(|(_, w)| w).sum();
+    if valid.is_empty() || wsum <= 0.0 {
+        return (f64::NAN, 0.0);
+    }
+    let weight = wsum / values.len() as f64;
+    let value = match kind.wrap_period() {
+        Some(period) => {
+            let scale = 2.0 * std::f64::consts::PI / period;
+            let (s, c) = valid.iter().fold((0.0, 0.0), |(s, c), (v, w)| {
+                (s + w * (v * scale).sin(), c + w * (v * scale).cos())
+            });
+            s.atan2(c) / scale
+        }
+        None if geometric => (valid.iter().map(|(v, w)| w * v.ln()).sum::<f64>() / wsum).exp(),
+        None => valid.iter().map(|(v, w)| w * v).sum::<f64>() / wsum,
+    };
+    (value, weight)
+}
+
+fn average_axis(
+    data: SolTabData,
+    axis: &str,
+    binning: Binning,
+) -> Result<SolTabData, anyhow::Error> {
+    let ax = match data.axes.iter().position(|(a, _)| a == axis) {
+        Some(i) => i,
+        None => return Ok(data),
+    };
+    let coords = match &data.axes[ax].1 {
+        AxisValues::Float(x) => x.clone(),
+        AxisValues::Text(_) => bail!(InvalidBinningError(
+            axis.to_string(),
+            "axis is not numeric".to_string()
+        )),
+    };
+    let bins = bins(axis, &coords, binning)?;
+
+    let (values, outer) = move_axis_last(data.values, ax)?;
+    let (weights, _) = move_axis_last(data.weights, ax)?;
+    let mut new_values = Array2::<f64>::zeros((values.nrows(), bins.len()));
+    let mut new_weights = Array2::<f64>::zeros((values.nrows(), bins.len()));
+    for (row, (v, w)) in values.outer_iter().zip(weights.outer_iter()).enumerate() {
+        for (b, indices) in bins.iter().enumerate() {
+            let bv: Vec<f64> = indices.iter().map(|&i| v[i]).collect();
+            let bw: Vec<f64> = indices.iter().map(|&i| w[i]).collect();
+            let (value, weight) = average_bin(&bv, &bw, &data.kind);
+            new_values[[row, b]] = value;
+            new_weights[[row, b]] = weight;
+        }
+    }
+
+    let mut axes = data.axes;
+    axes[ax].1 =
+        AxisValues::Float(Array1::from_iter(bins.iter().map(|indices| {
+            indices.iter().map(|&i| coords[i]).sum::<f64>() / indices.len() as f64
+        })));
+    Ok(SolTabData {
+        kind: data.kind,
+        axes,
+        values: restore_axis(new_values, &outer, ax)?,
+        weights: restore_axis(new_weights, &outer, ax)?,
+    })
+}
+
+/// Average SolTab `soltab` along time and/or frequency.
+///
+/// The coordinate of every output sample is the mean coordinate of the samples in its bin.
+/// Samples along an axis are assumed to be sorted.
+pub fn average_soltab(soltab: &SolTab, opts: &AverageOptions) -> Result<SolTabData, anyhow::Error> {
+    let mut data = soltab.read_data()?;
+    if let Some(binning) = opts.time {
+        data = average_axis(data, "time", binning)?;
+    }
+    if let Some(binning) = opts.freq {
+        data = average_axis(data, "freq", binning)?;
+    }
+    Ok(data)
+}
+
+/// Average SolTab `st_name` of `solset` and store the result as a new SolTab `out_name`.
+pub fn average<'a>(
+    solset: &'a mut SolSet,
+    st_name: &str,
+    out_name: &str,
+    opts: &AverageOptions,
+) -> Result<&'a SolTab, anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let data = average_soltab(soltab, opts)?;
+    let history = soltab.get_history();
+    let averaged = solset.create_soltab(out_name, &data)?;
+    for entry in history {
+        averaged.write_history_entry(&entry)?;
+    }
+    averaged.add_history(&format!(
+        "Averaged {} with time binning {:?} and frequency binning {:?}",
+        st_name, opts.time, opts.freq
+    ))?;
+    Ok(averaged)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Dimension;
+
+    use super::*;
+    use crate::testing::{digits, fixture, freqs, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    #[test]
+    fn bins_by_factor_and_resolution() {
+        let coords = Array1::from(vec![0.0, 10.0, 20.0, 30.0, 40.0]);
+        assert_eq!(
+            bins("time", &coords, Binning::Factor(2)).unwrap(),
+            vec![vec![0, 1], vec![2, 3], vec![4]]
+        );
+        assert_eq!(
+            bins("time", &coords, Binning::Resolution(30.0)).unwrap(),
+            vec![vec![0, 1, 2], vec![3, 4]]
+        );
+        assert!(bins("time", &coords, Binning::Factor(0)).is_err());
+        assert!(bins("time", &coords, Binning::Resolution(-1.0)).is_err());
+    }
+
+    #[test]
+    fn average_bin_by_kind() {
+        let pi = std::f64::consts::PI;
+        let (phase, weight) = average_bin(&[pi - 0.1, -pi + 0.1], &[1.0, 1.0], &SolTabKind::Phase);
+        assert!((phase.abs() - pi).abs() < 1e-12);
+        assert_eq!(weight, 1.0);
+        let (amplitude, weight) = average_bin(
+            &[1.0, 4.0, -1.0, 8.0],
+            &[1.0, 1.0, 1.0, 0.0],
+            &SolTabKind::Amplitude,
+        );
+        assert!((amplitude - 2.0).abs() < 1e-12);
+        assert_eq!(weight, 0.5);
+        let (tec, _) = average_bin(&[1.0, 2.0, f64::NAN], &[1.0, 3.0, 1.0], &SolTabKind::Tec);
+        assert!((tec - 1.75).abs() < 1e-12);
+        let (flagged, weight) = average_bin(&[1.0, 2.0], &[0.0, 0.0], &SolTabKind::Tec);
+        assert!(flagged.is_nan());
+        assert_eq!(weight, 0.0);
+    }
+
+    #[test]
+    fn average_with_freq_first() {
+        let path = temp_h5parm("average");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Tec,
+            vec![
+                ("freq", freqs(4)),
+                ("ant", names("CS", 2)),
+                ("time", times(6)),
+            ],
+            digits,
+        );
+        solset
+            .create_soltab("tec000", &data)
+            .unwrap()
+            .write_history_entry("solved")
+            .unwrap();
+        let opts = AverageOptions {
+            time: Some(Binning::Factor(3)),
+            freq: Some(Binning::Factor(2)),
+        };
+        let averaged = average(solset, "tec000", "tec001", &opts).unwrap();
+        let read = averaged.read_data().unwrap();
+        assert_eq!(read.values.shape(), &[2, 2, 2]);
+        for (i, v) in read.values.indexed_iter() {
+            let i = i.slice();
+            // Mean of channels 2f and 2f + 1 and times 3t to 3t + 2.
+            assert!((v - digits(&[2 * i[0], i[1], 3 * i[2]]) - 51.0).abs() < 1e-9);
+        }
+        assert!(read.weights.iter().all(|w| *w == 1.0));
+        let history = averaged.get_history();
+        assert_eq!(history[0], "solved");
+        assert!(history[1].contains("Averaged tec000"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+// Polynomial and spline fitting of amplitude bandpasses.
+
+use anyhow::bail;
+use ndarray::parallel::prelude::*;
+use ndarray::{Array2, Axis};
+use thiserror::Error;
+
+use crate::fit::{move_axis_last, restore_axis, weighted_lstsq};
+use crate::{SolSet, SolTabKind};
+
+#[derive(Debug, Error)]
+#[error("Bandpass fitting requires an amplitude SolTab with freq and ant axes, but {0} is not.")]
+struct NotAnAmplitudeSoltabError(String);
+
+/// Model fitted to the bandpass of every station, time, direction and polarisation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BandpassModel {
+    /// Polynomial of the given order.
+    Polynomial(usize),
+    /// Cubic B-spline with the given number of equally spaced interior knots.
+    Spline(usize),
+}
+
+/// Settings for the bandpass fit.
+#[derive(Debug, Clone)]
+pub struct BandpassOptions {
+    pub model: BandpassModel,
+    /// Name of the output SolTab holding the fitted model.
+    pub out_soltab: String,
+}
+
+impl Default for BandpassOptions {
+    fn default() -> Self {
+        BandpassOptions {
+            model: BandpassModel::Polynomial(3),
+            out_soltab: "bandpass000".to_string(),
+        }
+    }
+}
+
+/// Residual statistics of the bandpass fit of a single station.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StationResiduals {
+    pub station: String,
+    /// Weighted RMS of the residuals of all unflagged samples, or `None` if there are none.
+    pub rms: Option<f64>,
+    /// Largest absolute residual of all unflagged samples.
+    pub max_abs: f64,
+    /// Number of unflagged samples.
+    pub samples: usize,
+    /// Number of spectra for which the fit failed, e.g. because they were (nearly) fully flagged.
+    pub failed_fits: usize,
+}
+
+/// Evaluate all cubic B-spline basis functions on `knots` at `x` using the Cox-de Boor recursion.
+fn bspline_basis(knots: &[f64], x: f64) -> Vec<f64> {
+    const DEGREE: usize = 3;
+    let nintervals = knots.len() - 1;
+    let mut b: Vec<f64> = (0..nintervals)
+        .map(|j| {
+            if knots[j] <= x && x < knots[j + 1] {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    // The last non-empty interval is closed on the right.
+    if x >= knots[nintervals] {
+        if let Some(j) = (0..nintervals).rev().find(|&j| knots[j] < knots[j + 1]) {
+            b[j] = 1.0;
+        }
+    }
+    for d in 1..=DEGREE {
+        for j in 0..(nintervals - d) {
+            let left = if knots[j + d] > knots[j] {
+                (x - knots[j]) / (knots[j + d] - knots[j]) * b[j]
+            } else {
+                0.0
+            };
+            let right = if knots[j + d + 1] > knots[j + 1] {
+                (knots[j + d + 1] - x) / (knots[j + d + 1] - knots[j + 1]) * b[j + 1]
+            } else {
+                0.0
+            };
+            b[j] = left + right;
+        }
+    }
+    b.truncate(knots.len() - DEGREE - 1);
+    b
+}
+
+/// Design matrix of the given model evaluated at `freqs`.
+fn design_matrix(freqs: &[f64], model: BandpassModel) -> Array2<f64> {
+    let fmin = freqs.iter().cloned().fold(f64::INFINITY, f64::min);
+    let fmax = freqs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    match model {
+        BandpassModel::Polynomial(order) => {
+            // Scale frequencies to [-1, 1] to keep the normal equations well conditioned.
+            let centre = 0.5 * (fmax + fmin);
+            let half_width = (0.5 * (fmax - fmin)).max(f64::MIN_POSITIVE);
+            Array2::from_shape_fn((freqs.len(), order + 1), |(i, j)| {
+                ((freqs[i] - centre) / half_width).powi(j as i32)
+            })
+        }
+        BandpassModel::Spline(nknots) => {
+            let mut knots = vec![fmin; 4];
+            knots.extend(
+                (1..=nknots).map(|k| fmin + (fmax - fmin) * k as f64 / (nknots + 1) as f64),
+            );
+            knots.extend([fmax; 4]);
+            let mut design = Array2::<f64>::zeros((freqs.len(), nknots + 4));
+            for (i, f) in freqs.iter().enumerate() {
+                for (j, b) in bspline_basis(&knots, *f).into_iter().enumerate() {
+                    design[[i, j]] = b;
+                }
+            }
+            design
+        }
+    }
+}
+
+/// Fit the bandpass model to a single spectrum and evaluate it on all channels.
+///
+/// Channels with zero weight or non-finite values are ignored. Returns `None` if the fit is
+/// underdetermined.
+pub fn fit_spectrum(
+    freqs: &[f64],
+    values: &[f64],
+    weights: &[f64],
+    model: BandpassModel,
+) -> Option<Vec<f64>> {
+    let design = design_matrix(freqs, model);
+    let w: Vec<f64> = (0..freqs.len())
+        .map(|i| {
+            if values[i].is_finite() {
+                weights[i]
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let y: Vec<f64> = values
+        .iter()
+        .map(|v| if v.is_finite() { *v } else { 0.0 })
+        .collect();
+    if w.iter().filter(|w| **w > 0.0).count() < design.ncols() {
+        return None;
+    }
+    let coefficients = weighted_lstsq(&design, &y, &w)?;
+    Some(design.dot(&coefficients).to_vec())
+}
+
+/// Fit a bandpass model across frequency to every spectrum of amplitude SolTab `st_name`.
+///
+/// The model is written to a new amplitude SolTab with the same axes and weights as the input,
+/// where spectra that could not be fitted are set to NaN with zero weight. Returns residual statistics per
+/// station.
+pub fn fit_bandpass(
+    solset: &mut SolSet,
+    st_name: &str,
+    opts: &BandpassOptions,
+) -> Result<Vec<StationResiduals>, anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let axis_names = soltab.get_axes();
+    let freq_axis = axis_names.iter().position(|a| a == "freq");
+    let (freq_axis, ant_axis) = match (freq_axis, axis_names.iter().position(|a| a == "ant")) {
+        (Some(f), Some(a))
+            if matches!(
+                soltab.kind,
+                SolTabKind::Amplitude | SolTabKind::ScalarAmplitude
+            ) =>
+        {
+            (f, a)
+        }
+        _ => bail!(NotAnAmplitudeSoltabError(st_name.to_string())),
+    };
+    let freqs = soltab.get_frequencies()?.to_vec();
+    let mut data = soltab.read_data()?;
+    let (values, outer) = move_axis_last(data.values.clone(), freq_axis)?;
+    let (weights, _) = move_axis_last(data.weights.clone(), freq_axis)?;
+
+    let fits: Vec<Option<Vec<f64>>> = (0..values.nrows())
+        .into_par_iter()
+        .map(|i| {
+            fit_spectrum(
+                &freqs,
+                values.row(i).as_slice().unwrap(),
+                weights.row(i).as_slice().unwrap(),
+                opts.model,
+            )
+        })
+        .collect();
+    let mut model = Array2::<f64>::from_elem(values.dim(), f64::NAN);
+    let mut failed = Array2::<f64>::zeros(values.dim());
+    for (i, fit) in fits.into_iter().enumerate() {
+        match fit {
+            Some(fit) => model.row_mut(i).assign(&ndarray::Array1::from(fit)),
+            None => failed.row_mut(i).fill(1.0),
+        }
+    }
+    let model = restore_axis(model, &outer, freq_axis)?;
+    let failed = restore_axis(failed, &outer, freq_axis)?;
+
+    let mut stats = vec![];
+    for (s, station) in soltab.get_antennas().iter().enumerate() {
+        let v = data.values.index_axis(Axis(ant_axis), s);
+        let w = data.weights.index_axis(Axis(ant_axis), s);
+        let m = model.index_axis(Axis(ant_axis), s);
+        let (mut sum_sq, mut sum_w, mut max_abs, mut samples) = (0.0, 0.0, 0.0_f64, 0);
+        for ((v, w), m) in v.iter().zip(w.iter()).zip(m.iter()) {
+            if *w > 0.0 && v.is_finite() && m.is_finite() {
+                let r = v - m;
+                sum_sq += w * r * r;
+                sum_w += w;
+                max_abs = max_abs.max(r.abs());
+                samples += 1;
+            }
+        }
+        stats.push(StationResiduals {
+            station: station.to_string(),
+            rms: (sum_w > 0.0).then(|| (sum_sq / sum_w).sqrt()),
+            max_abs,
+            samples,
+            failed_fits: failed.index_axis(Axis(ant_axis), s).sum() as usize / freqs.len(),
+        });
+    }
+
+    // Samples that were flagged in the input stay flagged in the model.
+    data.weights *= &failed.mapv(|f| 1.0 - f);
+    data.values = model;
+    let history = soltab.get_history();
+    solset
+        .create_soltab(&opts.out_soltab, &data)?
+        .add_derived_history(
+            &history,
+            &format!("Fitted {:?} bandpass to {}", opts.model, st_name),
+        )?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{s, Dimension};
+
+    use super::*;
+    use crate::testing::{fixture, freqs, names, temp_h5parm, times};
+    use crate::{AxisValues, H5parm};
+
+    fn channels(n: usize) -> Vec<f64> {
+        (0..n).map(|i| 120e6 + 1e6 * i as f64).collect()
+    }
+
+    #[test]
+    fn polynomial_recovers_quadratic() {
+        let freqs = channels(20);
+        let values: Vec<f64> = freqs
+            .iter()
+            .map(|f| 1.0 + 0.5 * (f / 1e8) - 0.1 * (f / 1e8).powi(2))
+            .collect();
+        let mut weights = vec![1.0; freqs.len()];
+        let mut noisy = values.clone();
+        noisy[4] = 100.0;
+        weights[4] = 0.0;
+        noisy[9] = f64::NAN;
+        let fit = fit_spectrum(&freqs, &noisy, &weights, BandpassModel::Polynomial(2)).unwrap();
+        for (f, v) in fit.iter().zip(values.iter()) {
+            assert!((f - v).abs() < 1e-9);
+        }
+        assert!(fit_spectrum(
+            &freqs[..2],
+            &values[..2],
+            &weights[..2],
+            BandpassModel::Polynomial(2)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn spline_reproduces_cubic() {
+        let freqs = channels(30);
+        let x = |f: f64| (f - 120e6) / 1e7;
+        let values: Vec<f64> = freqs
+            .iter()
+            .map(|f| 2.0 + x(*f) - 0.3 * x(*f).powi(3))
+            .collect();
+        let weights = vec![1.0; freqs.len()];
+        let fit = fit_spectrum(&freqs, &values, &weights, BandpassModel::Spline(3)).unwrap();
+        for (f, v) in fit.iter().zip(values.iter()) {
+            assert!((f - v).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn fit_bandpass_with_freq_not_last() {
+        let path = temp_h5parm("bandpass");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let f = match freqs(10) {
+            AxisValues::Float(f) => f.to_vec(),
+            AxisValues::Text(_) => unreachable!(),
+        };
+        // A straight bandpass per station and polarisation.
+        let line =
+            |i: &[usize]| 1.0 + 0.1 * i[2] as f64 + 0.01 * i[3] as f64 + (f[i[1]] - f[0]) / 1e7;
+        let mut amplitudes = fixture(
+            SolTabKind::Amplitude,
+            vec![
+                ("time", times(3)),
+                ("freq", freqs(10)),
+                ("ant", names("CS", 3)),
+                ("pol", names("P", 2)),
+            ],
+            line,
+        );
+        // Flag the whole spectrum of the second station at the first time and polarisation.
+        amplitudes.weights.slice_mut(s![0, .., 1, 0]).fill(0.0);
+        // Flag a single channel of the first station and the third station entirely.
+        amplitudes.weights[[1, 3, 0, 1]] = 0.0;
+        amplitudes.weights.slice_mut(s![.., .., 2, ..]).fill(0.0);
+        solset.create_soltab("amplitude000", &amplitudes).unwrap();
+
+        let opts = BandpassOptions {
+            model: BandpassModel::Polynomial(1),
+            ..Default::default()
+        };
+        let stats = fit_bandpass(solset, "amplitude000", &opts).unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].failed_fits, 0);
+        assert_eq!(stats[1].failed_fits, 1);
+        assert_eq!(stats[2].failed_fits, 6);
+        assert_eq!(stats[0].samples, 59);
+        assert_eq!(stats[1].samples, 50);
+        assert_eq!(stats[2].samples, 0);
+        assert_eq!(stats[2].rms, None);
+        assert!(stats[..2]
+            .iter()
+            .all(|s| s.rms.unwrap() < 1e-9 && s.max_abs < 1e-9));
+
+        let model = solset
+            .get_soltab("bandpass000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(model.axes, amplitudes.axes);
+        for (i, m) in model.values.indexed_iter() {
+            let i = i.slice();
+            match (i[0], i[1], i[2], i[3]) {
+                (0, _, 1, 0) | (_, _, 2, _) => assert!(m.is_nan() && model.weights[i] == 0.0),
+                (1, 3, 0, 1) => assert!((m - line(i)).abs() < 1e-9 && model.weights[i] == 0.0),
+                _ => assert!((m - line(i)).abs() < 1e-9 && model.weights[i] == 1.0),
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+//pub mod h5parm;
+
+use clap::{Parser, ValueEnum};
+
+extern crate h5o3;
+
+use h5o3::concat::OverlapPolicy;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Overlap {
+    /// Fail if the inputs overlap.
+    Reject,
+    /// Keep the samples of the first input.
+    First,
+    /// Keep the samples of the last input.
+    Last,
+}
+
+/// Concatenates SolTabs from several LOFAR H5parms along the time or frequency axis into a new
+/// H5parm.
+#[derive(Parser, Debug)]
+#[command(name = "h5o3-concat")]
+#[command(author = "Frits Sweijen")]
+#[command(version = "0.0.0")]
+#[command(
+    help_template = "{name} \nVersion: {version} \nAuthor: {author}\n{about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+)]
+struct Args {
+    /// H5parms to concatenate.
+    #[arg(long, num_args = 1.., required = true)]
+    h5parms: Vec<String>,
+    /// Output H5parm. Must not exist yet.
+    #[arg(long)]
+    out: String,
+    /// Axis to concatenate along, time or freq.
+    #[arg(long, default_value = "time")]
+    axis: String,
+    /// SolSet to concatenate.
+    #[arg(long, default_value = "sol000")]
+    solset: String,
+    /// SolTabs to concatenate. Defaults to all SolTabs in the first H5parm.
+    #[arg(long, num_args = 1..)]
+    soltabs: Option<Vec<String>>,
+    /// How to handle overlapping samples.
+    #[arg(long, value_enum, default_value = "reject")]
+    overlap: Overlap,
+}
+
+fn main() {
+    let args = Args::parse();
+    let h5parms: Vec<h5o3::H5parm> = args
+        .h5parms
+        .iter()
+        .map(|h| h5o3::H5parm::open(h, true).expect("Failed to read H5parm."))
+        .collect();
+    let overlap = match args.overlap {
+        Overlap::Reject => OverlapPolicy::Reject,
+        Overlap::First => OverlapPolicy::KeepFirst,
+        Overlap::Last => OverlapPolicy::KeepLast,
+    };
+    let out = h5o3::concat::concatenate(
+        &h5parms,
+        &args.out,
+        &args.solset,
+        args.soltabs.clone(),
+        &args.axis,
+        overlap,
+    )
+    .expect("Failed to concatenate H5parms.");
+    let cli_args: Vec<String> = std::env::args().skip(1).collect();
+    for st in out.get_solset(args.solset.clone()).unwrap().get_soltabs() {
+        st.record_history("h5o3-concat", env!("CARGO_PKG_VERSION"), &cli_args)
+            .expect("Failed to write history to H5parm.");
+        println!("Concatenated {} along {}.", st.name, args.axis);
+    }
+    out.file.flush().expect("Failed to write data to file.");
+}
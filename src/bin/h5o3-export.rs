@@ -0,0 +1,61 @@
+//pub mod h5parm;
+
+use clap::{Parser, ValueEnum};
+
+extern crate h5o3;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    /// One .npy file per array in the output directory.
+    Npy,
+    /// A single .npz archive.
+    Npz,
+    /// A long-format CSV table with one row per sample.
+    Csv,
+    /// A long-format Apache Parquet table with one row per sample.
+    Parquet,
+}
+
+/// Exports SolTabs of LOFAR H5parms to other file formats.
+#[derive(Parser, Debug)]
+#[command(name = "h5o3-export")]
+#[command(author = "Frits Sweijen")]
+#[command(version = "0.0.0")]
+#[command(
+    help_template = "{name} \nVersion: {version} \nAuthor: {author}\n{about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+)]
+struct Args {
+    /// H5parm to export from.
+    #[arg(long)]
+    h5parm: String,
+    /// SolSet containing the SolTab.
+    #[arg(long, default_value = "sol000")]
+    solset: String,
+    /// SolTab to export.
+    #[arg(long)]
+    soltab: String,
+    /// Output format.
+    #[arg(long, value_enum)]
+    format: Format,
+    /// Output file, or directory for the npy format.
+    #[arg(long)]
+    out: String,
+}
+
+fn main() {
+    let args = Args::parse();
+    let h5parm = h5o3::H5parm::open(&args.h5parm, true).expect("Failed to read H5parm.");
+    let soltab = h5parm
+        .get_solset(args.solset.clone())
+        .expect("Failed to load solset.")
+        .get_soltab(args.soltab.clone())
+        .expect("Failed to load soltab.");
+    match args.format {
+        Format::Npy => h5o3::npy::export_npy(soltab, &args.out),
+        Format::Npz => h5o3::npy::export_npz(soltab, &args.out),
+        Format::Csv => h5o3::tabular::export_csv(soltab, &args.out),
+        Format::Parquet => h5o3::tabular::export_parquet(soltab, &args.out),
+    }
+    .expect("Failed to export soltab.");
+    println!("Exported {} to {}.", soltab.name, args.out);
+}
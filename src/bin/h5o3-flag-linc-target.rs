@@ -4,11 +4,12 @@ use std::ops::{Rem, SubAssign};
 
 use clap::Parser;
 use medians::Medianf64;
-use ndarray::{s, Array1};
-use num::complex::{Complex, ComplexFloat};
+use ndarray::{s, Array1, ArrayView1, Axis};
 
 extern crate h5o3;
 
+use h5o3::filter::{running_median, EdgeMode};
+
 /// Flags LINC Target phase solutions based on their relative noise with respect to the core
 /// stations. Solutions are flagged by setting their weight to 0. Optionally, the data can also be
 /// set to NaN.
@@ -38,45 +39,6 @@ struct Args {
     blank_data: bool,
 }
 
-fn medfilt(input: &Array1<f64>, kernel_size: usize) -> Vec<f64> {
-    assert!(kernel_size % 2 == 1, "Kernel size must be odd");
-
-    let half_k = kernel_size / 2;
-    let mut output = Vec::with_capacity(input.len());
-
-    for i in 0..input.len() {
-        let mut window = Vec::with_capacity(kernel_size);
-        for j in i.saturating_sub(half_k)..=i + half_k {
-            if j < input.len() {
-                window.push(input[j]);
-            } else {
-                window.push(0.0);
-            }
-        }
-        // Add left-side padding if necessary
-        while window.len() < kernel_size {
-            window.insert(0, 0.0);
-        }
-        window.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        output.push(window[half_k]);
-    }
-    output
-}
-
-fn circstd(x: Array1<f64>) -> f64 {
-    // See e.g. https://docs.scipy.org/doc/scipy/reference/generated/scipy.stats.circstd.html
-    let mut m = Complex::new(0.0, 0.0);
-    for theta in x.iter().filter(|i| i.is_finite()) {
-        let z = Complex::from_polar(1.0, *theta);
-        m += z;
-    }
-    let num = Complex::from_polar(m.abs() / x.len() as f64, m.arg());
-    let r = num.abs();
-    assert!(r <= 1.0, "r should be smaller than one");
-    assert!(r >= 0.0, "r should be larger than zero");
-    (-2.0 * r.ln()).sqrt()
-}
-
 fn main() {
     let args = Args::parse();
     let h5parm = h5o3::H5parm::open(&args.h5parm, false)
@@ -108,15 +70,17 @@ fn main() {
         .iter()
         .map(|ant| {
             let temp_phase = vals_diff.slice(s![.., *ant, 0]).to_owned();
-            let temp_phase = temp_phase.map(|x| if !x.is_finite() { 0.0 } else { *x });
 
-            let filtered = Array1::from(medfilt(&temp_phase, 59));
+            let filtered = running_median(&temp_phase, 59, Axis(0), EdgeMode::Reflect)
+                .expect("Failed to compute running median.");
 
             let detrended = temp_phase - filtered;
             let detrended: Vec<f64> = detrended.into_iter().filter(|x| x.is_finite()).collect();
             let detrended = Array1::from_vec(detrended);
             if !detrended.is_empty() {
-                let scatter = circstd(detrended);
+                let scatter = h5o3::circular::std(&detrended, Axis(0))
+                    .expect("Failed to compute circular standard deviation.")
+                    .into_scalar();
                 println!("Scatter for antenna {} is {}", *ant, scatter);
                 scatter
             } else {
@@ -133,6 +97,15 @@ fn main() {
 
     let flag_pc_before = phase.get_flagged_fraction();
 
+    // A chunk without any finite phase differences has no defined scatter. It is flagged, as
+    // it was before the circular statistics skipped non-finite values.
+    let noisy = |chunk: ArrayView1<f64>| {
+        let scatter = h5o3::circular::std(&chunk, Axis(0))
+            .expect("Failed to compute circular standard deviation.")
+            .into_scalar();
+        !scatter.is_finite() || scatter > args.sigma * median_std
+    };
+
     for (station, station_name) in ant.iter().enumerate() {
         if station_name.contains("CS") || station_name.contains("RS") {
             for chan in 0..freqs.len() {
@@ -140,7 +113,7 @@ fn main() {
                 for chunk in (0..time.len() - remaining).step_by(8) {
                     let temp_phase = vals_diff.slice(s![chunk..chunk + 8, station, chan]);
                     //if temp_phase.std(1.0) > median_std {
-                    if circstd(temp_phase.to_owned()) > args.sigma * median_std {
+                    if noisy(temp_phase) {
                         vals_p
                             .slice_mut(s![chunk..chunk + 8, station, chan, ..])
                             .iter_mut()
@@ -153,11 +126,11 @@ fn main() {
                 }
                 let final_chunk = time.len() - remaining;
                 let temp_phase = vals_diff.slice(s![final_chunk..time.len(), station, chan]);
-                if circstd(temp_phase.to_owned()) > args.sigma * median_std {
+                if noisy(temp_phase) {
                     vals_p
                         .slice_mut(s![final_chunk..time.len(), station, chan, ..])
                         .iter_mut()
-                        .for_each(|f| *f = 0.0);
+                        .for_each(|f| *f = f64::NAN);
                     weights
                         .slice_mut(s![final_chunk..time.len(), station, chan, ..])
                         .iter_mut()
@@ -167,23 +140,22 @@ fn main() {
         }
     }
     if args.blank_data {
-        h5parm
-            .file
-            .group("/target/TGSSphase_final")
-            .expect("Failed to read group")
-            .dataset("val")
-            .unwrap()
-            .write(&vals_p)
+        phase
+            .set_values(&vals_p)
             .expect("Failed to write values back to H5parm.");
     }
-    h5parm
-        .file
-        .group("/target/TGSSphase_final")
-        .expect("Failed to read group")
-        .dataset("weight")
-        .unwrap()
-        .write(&weights)
-        .expect("Failed to write weights back to H5parm."); //.unwrap_or_else(|_err| panic!("Failed to read values for SolTab {}", stringify!(full_st_name)));
+    phase
+        .set_weights(&weights)
+        .expect("Failed to write weights back to H5parm.");
+    // The history is only recorded once the solutions were written, so that it never describes
+    // flags that failed to be written.
+    phase
+        .record_history(
+            "h5o3-flag-linc-target",
+            env!("CARGO_PKG_VERSION"),
+            &std::env::args().skip(1).collect::<Vec<String>>(),
+        )
+        .expect("Failed to write history to H5parm.");
     h5parm.file.flush().expect("Failed to write data to file.");
 
     let flag_pc_after = phase.get_flagged_fraction();
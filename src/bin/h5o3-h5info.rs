@@ -1,9 +1,21 @@
 //pub mod h5parm;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 extern crate h5o3;
 
+use h5o3::frequency::Clock;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ClockArg {
+    /// 160 MHz sampling clock.
+    #[value(name = "160")]
+    Mhz160,
+    /// 200 MHz sampling clock.
+    #[value(name = "200")]
+    Mhz200,
+}
+
 /// A Rust interface to summarise LOFAR H5parm calibration tables.
 #[derive(Parser, Debug)]
 #[command(name = "LOFAR-H5info")]
@@ -20,12 +32,121 @@ struct Args {
     /// SolSet to display.
     #[arg(long, default_value = "")]
     solset: String,
-    /// Verbose output (e.g. the history)
+    /// Verbose output (e.g. the history and per-station statistics)
     #[arg(long, default_value("false"))]
     verbose: bool,
+    /// Sampling clock in MHz, to tell apart HBA bands that overlap in frequency.
+    #[arg(long, value_enum)]
+    clock: Option<ClockArg>,
+}
+
+fn print_time_summary(st: &h5o3::SolTab) {
+    if !st.get_axes().iter().any(|a| a == "time") {
+        return;
+    }
+    let times = st.get_times();
+    if times.is_empty() {
+        return;
+    }
+    println!(
+        "|\tTime: {} to {} ({} samples)",
+        h5o3::time::format_utc(times[0]),
+        h5o3::time::format_utc(times[times.len() - 1]),
+        times.len()
+    );
+    if let Some(integration) = h5o3::time::integration_time(&times) {
+        let gaps = h5o3::time::find_gaps(&times, 1.5);
+        println!(
+            "|\tIntegration time: {:.3} s, {} gap(s)",
+            integration,
+            gaps.len()
+        );
+        for gap in gaps {
+            println!(
+                "|\t  gap of {:.1} s from {} to {}",
+                gap.duration(integration),
+                h5o3::time::format_utc(gap.start),
+                h5o3::time::format_utc(gap.end)
+            );
+        }
+    }
+}
+
+fn print_frequency_summary(st: &h5o3::SolTab, clock: Option<Clock>) {
+    if !st.get_axes().iter().any(|a| a == "freq") {
+        return;
+    }
+    let freqs = st.get_frequencies().expect("Failed to read frequencies.");
+    let (lower, upper) = match h5o3::frequency::band_edges(&freqs) {
+        Some(edges) => edges,
+        None => return,
+    };
+    let width = h5o3::frequency::channel_width(&freqs).unwrap_or(0.0);
+    let band = match h5o3::frequency::detect_band(&freqs, clock) {
+        Some(band) => {
+            let subband = |f: f64| {
+                h5o3::frequency::frequency_to_subband(f, band.clock(), band.nyquist_zone())
+                    .map_or("-".to_string(), |sb| sb.to_string())
+            };
+            format!(
+                "{}, subbands {} to {}",
+                band,
+                subband(freqs[0]),
+                subband(freqs[freqs.len() - 1])
+            )
+        }
+        None => "unknown band".to_string(),
+    };
+    let gaps = h5o3::frequency::find_gaps(&freqs, 1.5);
+    println!(
+        "|\tFrequency: {:.3} to {:.3} MHz, {} channels of {:.3} kHz ({}), {} gap(s)",
+        lower / 1e6,
+        upper / 1e6,
+        freqs.len(),
+        width / 1e3,
+        band,
+        gaps.len()
+    );
+    for gap in gaps {
+        println!(
+            "|\t  gap from {:.3} to {:.3} MHz",
+            gap.start / 1e6,
+            gap.end / 1e6
+        );
+    }
+}
+
+fn print_station_stats(st: &h5o3::SolTab) {
+    let stats = match h5o3::stats::station_stats(st, &h5o3::stats::StatsOptions::default()) {
+        Ok(stats) => stats,
+        Err(e) => {
+            println!("|\t{}", e);
+            return;
+        }
+    };
+    let format = |x: Option<f64>| match x {
+        Some(x) => format!("{:.3}", x),
+        None => "-".to_string(),
+    };
+    println!(
+        "|\t{:<16} {:<11} {:<13} {:<11} {:<11} {:<7} {:<13}",
+        "Station", "% flagged", "Phase noise", "Amp. scat.", "Scatter", "Jumps", "Pol. coher."
+    );
+    for s in stats {
+        println!(
+            "|\t{:<16} {:<11.2} {:<13} {:<11} {:<11} {:<7} {:<13}",
+            s.station,
+            s.flagged_fraction * 100.0,
+            format(s.phase_noise),
+            format(s.amplitude_scatter),
+            format(s.scatter),
+            s.jumps,
+            format(s.pol_coherence)
+        );
+    }
 }
 
-fn summarise_h5parm(h5parm: &String, solset: String, verbose: bool) {
+fn summarise_h5parm(h5parm: &String, solset: String, verbose: bool, clock: Option<Clock>) {
     let h5name = h5parm.split("/").last().unwrap();
     println!("Summarising {}\n", h5name);
     let h5 = h5o3::H5parm::open(h5parm, false).expect("Failed to read H5parm.");
@@ -62,10 +183,12 @@ fn summarise_h5parm(h5parm: &String, solset: String, verbose: bool) {
                     is.len()
                 );
                 if verbose {
-                    let h = st.get_history();
-                    if h.len() > 0 {
+                    for h in st.get_history() {
                         println!("|\t{}", h);
                     }
+                    print_time_summary(&st);
+                    print_frequency_summary(&st, clock);
+                    print_station_stats(&st);
                     println!("|");
                 }
             }
@@ -95,10 +218,12 @@ fn summarise_h5parm(h5parm: &String, solset: String, verbose: bool) {
                 rs.len()
             );
             if verbose {
-                let h = st.get_history();
-                if h.len() > 0 {
+                for h in st.get_history() {
                     println!("|\t{}", h);
                 }
+                print_time_summary(st);
+                print_frequency_summary(st, clock);
+                print_station_stats(st);
                 println!("|");
             }
         }
@@ -108,5 +233,9 @@ fn summarise_h5parm(h5parm: &String, solset: String, verbose: bool) {
 fn main() {
     let args = Args::parse();
     println!("H5parm: {}\n", args.h5parm);
-    summarise_h5parm(&args.h5parm, args.solset, args.verbose);
+    let clock = args.clock.map(|c| match c {
+        ClockArg::Mhz160 => Clock::Mhz160,
+        ClockArg::Mhz200 => Clock::Mhz200,
+    });
+    summarise_h5parm(&args.h5parm, args.solset, args.verbose, clock);
 }
@@ -0,0 +1,93 @@
+//pub mod h5parm;
+
+use clap::{Parser, Subcommand};
+
+extern crate h5o3;
+
+use h5o3::killms::KillMsOptions;
+
+/// Converts between killMS/DDFacet solution files and LOFAR H5parms.
+#[derive(Parser, Debug)]
+#[command(name = "h5o3-killms")]
+#[command(author = "Frits Sweijen")]
+#[command(version = "0.0.0")]
+#[command(
+    help_template = "{name} \nVersion: {version} \nAuthor: {author}\n{about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+)]
+struct Args {
+    /// H5parm to read from or write to. It is created when importing if it does not exist.
+    #[arg(long)]
+    h5parm: String,
+    /// SolSet to read from or write to.
+    #[arg(long, default_value = "sol000")]
+    solset: String,
+    /// Amplitude SolTab.
+    #[arg(long, default_value = "amplitude000")]
+    amplitude: String,
+    /// Phase SolTab.
+    #[arg(long, default_value = "phase000")]
+    phase: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Import killMS solutions into amplitude and phase SolTabs.
+    Import {
+        /// killMS solution file (.npz).
+        #[arg(long)]
+        npz: String,
+    },
+    /// Export amplitude and phase SolTabs as killMS solutions.
+    Export {
+        /// killMS solution file (.npz) to write.
+        #[arg(long)]
+        npz: String,
+        /// Only export the phases, with unit amplitudes.
+        #[arg(long, default_value = "false")]
+        phase_only: bool,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+    match args.command {
+        Command::Import { npz } => {
+            let mut h5parm = match std::path::Path::new(&args.h5parm).exists() {
+                true => h5o3::H5parm::open(&args.h5parm, false)
+                    .expect("Failed opening h5parm in readwrite mode."),
+                false => h5o3::H5parm::create(&args.h5parm).expect("Failed to create H5parm."),
+            };
+            if !h5parm.has_solset(&args.solset) {
+                h5parm
+                    .create_solset(&args.solset)
+                    .expect("Failed to create solset.");
+            }
+            let solset = h5parm
+                .get_solset_mut(args.solset.clone())
+                .expect("Failed to load solset.");
+            let opts = KillMsOptions {
+                amplitude_soltab: Some(args.amplitude.clone()),
+                phase_soltab: Some(args.phase.clone()),
+            };
+            h5o3::killms::import_killms(&npz, solset, &opts)
+                .expect("Failed to import killMS solutions.");
+            h5parm.file.flush().expect("Failed to write data to file.");
+            println!("Imported {} into {}.", npz, args.h5parm);
+        }
+        Command::Export { npz, phase_only } => {
+            let h5parm = h5o3::H5parm::open(&args.h5parm, true).expect("Failed to read H5parm.");
+            let solset = h5parm
+                .get_solset(args.solset.clone())
+                .expect("Failed to load solset.");
+            let opts = KillMsOptions {
+                amplitude_soltab: (!phase_only).then_some(args.amplitude.clone()),
+                phase_soltab: Some(args.phase.clone()),
+            };
+            h5o3::killms::export_killms(solset, &npz, &opts)
+                .expect("Failed to export killMS solutions.");
+            println!("Exported {} to {}.", args.h5parm, npz);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//pub mod h5parm;
+
+use clap::{Parser, Subcommand};
+
+extern crate h5o3;
+
+/// Copy, rename and delete SolTabs and SolSets, and edit directions, in LOFAR H5parm calibration
+/// tables.
+#[derive(Parser, Debug)]
+#[command(name = "h5o3-manage")]
+#[command(author = "Frits Sweijen")]
+#[command(version = "0.0.0")]
+#[command(
+    help_template = "{name} \nVersion: {version} \nAuthor: {author}\n{about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+)]
+struct Args {
+    /// H5parm to modify.
+    #[arg(long)]
+    h5parm: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Copy a SolTab within a SolSet.
+    CopySoltab {
+        /// SolSet containing the SolTab.
+        #[arg(long, default_value = "sol000")]
+        solset: String,
+        /// SolTab to copy.
+        #[arg(long)]
+        src: String,
+        /// Name of the copy.
+        #[arg(long)]
+        dst: String,
+    },
+    /// Rename a SolTab within a SolSet.
+    RenameSoltab {
+        /// SolSet containing the SolTab.
+        #[arg(long, default_value = "sol000")]
+        solset: String,
+        /// SolTab to rename.
+        #[arg(long)]
+        src: String,
+        /// New name of the SolTab.
+        #[arg(long)]
+        dst: String,
+    },
+    /// Delete a SolTab from a SolSet.
+    DeleteSoltab {
+        /// SolSet containing the SolTab.
+        #[arg(long, default_value = "sol000")]
+        solset: String,
+        /// SolTab to delete.
+        #[arg(long)]
+        soltab: String,
+    },
+    /// Copy a SolSet, optionally from another H5parm.
+    CopySolset {
+        /// SolSet to copy.
+        #[arg(long)]
+        src: String,
+        /// Name of the copy.
+        #[arg(long)]
+        dst: String,
+        /// H5parm to copy the SolSet from, instead of the H5parm being modified.
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Rename a SolSet.
+    RenameSolset {
+        /// SolSet to rename.
+        #[arg(long)]
+        src: String,
+        /// New name of the SolSet.
+        #[arg(long)]
+        dst: String,
+    },
+    /// Delete a SolSet.
+    DeleteSolset {
+        /// SolSet to delete.
+        #[arg(long)]
+        solset: String,
+    },
+    /// Keep only the given directions in all SolTabs of a SolSet, in the given order.
+    SelectDirections {
+        /// SolSet to modify.
+        #[arg(long, default_value = "sol000")]
+        solset: String,
+        /// Directions to keep.
+        #[arg(long, num_args = 1.., required = true)]
+        directions: Vec<String>,
+    },
+    /// Remove directions from all SolTabs of a SolSet.
+    DropDirections {
+        /// SolSet to modify.
+        #[arg(long, default_value = "sol000")]
+        solset: String,
+        /// Directions to remove.
+        #[arg(long, num_args = 1.., required = true)]
+        directions: Vec<String>,
+    },
+    /// Rename directions in all SolTabs of a SolSet.
+    RenameDirections {
+        /// SolSet to modify.
+        #[arg(long, default_value = "sol000")]
+        solset: String,
+        /// Directions to rename, given as old=new.
+        #[arg(long, num_args = 1.., required = true)]
+        directions: Vec<String>,
+    },
+}
+
+/// Record the call of this tool in the history of the SolTabs of `solset` that `filter` accepts.
+fn record_history(solset: &h5o3::SolSet, args: &[String], filter: impl Fn(&h5o3::SolTab) -> bool) {
+    for st in solset.get_soltabs().iter().filter(|st| filter(st)) {
+        st.record_history("h5o3-manage", env!("CARGO_PKG_VERSION"), args)
+            .expect("Failed to write history to H5parm.");
+    }
+}
+
+fn has_directions(st: &h5o3::SolTab) -> bool {
+    st.get_axes().iter().any(|a| a == "dir")
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut h5parm =
+        h5o3::H5parm::open(&args.h5parm, false).expect("Failed opening h5parm in readwrite mode.");
+    let cli_args: Vec<String> = std::env::args().skip(1).collect();
+    match args.command {
+        Command::CopySoltab { solset, src, dst } => {
+            let ss = h5parm
+                .get_solset_mut(solset)
+                .expect("Failed to load solset.");
+            let st = ss.copy_soltab(&src, &dst).expect("Failed to copy soltab.");
+            st.record_history("h5o3-manage", env!("CARGO_PKG_VERSION"), &cli_args)
+                .expect("Failed to write history to H5parm.");
+            println!("Copied {} to {}.", src, dst);
+        }
+        Command::RenameSoltab { solset, src, dst } => {
+            let ss = h5parm
+                .get_solset_mut(solset)
+                .expect("Failed to load solset.");
+            ss.rename_soltab(&src, &dst)
+                .expect("Failed to rename soltab.");
+            ss.get_soltab(dst.clone())
+                .unwrap()
+                .record_history("h5o3-manage", env!("CARGO_PKG_VERSION"), &cli_args)
+                .expect("Failed to write history to H5parm.");
+            println!("Renamed {} to {}.", src, dst);
+        }
+        // Deleting removes the history along with the SolTab or SolSet, so nothing is recorded.
+        Command::DeleteSoltab { solset, soltab } => {
+            h5parm
+                .get_solset_mut(solset)
+                .expect("Failed to load solset.")
+                .delete_soltab(&soltab)
+                .expect("Failed to delete soltab.");
+            println!("Deleted {}.", soltab);
+        }
+        Command::CopySolset { src, dst, from } => {
+            match from {
+                Some(other) => {
+                    let other =
+                        h5o3::H5parm::open(&other, true).expect("Failed to read source H5parm.");
+                    h5parm
+                        .copy_solset_from(&other, &src, &dst)
+                        .expect("Failed to copy solset.");
+                }
+                None => {
+                    h5parm
+                        .copy_solset(&src, &dst)
+                        .expect("Failed to copy solset.");
+                }
+            }
+            record_history(h5parm.get_solset(dst.clone()).unwrap(), &cli_args, |_| true);
+            println!("Copied {} to {}.", src, dst);
+        }
+        Command::RenameSolset { src, dst } => {
+            h5parm
+                .rename_solset(&src, &dst)
+                .expect("Failed to rename solset.");
+            record_history(h5parm.get_solset(dst.clone()).unwrap(), &cli_args, |_| true);
+            println!("Renamed {} to {}.", src, dst);
+        }
+        Command::DeleteSolset { solset } => {
+            h5parm
+                .delete_solset(&solset)
+                .expect("Failed to delete solset.");
+            println!("Deleted {}.", solset);
+        }
+        Command::SelectDirections { solset, directions } => {
+            let ss = h5parm
+                .get_solset_mut(solset)
+                .expect("Failed to load solset.");
+            h5o3::directions::select_directions(ss, &directions)
+                .expect("Failed to select directions.");
+            record_history(ss, &cli_args, has_directions);
+            println!("Selected {}.", directions.join(", "));
+        }
+        Command::DropDirections { solset, directions } => {
+            let ss = h5parm
+                .get_solset_mut(solset)
+                .expect("Failed to load solset.");
+            h5o3::directions::drop_directions(ss, &directions).expect("Failed to drop directions.");
+            record_history(ss, &cli_args, has_directions);
+            println!("Dropped {}.", directions.join(", "));
+        }
+        Command::RenameDirections { solset, directions } => {
+            let renames: Vec<(String, String)> = directions
+                .iter()
+                .map(|d| match d.split_once('=') {
+                    Some((old, new)) => (old.to_string(), new.to_string()),
+                    None => panic!("Direction renames must be given as old=new, not {}.", d),
+                })
+                .collect();
+            let ss = h5parm
+                .get_solset_mut(solset)
+                .expect("Failed to load solset.");
+            h5o3::directions::rename_directions(ss, &renames)
+                .expect("Failed to rename directions.");
+            record_history(ss, &cli_args, has_directions);
+            println!("Renamed {}.", directions.join(", "));
+        }
+    }
+    h5parm.file.flush().expect("Failed to write data to file.");
+}
@@ -0,0 +1,85 @@
+//pub mod h5parm;
+
+use clap::{Parser, ValueEnum};
+
+extern crate h5o3;
+
+use h5o3::plot::{Layout, PlotKind, PlotOptions};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    /// Solutions against time for a single channel.
+    Time,
+    /// Solutions against time and frequency.
+    Waterfall,
+}
+
+/// Plots solutions of LOFAR H5parms per station to PNG or SVG files.
+#[derive(Parser, Debug)]
+#[command(name = "h5o3-plot")]
+#[command(author = "Frits Sweijen")]
+#[command(version = "0.0.0")]
+#[command(
+    help_template = "{name} \nVersion: {version} \nAuthor: {author}\n{about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+)]
+struct Args {
+    /// H5parm to plot.
+    #[arg(long)]
+    h5parm: String,
+    /// SolSet containing the SolTab.
+    #[arg(long, default_value = "sol000")]
+    solset: String,
+    /// SolTab to plot.
+    #[arg(long)]
+    soltab: String,
+    /// Output file. The extension (.png or .svg) selects the format.
+    #[arg(long)]
+    out: String,
+    /// Type of plot.
+    #[arg(long, value_enum, default_value = "time")]
+    kind: Kind,
+    /// Write one file per station instead of a grid of panels.
+    #[arg(long, default_value = "false")]
+    per_station: bool,
+    /// Reference antenna for phases.
+    #[arg(long)]
+    refant: Option<String>,
+    /// Direction to plot. Defaults to the first direction.
+    #[arg(long)]
+    direction: Option<String>,
+    /// Polarisations to plot. Defaults to all.
+    #[arg(long, num_args = 1..)]
+    pols: Option<Vec<String>>,
+    /// Channel to plot for time series.
+    #[arg(long, default_value = "0")]
+    channel: usize,
+}
+
+fn main() {
+    let args = Args::parse();
+    let h5parm = h5o3::H5parm::open(&args.h5parm, true).expect("Failed to read H5parm.");
+    let soltab = h5parm
+        .get_solset(args.solset.clone())
+        .expect("Failed to load solset.")
+        .get_soltab(args.soltab.clone())
+        .expect("Failed to load soltab.");
+    let opts = PlotOptions {
+        kind: match args.kind {
+            Kind::Time => PlotKind::TimeSeries,
+            Kind::Waterfall => PlotKind::Waterfall,
+        },
+        layout: match args.per_station {
+            true => Layout::PerStation,
+            false => Layout::Grid,
+        },
+        reference_antenna: args.refant,
+        direction: args.direction,
+        polarisations: args.pols,
+        channel: args.channel,
+        ..Default::default()
+    };
+    let files = h5o3::plot::plot_soltab(soltab, &args.out, &opts).expect("Failed to plot soltab.");
+    for f in files {
+        println!("Wrote {}.", f);
+    }
+}
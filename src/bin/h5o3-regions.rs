@@ -0,0 +1,63 @@
+//pub mod h5parm;
+
+use clap::Parser;
+
+extern crate h5o3;
+
+use h5o3::regions::{BoundingBox, DirectionPosition};
+
+/// Writes the directions of a LOFAR H5parm as a DS9 region file, optionally as Voronoi facets
+/// for facet imaging with WSClean.
+#[derive(Parser, Debug)]
+#[command(name = "h5o3-regions")]
+#[command(author = "Frits Sweijen")]
+#[command(version = "0.0.0")]
+#[command(
+    help_template = "{name} \nVersion: {version} \nAuthor: {author}\n{about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+)]
+struct Args {
+    /// H5parm to read the directions from.
+    #[arg(long)]
+    h5parm: String,
+    /// SolSet containing the source table.
+    #[arg(long, default_value = "sol000")]
+    solset: String,
+    /// SolTab whose direction order is used.
+    #[arg(long)]
+    soltab: String,
+    /// Output region file.
+    #[arg(long)]
+    out: String,
+    /// Write Voronoi facets within the bounding box given as RA, Dec, width and height in
+    /// degrees, instead of points.
+    #[arg(long, num_args = 4, value_names = ["RA", "DEC", "WIDTH", "HEIGHT"])]
+    facets: Option<Vec<f64>>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let h5parm = h5o3::H5parm::open(&args.h5parm, true).expect("Failed to read H5parm.");
+    let solset = h5parm
+        .get_solset(args.solset.clone())
+        .expect("Failed to load solset.");
+    let positions: Vec<DirectionPosition> =
+        h5o3::regions::direction_positions(solset, &args.soltab)
+            .expect("Failed to read direction positions.");
+    match args.facets {
+        Some(b) => {
+            let bbox = BoundingBox {
+                ra: b[0].to_radians(),
+                dec: b[1].to_radians(),
+                width: b[2].to_radians(),
+                height: b[3].to_radians(),
+            };
+            h5o3::regions::write_ds9_facets(&args.out, &positions, &bbox)
+                .expect("Failed to write facets.");
+        }
+        None => {
+            h5o3::regions::write_ds9_points(&args.out, &positions)
+                .expect("Failed to write region file.");
+        }
+    }
+    println!("Wrote {} directions to {}.", positions.len(), args.out);
+}
@@ -0,0 +1,323 @@
+// Circular statistics of angles in rad, such as phases.
+//
+// All functions reduce an array along a single axis. Non-finite values are ignored, so flagged
+// samples can be passed as NaN. Lanes without any finite values (or with zero total weight)
+// reduce to NaN.
+
+use anyhow::bail;
+use ndarray::{Array, ArrayBase, ArrayView1, Axis, Data, RemoveAxis};
+use num::complex::Complex;
+use thiserror::Error;
+
+use crate::fit::wrap_phase;
+
+#[derive(Debug, Error)]
+#[error("Axis {0} is out of bounds for an array with {1} dimensions")]
+struct AxisOutOfBoundsError(usize, usize);
+
+#[derive(Debug, Error)]
+#[error("Weights of shape {0:?} do not match values of shape {1:?}")]
+struct WeightShapeError(Vec<usize>, Vec<usize>);
+
+#[derive(Debug, Error)]
+#[error("Weights must be finite and non-negative, found {0}")]
+struct InvalidWeightError(f64);
+
+/// Sum of the unit vectors of the finite angles in `x` scaled by `w`, and the total weight.
+fn resultant(x: ArrayView1<f64>, w: Option<ArrayView1<f64>>) -> (Complex<f64>, f64) {
+    let mut sum = Complex::new(0.0, 0.0);
+    let mut total = 0.0;
+    for (i, theta) in x.iter().enumerate() {
+        let weight = w.map_or(1.0, |w| w[i]);
+        if theta.is_finite() && weight > 0.0 {
+            sum += Complex::from_polar(weight, *theta);
+            total += weight;
+        }
+    }
+    (sum, total)
+}
+
+/// Mean resultant length of a lane, between 0 (uniform) and 1 (all angles equal).
+fn resultant_length(x: ArrayView1<f64>, w: Option<ArrayView1<f64>>) -> f64 {
+    match resultant(x, w) {
+        (_, total) if total <= 0.0 => f64::NAN,
+        // Rounding can push the length just above one for identical angles.
+        (sum, total) => (sum.norm() / total).min(1.0),
+    }
+}
+
+/// Angle of a lane that minimises the (weighted) sum of circular distances to all angles.
+fn lane_median(x: ArrayView1<f64>, w: Option<ArrayView1<f64>>) -> f64 {
+    let samples: Vec<(f64, f64)> = x
+        .iter()
+        .enumerate()
+        .map(|(i, theta)| (*theta, w.map_or(1.0, |w| w[i])))
+        .filter(|(theta, weight)| theta.is_finite() && *weight > 0.0)
+        .collect();
+    let distance = |a: f64| -> f64 {
+        samples
+            .iter()
+            .map(|(theta, weight)| weight * wrap_phase(theta - a).abs())
+            .sum()
+    };
+    samples
+        .iter()
+        .map(|(theta, _)| (*theta, distance(*theta)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(f64::NAN, |(theta, _)| wrap_phase(theta))
+}
+
+/// Apply `f` to every lane of `x` (and `w`) along `axis`.
+fn reduce<S, T, D, F>(
+    x: &ArrayBase<S, D>,
+    w: Option<&ArrayBase<T, D>>,
+    axis: Axis,
+    f: F,
+) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    T: Data<Elem = f64>,
+    D: RemoveAxis,
+    F: Fn(ArrayView1<f64>, Option<ArrayView1<f64>>) -> f64,
+{
+    if axis.index() >= x.ndim() {
+        bail!(AxisOutOfBoundsError(axis.index(), x.ndim()));
+    }
+    let w = match w {
+        Some(w) if w.shape() != x.shape() => {
+            bail!(WeightShapeError(w.shape().to_vec(), x.shape().to_vec()))
+        }
+        Some(w) => {
+            if let Some(bad) = w.iter().find(|v| !v.is_finite() || **v < 0.0) {
+                bail!(InvalidWeightError(*bad));
+            }
+            w
+        }
+        None => return Ok(x.map_axis(axis, |lane| f(lane, None))),
+    };
+    let lanes: Vec<f64> = x
+        .lanes(axis)
+        .into_iter()
+        .zip(w.lanes(axis))
+        .map(|(x, w)| f(x, Some(w)))
+        .collect();
+    Ok(Array::from_shape_vec(x.raw_dim().remove_axis(axis), lanes)?)
+}
+
+/// Circular mean in the interval [-pi, pi] along `axis`.
+pub fn mean<S, D>(x: &ArrayBase<S, D>, axis: Axis) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce::<S, S, D, _>(x, None, axis, |x, w| match resultant(x, w) {
+        (_, total) if total <= 0.0 => f64::NAN,
+        (sum, _) => sum.arg(),
+    })
+}
+
+/// Circular variance `1 - R` along `axis`, where `R` is the mean resultant length.
+pub fn var<S, D>(x: &ArrayBase<S, D>, axis: Axis) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce::<S, S, D, _>(x, None, axis, |x, w| 1.0 - resultant_length(x, w))
+}
+
+/// Circular standard deviation `sqrt(-2 ln R)` along `axis`, where `R` is the mean resultant
+/// length. See e.g. `scipy.stats.circstd`.
+pub fn std<S, D>(x: &ArrayBase<S, D>, axis: Axis) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce::<S, S, D, _>(x, None, axis, |x, w| {
+        (-2.0 * resultant_length(x, w).ln()).sqrt()
+    })
+}
+
+/// Circular median along `axis`: the sample that minimises the sum of circular distances to all
+/// other samples.
+pub fn median<S, D>(
+    x: &ArrayBase<S, D>,
+    axis: Axis,
+) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce::<S, S, D, _>(x, None, axis, lane_median)
+}
+
+/// Weighted circular mean along `axis`. Weights must have the same shape as `x`, be finite and
+/// be non-negative.
+pub fn weighted_mean<S, T, D>(
+    x: &ArrayBase<S, D>,
+    w: &ArrayBase<T, D>,
+    axis: Axis,
+) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    T: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce(x, Some(w), axis, |x, w| match resultant(x, w) {
+        (_, total) if total <= 0.0 => f64::NAN,
+        (sum, _) => sum.arg(),
+    })
+}
+
+/// Weighted circular variance along `axis`. See [`weighted_mean`] for the weights.
+pub fn weighted_var<S, T, D>(
+    x: &ArrayBase<S, D>,
+    w: &ArrayBase<T, D>,
+    axis: Axis,
+) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    T: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce(x, Some(w), axis, |x, w| 1.0 - resultant_length(x, w))
+}
+
+/// Weighted circular standard deviation along `axis`. See [`weighted_mean`] for the weights.
+pub fn weighted_std<S, T, D>(
+    x: &ArrayBase<S, D>,
+    w: &ArrayBase<T, D>,
+    axis: Axis,
+) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    T: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce(x, Some(w), axis, |x, w| {
+        (-2.0 * resultant_length(x, w).ln()).sqrt()
+    })
+}
+
+/// Weighted circular median along `axis`. See [`weighted_mean`] for the weights.
+pub fn weighted_median<S, T, D>(
+    x: &ArrayBase<S, D>,
+    w: &ArrayBase<T, D>,
+    axis: Axis,
+) -> Result<Array<f64, D::Smaller>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    T: Data<Elem = f64>,
+    D: RemoveAxis,
+{
+    reduce(x, Some(w), axis, lane_median)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use ndarray::{array, Array1};
+
+    use super::*;
+
+    /// Whether two angles are equal up to a multiple of 2 pi.
+    fn same_angle(a: f64, b: f64) -> bool {
+        wrap_phase(a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn mean_across_wrap() {
+        let x = array![PI - 0.2, -PI + 0.1];
+        let m = mean(&x, Axis(0)).unwrap().into_scalar();
+        assert!(same_angle(m, PI - 0.05));
+        assert!((-PI..=PI).contains(&m));
+        let m = mean(&array![PI - 0.1, -PI + 0.1], Axis(0))
+            .unwrap()
+            .into_scalar();
+        assert!((m.abs() - PI).abs() < 1e-12);
+    }
+
+    #[test]
+    fn spread_across_wrap() {
+        // Angles close together on either side of the wrap have a small spread.
+        let x = array![PI - 0.1, -PI + 0.1];
+        assert!((var(&x, Axis(0)).unwrap().into_scalar() - (1.0 - 0.1f64.cos())).abs() < 1e-12);
+        let expected = (-2.0 * 0.1f64.cos().ln()).sqrt();
+        assert!((std(&x, Axis(0)).unwrap().into_scalar() - expected).abs() < 1e-12);
+
+        let identical = array![PI, -PI, 3.0 * PI];
+        assert!(var(&identical, Axis(0)).unwrap().into_scalar().abs() < 1e-12);
+        assert!(std(&identical, Axis(0)).unwrap().into_scalar().abs() < 1e-7);
+
+        let opposite = array![0.0, PI];
+        assert!((var(&opposite, Axis(0)).unwrap().into_scalar() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn median_across_wrap() {
+        let x = array![3.0, -3.0, 3.1];
+        assert!(same_angle(median(&x, Axis(0)).unwrap().into_scalar(), 3.1));
+        let x = array![PI - 0.1, -PI + 0.05, -PI + 0.2, 1.0];
+        assert!(same_angle(
+            median(&x, Axis(0)).unwrap().into_scalar(),
+            -PI + 0.05
+        ));
+    }
+
+    #[test]
+    fn non_finite_values_are_ignored() {
+        let x = array![[0.5, f64::NAN, 0.5], [f64::NAN, f64::NAN, f64::INFINITY]];
+        let m = mean(&x, Axis(1)).unwrap();
+        assert!((m[0] - 0.5).abs() < 1e-12);
+        assert!(m[1].is_nan());
+        let v = var(&x, Axis(1)).unwrap();
+        assert!(v[0].abs() < 1e-12);
+        assert!(v[1].is_nan());
+        let md = median(&x, Axis(1)).unwrap();
+        assert_eq!(md[0], 0.5);
+        assert!(md[1].is_nan());
+    }
+
+    #[test]
+    fn reduce_along_axis() {
+        let x = array![[PI - 0.1, 0.0], [-PI + 0.1, 0.2]];
+        let m = mean(&x, Axis(0)).unwrap();
+        assert_eq!(m.shape(), &[2]);
+        assert!((m[0].abs() - PI).abs() < 1e-12);
+        assert!((m[1] - 0.1).abs() < 1e-12);
+        let m = mean(&x, Axis(1)).unwrap();
+        assert!(same_angle(m[0], PI / 2.0 - 0.05));
+        assert!(same_angle(m[1], PI / 2.0 + 0.15));
+        assert!(mean(&x, Axis(2)).is_err());
+    }
+
+    #[test]
+    fn weights() {
+        let x = array![PI - 0.1, -PI + 0.1, 0.0];
+        let w = array![1.0, 1.0, 0.0];
+        // The zero weight removes the angle at 0 from all statistics.
+        let m = weighted_mean(&x, &w, Axis(0)).unwrap().into_scalar();
+        assert!((m.abs() - PI).abs() < 1e-12);
+        let v = weighted_var(&x, &w, Axis(0)).unwrap().into_scalar();
+        assert!((v - (1.0 - 0.1f64.cos())).abs() < 1e-12);
+        let s = weighted_std(&x, &w, Axis(0)).unwrap().into_scalar();
+        assert!((s - (-2.0 * 0.1f64.cos().ln()).sqrt()).abs() < 1e-12);
+        let md = weighted_median(&x, &w, Axis(0)).unwrap().into_scalar();
+        assert!(same_angle(md, PI - 0.1) || same_angle(md, -PI + 0.1));
+
+        let w = array![3.0, 1.0, 0.0];
+        let md = weighted_median(&x, &w, Axis(0)).unwrap().into_scalar();
+        assert!(same_angle(md, PI - 0.1));
+        let m = weighted_mean(&x, &w, Axis(0)).unwrap().into_scalar();
+        assert!(wrap_phase(m - PI) < 0.0 && wrap_phase(m - PI) > -0.1);
+
+        let zero = Array1::<f64>::zeros(3);
+        assert!(weighted_mean(&x, &zero, Axis(0))
+            .unwrap()
+            .into_scalar()
+            .is_nan());
+        assert!(weighted_mean(&x, &array![1.0, 1.0], Axis(0)).is_err());
+        assert!(weighted_mean(&x, &array![1.0, -1.0, 1.0], Axis(0)).is_err());
+        assert!(weighted_var(&x, &array![1.0, f64::NAN, 1.0], Axis(0)).is_err());
+    }
+}
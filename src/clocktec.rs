@@ -0,0 +1,353 @@
+// Clock/TEC separation of phase solutions.
+
+use anyhow::bail;
+use ndarray::parallel::prelude::*;
+use ndarray::{Array2, ArrayD, IxDyn};
+use num::complex::Complex;
+use thiserror::Error;
+
+use crate::convert::TEC_TO_PHASE;
+use crate::fit::{move_axis_last, weighted_lstsq, wrap_phase};
+use crate::{AxisValues, SolSet, SolTabData, SolTabKind};
+
+/// Scaling of the third-order TEC term, i.e. its phase contribution is `tec3rd * 1e21 / freq^3`.
+pub const TEC3RD_TO_PHASE: f64 = 1e21;
+
+/// Number of local maxima of the grid search that are refined.
+const MAX_CANDIDATES: usize = 5;
+
+#[derive(Debug, Error)]
+#[error("Clock/TEC separation requires a phase SolTab with a freq axis, but {0} is not.")]
+struct NotAPhaseSoltabError(String);
+
+/// Settings for the clock/TEC separation.
+#[derive(Debug, Clone)]
+pub struct ClockTecOptions {
+    /// Range of clock values in s searched for the initial guess.
+    pub clock_range: (f64, f64),
+    /// Grid spacing of the clock search in s.
+    pub clock_step: f64,
+    /// Range of TEC values in TECU searched for the initial guess.
+    pub tec_range: (f64, f64),
+    /// Grid spacing of the TEC search in TECU.
+    pub tec_step: f64,
+    /// Also fit a third-order TEC term.
+    pub fit_tec3rd: bool,
+    /// Maximum number of iterations used to refine the initial guess.
+    pub max_iterations: usize,
+    /// Name of the output clock SolTab.
+    pub clock_soltab: String,
+    /// Name of the output TEC SolTab.
+    pub tec_soltab: String,
+    /// Name of the output third-order TEC SolTab, if fitted.
+    pub tec3rd_soltab: String,
+}
+
+impl Default for ClockTecOptions {
+    fn default() -> Self {
+        ClockTecOptions {
+            clock_range: (-1e-7, 1e-7),
+            clock_step: 1e-9,
+            tec_range: (-0.2, 0.2),
+            tec_step: 0.005,
+            fit_tec3rd: false,
+            max_iterations: 20,
+            clock_soltab: "clock000".to_string(),
+            tec_soltab: "tec000".to_string(),
+            tec3rd_soltab: "tec3rd000".to_string(),
+        }
+    }
+}
+
+/// Result of a clock/TEC fit to the phases of a single station, time, direction and polarisation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockTecSolution {
+    /// Clock delay in s.
+    pub clock: f64,
+    /// Differential TEC in TECU.
+    pub tec: f64,
+    /// Third-order TEC term, zero if not fitted.
+    pub tec3rd: f64,
+    /// Weighted RMS of the wrapped phase residuals in rad.
+    pub rms: f64,
+}
+
+/// Fit `phase = 2 pi freq clock + TEC_TO_PHASE tec / freq [+ TEC3RD_TO_PHASE tec3rd / freq^3]` to
+/// the given phases.
+///
+/// Initial guesses are found by a grid search over clock and TEC maximising the phase coherence.
+/// The strongest local maxima are refined by iterative least squares on the wrapped residuals and
+/// the refined solution with the smallest residuals is returned. Channels with zero
+/// weight or non-finite phases are ignored. Returns `None` if too few channels remain.
+pub fn fit_clock_tec(
+    freqs: &[f64],
+    phases: &[f64],
+    weights: &[f64],
+    opts: &ClockTecOptions,
+) -> Option<ClockTecSolution> {
+    let valid: Vec<usize> = (0..freqs.len())
+        .filter(|&i| weights[i] > 0.0 && phases[i].is_finite())
+        .collect();
+    let nparams = if opts.fit_tec3rd { 3 } else { 2 };
+    if valid.len() <= nparams {
+        return None;
+    }
+    let nu: Vec<f64> = valid.iter().map(|&i| freqs[i]).collect();
+    let phi: Vec<f64> = valid.iter().map(|&i| phases[i]).collect();
+    let w: Vec<f64> = valid.iter().map(|&i| weights[i]).collect();
+
+    // Grid search for the coherence of every combination.
+    let nclock = ((opts.clock_range.1 - opts.clock_range.0) / opts.clock_step).floor() as usize + 1;
+    let ntec = ((opts.tec_range.1 - opts.tec_range.0) / opts.tec_step).floor() as usize + 1;
+    let clock_terms: Vec<Vec<Complex<f64>>> = (0..nclock)
+        .map(|k| {
+            let c = opts.clock_range.0 + k as f64 * opts.clock_step;
+            nu.iter()
+                .map(|f| Complex::from_polar(1.0, -2.0 * std::f64::consts::PI * f * c))
+                .collect()
+        })
+        .collect();
+    let mut coherence = Array2::<f64>::zeros((ntec, nclock));
+    for j in 0..ntec {
+        let tec = opts.tec_range.0 + j as f64 * opts.tec_step;
+        let y: Vec<Complex<f64>> = (0..nu.len())
+            .map(|i| Complex::from_polar(w[i], phi[i] - TEC_TO_PHASE * tec / nu[i]))
+            .collect();
+        for (k, terms) in clock_terms.iter().enumerate() {
+            coherence[[j, k]] = y.iter().zip(terms).map(|(a, b)| (a * b).re).sum();
+        }
+    }
+
+    // Clock and TEC are nearly degenerate over narrow bands: combinations that differ by about a
+    // full turn on every channel fit almost equally well. The true solution can fall between grid
+    // points and lose out to such an alias, so several local maxima are refined and the one with
+    // the smallest residuals is kept.
+    let mut candidates: Vec<(f64, usize, usize)> = vec![];
+    for j in 0..ntec {
+        for k in 0..nclock {
+            let c = coherence[[j, k]];
+            let is_peak = (j.saturating_sub(1)..(j + 2).min(ntec)).all(|jj| {
+                (k.saturating_sub(1)..(k + 2).min(nclock)).all(|kk| coherence[[jj, kk]] <= c)
+            });
+            if is_peak {
+                candidates.push((c, j, k));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    candidates.truncate(MAX_CANDIDATES);
+
+    let design = Array2::from_shape_fn((nu.len(), nparams), |(i, j)| match j {
+        0 => 2.0 * std::f64::consts::PI * nu[i],
+        1 => TEC_TO_PHASE / nu[i],
+        _ => TEC3RD_TO_PHASE / nu[i].powi(3),
+    });
+    let residuals = |p: &[f64]| -> Vec<f64> {
+        (0..nu.len())
+            .map(|i| {
+                let model: f64 = (0..nparams).map(|j| design[[i, j]] * p[j]).sum();
+                wrap_phase(phi[i] - model)
+            })
+            .collect()
+    };
+    let mut best: Option<ClockTecSolution> = None;
+    'candidates: for (_, j, k) in candidates {
+        let mut params = vec![
+            opts.clock_range.0 + k as f64 * opts.clock_step,
+            opts.tec_range.0 + j as f64 * opts.tec_step,
+            0.0,
+        ];
+        for _ in 0..opts.max_iterations {
+            // A singular system for one candidate says nothing about the others.
+            let Some(delta) = weighted_lstsq(&design, &residuals(&params), &w) else {
+                continue 'candidates;
+            };
+            for (p, d) in params.iter_mut().zip(delta.iter()) {
+                *p += d;
+            }
+            if delta[0].abs() < 1e-3 * opts.clock_step && delta[1].abs() < 1e-3 * opts.tec_step {
+                break;
+            }
+        }
+        let r = residuals(&params);
+        let rms =
+            (r.iter().zip(&w).map(|(r, w)| w * r * r).sum::<f64>() / w.iter().sum::<f64>()).sqrt();
+        if best.map_or(true, |b| rms < b.rms) {
+            best = Some(ClockTecSolution {
+                clock: params[0],
+                tec: params[1],
+                tec3rd: params[2],
+                rms,
+            });
+        }
+    }
+    best
+}
+
+/// Separate the phases in SolTab `st_name` into clock and TEC SolTabs in the same solset.
+///
+/// Every station, time, direction and polarisation is fitted independently and in parallel. The
+/// output SolTabs have the axes of the input without `freq`; failed fits are stored as NaN with
+/// zero weight.
+pub fn clocktec(
+    solset: &mut SolSet,
+    st_name: &str,
+    opts: &ClockTecOptions,
+) -> Result<(), anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let axis_names = soltab.get_axes();
+    let freq_axis = match axis_names.iter().position(|a| a == "freq") {
+        Some(i) if matches!(soltab.kind, SolTabKind::Phase | SolTabKind::ScalarPhase) => i,
+        _ => bail!(NotAPhaseSoltabError(st_name.to_string())),
+    };
+    let freqs = soltab.get_frequencies()?.to_vec();
+
+    let (values, out_shape) = move_axis_last(soltab.get_values(), freq_axis)?;
+    let (weights, _) = move_axis_last(soltab.get_weights(), freq_axis)?;
+    let nlanes = values.nrows();
+
+    let solutions: Vec<Option<ClockTecSolution>> = (0..nlanes)
+        .into_par_iter()
+        .map(|i| {
+            fit_clock_tec(
+                &freqs,
+                values.row(i).as_slice().unwrap(),
+                weights.row(i).as_slice().unwrap(),
+                opts,
+            )
+        })
+        .collect();
+
+    let mut axes: Vec<(String, AxisValues)> = vec![];
+    for name in axis_names.iter().filter(|a| *a != "freq") {
+        axes.push((name.clone(), soltab.get_axis_values(name)?));
+    }
+    let out_weights = ArrayD::from_shape_vec(
+        IxDyn(&out_shape),
+        solutions
+            .iter()
+            .map(|s| if s.is_some() { 1.0 } else { 0.0 })
+            .collect(),
+    )?;
+    let mut params = vec![
+        (
+            opts.clock_soltab.clone(),
+            SolTabKind::Clock,
+            (|s: &ClockTecSolution| s.clock) as fn(&ClockTecSolution) -> f64,
+        ),
+        (opts.tec_soltab.clone(), SolTabKind::Tec, |s| s.tec),
+    ];
+    if opts.fit_tec3rd {
+        params.push((opts.tec3rd_soltab.clone(), SolTabKind::Tec3rd, |s| s.tec3rd));
+    }
+    let mut outputs = vec![];
+    for (name, kind, param) in params {
+        let out_values = ArrayD::from_shape_vec(
+            IxDyn(&out_shape),
+            solutions
+                .iter()
+                .map(|s| s.as_ref().map_or(f64::NAN, param))
+                .collect(),
+        )?;
+        let data = SolTabData {
+            kind,
+            axes: axes.clone(),
+            values: out_values,
+            weights: out_weights.clone(),
+        };
+        outputs.push((name, data));
+    }
+    let history = soltab.get_history();
+    // Only write once every output has been computed, so a failure leaves the solset unchanged.
+    solset.create_soltabs(&outputs)?;
+    for (name, _) in outputs.iter() {
+        solset
+            .get_soltab(name.clone())?
+            .add_derived_history(&history, &format!("Fitted clock and TEC to {}", st_name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, freqs, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    fn synthetic_phases(freqs: &[f64], clock: f64, tec: f64) -> Vec<f64> {
+        freqs
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                // Small deterministic noise of up to 0.05 rad.
+                let noise = 0.05 * ((i * 7919) % 13) as f64 / 13.0 - 0.025;
+                wrap_phase(2.0 * std::f64::consts::PI * f * clock + TEC_TO_PHASE * tec / f + noise)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovers_clock_and_tec() {
+        let freqs: Vec<f64> = (0..60).map(|i| 120e6 + i as f64 * 1e6).collect();
+        let weights = vec![1.0; freqs.len()];
+        // (5.3e-9, 0.0) has a strong alias near (1.9e-9, 0.054) over this band.
+        for (clock, tec) in [(5.3e-9, 0.042), (5.3e-9, 0.0), (-2.1e-8, -0.11), (0.0, 0.0)] {
+            let phases = synthetic_phases(&freqs, clock, tec);
+            let fit =
+                fit_clock_tec(&freqs, &phases, &weights, &ClockTecOptions::default()).unwrap();
+            assert!(
+                (fit.clock - clock).abs() < 2e-10,
+                "{} vs {}",
+                fit.clock,
+                clock
+            );
+            assert!((fit.tec - tec).abs() < 2e-3, "{} vs {}", fit.tec, tec);
+            assert!(fit.rms < 0.05);
+        }
+    }
+
+    #[test]
+    fn ignores_flagged_channels() {
+        let freqs: Vec<f64> = (0..40).map(|i| 130e6 + i as f64 * 1e6).collect();
+        let mut phases = synthetic_phases(&freqs, 1e-8, 0.03);
+        let mut weights = vec![1.0; freqs.len()];
+        phases[3] = f64::NAN;
+        phases[10] = 2.5;
+        weights[10] = 0.0;
+        let fit = fit_clock_tec(&freqs, &phases, &weights, &ClockTecOptions::default()).unwrap();
+        assert!((fit.clock - 1e-8).abs() < 2e-10);
+        assert!((fit.tec - 0.03).abs() < 2e-3);
+    }
+
+    #[test]
+    fn too_few_channels() {
+        let freqs = [120e6, 140e6, 160e6];
+        let phases = synthetic_phases(&freqs, 1e-8, 0.03);
+        let opts = ClockTecOptions::default();
+        assert!(fit_clock_tec(&freqs, &phases, &[1.0, 1.0, 0.0], &opts).is_none());
+    }
+
+    #[test]
+    fn existing_output_leaves_solset_unchanged() {
+        let path = temp_h5parm("clocktec_clash");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let phases = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(2)),
+                ("freq", freqs(8)),
+                ("ant", names("CS", 2)),
+            ],
+            |_| 0.0,
+        );
+        solset.create_soltab("phase000", &phases).unwrap();
+        let mut tec = phases.clone();
+        tec.kind = SolTabKind::Tec;
+        solset.create_soltab("tec000", &tec).unwrap();
+        assert!(clocktec(solset, "phase000", &ClockTecOptions::default()).is_err());
+        assert!(!solset.has_soltab("clock000"));
+        assert_eq!(solset.get_soltab_names(), vec!["phase000", "tec000"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+// Concatenation of SolTabs along the time or frequency axis.
+
+use anyhow::bail;
+use ndarray::{Array1, ArrayD, ArrayViewD, Axis};
+use thiserror::Error;
+
+use crate::{AxisValues, H5parm, SolTab, SolTabData};
+
+#[derive(Debug, Error)]
+#[error("Cannot concatenate SolTabs: {0}")]
+struct IncompatibleSoltabsError(String);
+
+#[derive(Debug, Error)]
+#[error("SolTabs overlap at {0} = {1}.")]
+struct OverlapError(String, f64);
+
+#[derive(Debug, Error)]
+#[error("SolTabs overlap between {0} = {1} and {2}.")]
+struct RangeOverlapError(String, f64, f64);
+
+/// How to handle samples with the same coordinate along the concatenation axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Fail if any samples overlap.
+    Reject,
+    /// Keep the sample of the first input in which it occurs.
+    KeepFirst,
+    /// Keep the sample of the last input in which it occurs.
+    KeepLast,
+}
+
+fn same_coordinate(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-12 * a.abs().max(b.abs())
+}
+
+fn axes_agree(a: &AxisValues, b: &AxisValues) -> bool {
+    match (a, b) {
+        (AxisValues::Float(x), AxisValues::Float(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| same_coordinate(*a, *b))
+        }
+        (AxisValues::Text(x), AxisValues::Text(y)) => x == y,
+        _ => false,
+    }
+}
+
+/// Concatenate SolTabs along `axis`, which must be `time` or `freq`.
+///
+/// All inputs must be of the same type and have the same axes, with identical coordinates along
+/// all axes but `axis`. The result is sorted by coordinate along `axis`. With
+/// [`OverlapPolicy::Reject`] the inputs must also cover disjoint ranges along `axis`, so that
+/// interleaved grids are rejected as well.
+pub fn concatenate_soltabs(
+    soltabs: &[&SolTab],
+    axis: &str,
+    overlap: OverlapPolicy,
+) -> Result<SolTabData, anyhow::Error> {
+    if axis != "time" && axis != "freq" {
+        bail!(IncompatibleSoltabsError(format!(
+            "can only concatenate along time or freq, not {}",
+            axis
+        )));
+    }
+    let data = soltabs
+        .iter()
+        .map(|st| st.read_data())
+        .collect::<Result<Vec<SolTabData>, hdf5::Error>>()?;
+    let first = match data.first() {
+        Some(d) => d,
+        None => bail!(IncompatibleSoltabsError("no SolTabs given".to_string())),
+    };
+    let ax = match first.axes.iter().position(|(a, _)| a == axis) {
+        Some(i) => i,
+        None => bail!(IncompatibleSoltabsError(format!("no {} axis", axis))),
+    };
+    for (st, d) in soltabs.iter().zip(data.iter()).skip(1) {
+        if d.kind != first.kind {
+            bail!(IncompatibleSoltabsError(format!(
+                "{} is of type {} instead of {}",
+                st.name, d.kind, first.kind
+            )));
+        }
+        if d.axes.len() != first.axes.len()
+            || d.axes
+                .iter()
+                .zip(first.axes.iter())
+                .any(|((a, _), (b, _))| a != b)
+        {
+            bail!(IncompatibleSoltabsError(format!(
+                "axes of {} differ from the first SolTab",
+                st.name
+            )));
+        }
+        for (i, ((name, coords), (_, first_coords))) in
+            d.axes.iter().zip(first.axes.iter()).enumerate()
+        {
+            if i != ax && !axes_agree(coords, first_coords) {
+                bail!(IncompatibleSoltabsError(format!(
+                    "{} axis of {} differs from the first SolTab",
+                    name, st.name
+                )));
+            }
+        }
+    }
+
+    // Sort all samples by coordinate, keeping the input order for equal coordinates.
+    let mut samples: Vec<(f64, usize, usize)> = vec![];
+    let mut ranges: Vec<(f64, f64)> = vec![];
+    for (input, d) in data.iter().enumerate() {
+        if let AxisValues::Float(x) = &d.axes[ax].1 {
+            samples.extend(x.iter().enumerate().map(|(i, c)| (*c, input, i)));
+            let lowest = x.iter().copied().fold(f64::INFINITY, f64::min);
+            let highest = x.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            if lowest <= highest {
+                ranges.push((lowest, highest));
+            }
+        }
+    }
+    if overlap == OverlapPolicy::Reject {
+        for (i, a) in ranges.iter().enumerate() {
+            for b in &ranges[..i] {
+                if a.0 <= b.1 && b.0 <= a.1 {
+                    bail!(RangeOverlapError(
+                        axis.to_string(),
+                        a.0.max(b.0),
+                        a.1.min(b.1)
+                    ));
+                }
+            }
+        }
+    }
+    samples.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut keep: Vec<(f64, usize, usize)> = vec![];
+    for sample in samples {
+        match keep.last_mut() {
+            Some(last) if same_coordinate(last.0, sample.0) => match overlap {
+                OverlapPolicy::Reject => bail!(OverlapError(axis.to_string(), sample.0)),
+                OverlapPolicy::KeepFirst => {}
+                OverlapPolicy::KeepLast => *last = sample,
+            },
+            _ => keep.push(sample),
+        }
+    }
+
+    let gather = |arrays: Vec<&ArrayD<f64>>| -> Result<ArrayD<f64>, ndarray::ShapeError> {
+        let slices: Vec<ArrayViewD<f64>> = keep
+            .iter()
+            .map(|(_, input, i)| {
+                arrays[*input]
+                    .index_axis(Axis(ax), *i)
+                    .insert_axis(Axis(ax))
+            })
+            .collect();
+        // Concatenation gives arrays in another layout than the C order that HDF5 writes.
+        Ok(ndarray::concatenate(Axis(ax), &slices)?
+            .as_standard_layout()
+            .into_owned())
+    };
+    let mut result = first.clone();
+    result.values = gather(data.iter().map(|d| &d.values).collect())?;
+    result.weights = gather(data.iter().map(|d| &d.weights).collect())?;
+    result.axes[ax].1 = AxisValues::Float(Array1::from_iter(keep.iter().map(|(c, _, _)| *c)));
+    Ok(result)
+}
+
+/// Concatenate the SolTabs of solset `solset` in `h5parms` along `axis` into a new H5parm `out`.
+///
+/// If `soltabs` is `None`, all SolTabs of the solset in the first H5parm are concatenated. The
+/// `antenna` and `source` tables are taken from the first H5parm, and the history of every output
+/// SolTab holds the entries of all its inputs, without repeating identical entries.
+pub fn concatenate(
+    h5parms: &[H5parm],
+    out: &String,
+    solset: &str,
+    soltabs: Option<Vec<String>>,
+    axis: &str,
+    overlap: OverlapPolicy,
+) -> Result<H5parm, anyhow::Error> {
+    let solsets = h5parms
+        .iter()
+        .map(|h| match h.get_solset(solset.to_string()) {
+            Some(ss) => Ok(ss),
+            None => Err(IncompatibleSoltabsError(format!(
+                "{} has no solset {}",
+                h.name, solset
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = match solsets.first() {
+        Some(ss) => ss,
+        None => bail!(IncompatibleSoltabsError("no H5parms given".to_string())),
+    };
+    let soltabs = soltabs.unwrap_or_else(|| first.get_soltab_names());
+
+    let mut concatenated = H5parm::create(out)?;
+    let new_solset = concatenated.create_solset(solset)?;
+    if first.has_table("antenna") {
+        new_solset.set_antenna_table(&first.get_antenna_table()?)?;
+    }
+    if first.has_table("source") {
+        new_solset.set_source_table(&first.get_source_table()?)?;
+    }
+    for st_name in soltabs.iter() {
+        let inputs = solsets
+            .iter()
+            .map(|ss| ss.get_soltab(st_name.clone()))
+            .collect::<Result<Vec<&SolTab>, anyhow::Error>>()?;
+        let data = concatenate_soltabs(&inputs, axis, overlap)?;
+        let mut history: Vec<String> = vec![];
+        for entry in inputs.iter().flat_map(|st| st.get_history()) {
+            if !history.contains(&entry) {
+                history.push(entry);
+            }
+        }
+        let new_soltab = new_solset.create_soltab(st_name, &data)?;
+        for entry in history {
+            new_soltab.write_history_entry(&entry)?;
+        }
+    }
+    concatenated.file.flush()?;
+    Ok(concatenated)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Dimension;
+
+    use super::*;
+    use crate::testing::{digits, fixture, names, temp_h5parm, times};
+    use crate::SolTabKind;
+
+    /// Phases on the given channels in MHz, with the channel number as its first digit.
+    fn phases(channels: &[f64]) -> SolTabData {
+        let freqs = AxisValues::Float(channels.iter().map(|c| c * 1e6).collect());
+        fixture(
+            SolTabKind::Phase,
+            vec![("time", times(2)), ("freq", freqs), ("ant", names("CS", 3))],
+            |i| digits(&[channels[i[1]] as usize, i[0], i[2]]),
+        )
+    }
+
+    #[test]
+    fn concatenate_along_freq() {
+        let path = temp_h5parm("concat");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        solset.create_soltab("high", &phases(&[5.0, 6.0])).unwrap();
+        solset
+            .create_soltab("low", &phases(&[1.0, 2.0, 3.0]))
+            .unwrap();
+        let inputs = [
+            solset.get_soltab("high".to_string()).unwrap(),
+            solset.get_soltab("low".to_string()).unwrap(),
+        ];
+        let data = concatenate_soltabs(&inputs, "freq", OverlapPolicy::Reject).unwrap();
+        assert!(data.values.is_standard_layout());
+        assert_eq!(data.axes, phases(&[1.0, 2.0, 3.0, 5.0, 6.0]).axes);
+        assert_eq!(data.values, phases(&[1.0, 2.0, 3.0, 5.0, 6.0]).values);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        let path = temp_h5parm("concat_overlap");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        solset
+            .create_soltab("even", &phases(&[2.0, 4.0, 6.0]))
+            .unwrap();
+        solset.create_soltab("odd", &phases(&[3.0, 5.0])).unwrap();
+        solset.create_soltab("end", &phases(&[6.0, 7.0])).unwrap();
+        let get = |name: &str| solset.get_soltab(name.to_string()).unwrap();
+
+        // Interleaved grids have no coordinate in common, but still overlap.
+        let interleaved = [get("even"), get("odd")];
+        assert!(concatenate_soltabs(&interleaved, "freq", OverlapPolicy::Reject).is_err());
+        let data = concatenate_soltabs(&interleaved, "freq", OverlapPolicy::KeepFirst).unwrap();
+        assert_eq!(data.values, phases(&[2.0, 3.0, 4.0, 5.0, 6.0]).values);
+
+        let shared = [get("even"), get("end")];
+        assert!(concatenate_soltabs(&shared, "freq", OverlapPolicy::Reject).is_err());
+        let first = concatenate_soltabs(&shared, "freq", OverlapPolicy::KeepFirst).unwrap();
+        let last = concatenate_soltabs(&shared, "freq", OverlapPolicy::KeepLast).unwrap();
+        assert_eq!(first.values.shape(), &[2, 4, 3]);
+        assert_eq!(first.values, last.values);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn concatenate_files_with_history() {
+        let paths: Vec<String> = (0..3)
+            .map(|i| temp_h5parm(&format!("concat_files{}", i)))
+            .collect();
+        let mut h5parms = vec![];
+        for (path, channels) in paths.iter().zip([[1.0, 2.0], [3.0, 4.0]]) {
+            let mut h5 = H5parm::create(path).unwrap();
+            let soltab = h5
+                .create_solset("sol000")
+                .unwrap()
+                .create_soltab("phase000", &phases(&channels))
+                .unwrap();
+            soltab.write_history_entry("solved").unwrap();
+            soltab
+                .write_history_entry(&format!("band {}", channels[0]))
+                .unwrap();
+            h5parms.push(h5);
+        }
+        concatenate(
+            &h5parms,
+            &paths[2],
+            "sol000",
+            None,
+            "freq",
+            OverlapPolicy::Reject,
+        )
+        .unwrap();
+        let out = H5parm::open(&paths[2], true).unwrap();
+        let soltab = out
+            .get_solset("sol000".to_string())
+            .unwrap()
+            .get_soltab("phase000".to_string())
+            .unwrap();
+        assert_eq!(soltab.get_history(), vec!["solved", "band 1", "band 3"]);
+        let values = soltab.get_values();
+        for (i, v) in values.indexed_iter() {
+            let i = i.slice();
+            assert_eq!(*v, digits(&[i[1] + 1, i[0], i[2]]));
+        }
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
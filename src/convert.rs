@@ -0,0 +1,163 @@
+// Conversion of TEC, clock and rotation measure solutions to phases and rotation angles.
+
+use anyhow::bail;
+use ndarray::{Array1, Axis};
+use thiserror::Error;
+
+use crate::{AxisValues, SolSet, SolTab, SolTabData, SolTabKind};
+
+/// Conversion factor from TEC (in TECU) to phase (in rad) at 1 Hz.
+pub const TEC_TO_PHASE: f64 = -8.44797245e9;
+/// Speed of light in m/s.
+pub const SPEED_OF_LIGHT: f64 = 299792458.0;
+
+#[derive(Debug, Error)]
+#[error("Cannot convert SolTab of type {0} to phases or rotation angles!")]
+struct UnsupportedKindError(String);
+
+/// Phase in rad caused by a differential TEC value (in TECU) at frequency `freq` (in Hz).
+pub fn tec_to_phase(tec: f64, freq: f64) -> f64 {
+    TEC_TO_PHASE * tec / freq
+}
+
+/// Phase in rad caused by a clock delay `clock` (in s) at frequency `freq` (in Hz).
+pub fn clock_to_phase(clock: f64, freq: f64) -> f64 {
+    2.0 * std::f64::consts::PI * freq * clock
+}
+
+/// Rotation angle in rad caused by a rotation measure `rm` (in rad/m^2) at frequency `freq` (in Hz).
+pub fn rm_to_rotation(rm: f64, freq: f64) -> f64 {
+    let wavelength = SPEED_OF_LIGHT / freq;
+    rm * wavelength * wavelength
+}
+
+/// Evaluate a `tec`, `clock` or `rotationmeasure` SolTab on the given frequencies.
+///
+/// If the input has a `freq` axis, each output channel uses the nearest input frequency.
+/// Otherwise a `freq` axis is inserted after the `time` axis.
+pub fn evaluate_on_frequencies(
+    soltab: &SolTab,
+    freqs: &Array1<f64>,
+) -> Result<SolTabData, anyhow::Error> {
+    let (kind, convert): (SolTabKind, fn(f64, f64) -> f64) = match soltab.kind {
+        SolTabKind::Tec => (SolTabKind::Phase, tec_to_phase),
+        SolTabKind::Clock => (SolTabKind::Phase, clock_to_phase),
+        SolTabKind::RotationMeasure => (SolTabKind::Rotation, rm_to_rotation),
+        _ => bail!(UnsupportedKindError(soltab.kind.to_string())),
+    };
+
+    let axis_names = soltab.get_axes();
+    let mut values = soltab.get_values();
+    let mut weights = soltab.get_weights();
+    let freq_axis = match axis_names.iter().position(|a| a == "freq") {
+        Some(i) => {
+            let input_freqs = soltab.get_frequencies()?;
+            let nearest: Vec<usize> = freqs
+                .iter()
+                .map(|f| {
+                    input_freqs
+                        .iter()
+                        .enumerate()
+                        .min_by(|a, b| (a.1 - f).abs().total_cmp(&(b.1 - f).abs()))
+                        .map(|(j, _)| j)
+                        .unwrap_or(0)
+                })
+                .collect();
+            values = values.select(Axis(i), &nearest);
+            weights = weights.select(Axis(i), &nearest);
+            i
+        }
+        None => {
+            let i = axis_names
+                .iter()
+                .position(|a| a == "time")
+                .map_or(0, |t| t + 1);
+            let broadcast = vec![0; freqs.len()];
+            values = values.insert_axis(Axis(i)).select(Axis(i), &broadcast);
+            weights = weights.insert_axis(Axis(i)).select(Axis(i), &broadcast);
+            i
+        }
+    };
+    for (f, mut lane) in freqs.iter().zip(values.axis_iter_mut(Axis(freq_axis))) {
+        lane.mapv_inplace(|x| convert(x, *f));
+    }
+
+    let mut axes: Vec<(String, AxisValues)> = vec![];
+    for name in axis_names.iter().filter(|a| *a != "freq") {
+        axes.push((name.clone(), soltab.get_axis_values(name)?));
+    }
+    axes.insert(
+        freq_axis,
+        ("freq".to_string(), AxisValues::Float(freqs.clone())),
+    );
+    Ok(SolTabData {
+        kind,
+        axes,
+        values,
+        weights,
+    })
+}
+
+/// Convert the `tec`, `clock` or `rotationmeasure` SolTab `st_name` to a new phase or rotation
+/// SolTab `out_name` on the given frequency axis.
+pub fn convert_soltab<'a>(
+    solset: &'a mut SolSet,
+    st_name: &str,
+    out_name: &str,
+    freqs: &Array1<f64>,
+) -> Result<&'a SolTab, anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let data = evaluate_on_frequencies(soltab, freqs)?;
+    let history = soltab.get_history();
+    let converted = solset.create_soltab(out_name, &data)?;
+    converted.add_derived_history(
+        &history,
+        &format!(
+            "Converted {} to {} on {} channels",
+            st_name,
+            data.kind,
+            freqs.len()
+        ),
+    )?;
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, freqs, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    #[test]
+    fn convert_tec_with_freq_axis() {
+        let path = temp_h5parm("convert_tec");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Tec,
+            vec![
+                ("time", times(2)),
+                ("freq", freqs(3)),
+                ("ant", names("CS", 2)),
+            ],
+            |i| 0.01 * (i[0] + i[2]) as f64,
+        );
+        solset.create_soltab("tec000", &data).unwrap();
+        let out_freqs = Array1::from(vec![120e6, 120.390625e6, 120e6, 120.1953125e6]);
+        let phase = convert_soltab(solset, "tec000", "phase000", &out_freqs)
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(phase.kind, SolTabKind::Phase);
+        assert_eq!(phase.values.shape(), &[2, 4, 2]);
+        for t in 0..2 {
+            for (f, freq) in out_freqs.iter().enumerate() {
+                for a in 0..2 {
+                    let expected = tec_to_phase(0.01 * (t + a) as f64, *freq);
+                    assert!((phase.values[[t, f, a]] - expected).abs() < 1e-9);
+                }
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
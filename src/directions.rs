@@ -0,0 +1,362 @@
+// Selection, removal, reordering and renaming of directions in a solset.
+
+use anyhow::bail;
+use hdf5::types::FixedAscii;
+use ndarray::Axis;
+use thiserror::Error;
+
+use crate::{AxisValues, SolSet, SolTabData};
+
+#[derive(Debug, Error)]
+#[error("Direction {0} does not exist in {1}.")]
+struct MissingDirectionError(String, String);
+
+#[derive(Debug, Error)]
+#[error("Direction {0} occurs more than once.")]
+struct DuplicateDirectionError(String);
+
+#[derive(Debug, Error)]
+#[error("SolSet {0} has no SolTabs with a dir axis.")]
+struct NoDirectionAxisError(String);
+
+/// Whether direction names `a` and `b` are the same, ignoring the surrounding brackets.
+pub fn same_direction(a: &str, b: &str) -> bool {
+    a.trim_start_matches('[').trim_end_matches(']')
+        == b.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// Direction names of the first SolTab in `solset` that has a `dir` axis.
+pub fn get_direction_names(solset: &SolSet) -> Result<Vec<String>, anyhow::Error> {
+    match solset
+        .get_soltabs()
+        .iter()
+        .find(|st| st.get_axes().iter().any(|a| a == "dir"))
+    {
+        Some(st) => Ok(st.get_direction_names()?),
+        None => bail!(NoDirectionAxisError(solset.name.clone())),
+    }
+}
+
+fn find_direction(names: &[String], name: &str, location: &str) -> Result<usize, anyhow::Error> {
+    match names.iter().position(|n| same_direction(n, name)) {
+        Some(i) => Ok(i),
+        None => bail!(MissingDirectionError(
+            name.to_string(),
+            location.to_string()
+        )),
+    }
+}
+
+/// Unused SolTab name derived from `name`, to write a rewritten SolTab to before it replaces the
+/// original.
+fn temporary_name(solset: &SolSet, name: &str) -> String {
+    let mut tmp = format!("{}_tmp", name);
+    while solset.has_soltab(&tmp) {
+        tmp.push('_');
+    }
+    tmp
+}
+
+/// Rewrite the `dir` axis of every SolTab and the `source` table of `solset`.
+///
+/// Every entry of `plan` is the name of an existing direction and the name it gets in the output,
+/// in output order. Directions not in `plan` are removed. The rewritten SolTabs are first written
+/// under temporary names and only replace the originals once all of them were written, so that
+/// a failure leaves the solset unchanged.
+fn apply_plan(
+    solset: &mut SolSet,
+    plan: &[(String, String)],
+    description: &str,
+) -> Result<(), anyhow::Error> {
+    for (i, (_, new)) in plan.iter().enumerate() {
+        if plan[..i].iter().any(|(_, n)| same_direction(n, new)) {
+            bail!(DuplicateDirectionError(new.clone()));
+        }
+    }
+
+    let new_sources = match solset.has_table("source") {
+        true => {
+            let sources = solset.get_source_table()?;
+            let names: Vec<String> = sources.iter().map(|s| s.name.to_string()).collect();
+            let mut new_sources = vec![];
+            for (old, new) in plan.iter() {
+                let i = find_direction(&names, old, "the source table")?;
+                let mut entry = sources[i].clone();
+                entry.name = FixedAscii::<128>::from_ascii(new)?;
+                new_sources.push(entry);
+            }
+            Some(new_sources)
+        }
+        false => None,
+    };
+
+    let names = solset.get_soltab_names();
+    let mut rewritten: Vec<(String, String)> = vec![];
+    for st_name in names.iter() {
+        let tmp = temporary_name(solset, st_name);
+        match rewrite_soltab(solset, st_name, &tmp, plan, description) {
+            Ok(true) => rewritten.push((st_name.clone(), tmp)),
+            Ok(false) => {}
+            Err(e) => {
+                for (_, written) in rewritten.iter() {
+                    solset.delete_soltab(written)?;
+                }
+                if solset.has_soltab(&tmp) {
+                    solset.delete_soltab(&tmp)?;
+                }
+                return Err(e);
+            }
+        }
+    }
+    for (original, tmp) in rewritten.iter() {
+        solset.delete_soltab(original)?;
+        solset.rename_soltab(tmp, original)?;
+    }
+    // Renamed SolTabs were appended, so restore the original order.
+    solset
+        .soltabs
+        .sort_by_key(|st| names.iter().position(|n| *n == st.name));
+    if let Some(sources) = new_sources {
+        solset.set_source_table(&sources)?;
+    }
+    Ok(())
+}
+
+/// Write SolTab `st_name` with the directions of `plan` as a new SolTab `out_name`, including its
+/// history. Returns `false` if the SolTab has no `dir` axis and nothing was written.
+fn rewrite_soltab(
+    solset: &mut SolSet,
+    st_name: &str,
+    out_name: &str,
+    plan: &[(String, String)],
+    description: &str,
+) -> Result<bool, anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let data = soltab.read_data()?;
+    let ax = match data.axes.iter().position(|(a, _)| a == "dir") {
+        Some(i) => i,
+        None => return Ok(false),
+    };
+    let dirs = soltab.get_direction_names()?;
+    let indices = plan
+        .iter()
+        .map(|(old, _)| find_direction(&dirs, old, st_name))
+        .collect::<Result<Vec<usize>, _>>()?;
+    let mut axes = data.axes.clone();
+    axes[ax].1 = AxisValues::Text(plan.iter().map(|(_, new)| new.clone()).collect());
+    let new_data = SolTabData {
+        kind: data.kind.clone(),
+        axes,
+        values: data
+            .values
+            .select(Axis(ax), &indices)
+            .as_standard_layout()
+            .into_owned(),
+        weights: data
+            .weights
+            .select(Axis(ax), &indices)
+            .as_standard_layout()
+            .into_owned(),
+    };
+
+    let history = soltab.get_history();
+    let new_soltab = solset.create_soltab(out_name, &new_data)?;
+    for entry in history {
+        new_soltab.write_history_entry(&entry)?;
+    }
+    new_soltab.add_history(description)?;
+    Ok(true)
+}
+
+/// Keep only directions `names` in all SolTabs of `solset` and the `source` table, in the given
+/// order. This can also be used to reorder directions.
+pub fn select_directions(solset: &mut SolSet, names: &[String]) -> Result<(), anyhow::Error> {
+    let existing = get_direction_names(solset)?;
+    let plan = names
+        .iter()
+        .map(|n| {
+            let i = find_direction(&existing, n, &solset.name)?;
+            Ok((existing[i].clone(), existing[i].clone()))
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    apply_plan(
+        solset,
+        &plan,
+        &format!("Selected directions {}", names.join(",")),
+    )
+}
+
+/// Remove directions `names` from all SolTabs of `solset` and the `source` table.
+pub fn drop_directions(solset: &mut SolSet, names: &[String]) -> Result<(), anyhow::Error> {
+    let existing = get_direction_names(solset)?;
+    for n in names.iter() {
+        find_direction(&existing, n, &solset.name)?;
+    }
+    let plan: Vec<(String, String)> = existing
+        .iter()
+        .filter(|e| !names.iter().any(|n| same_direction(e, n)))
+        .map(|e| (e.clone(), e.clone()))
+        .collect();
+    apply_plan(
+        solset,
+        &plan,
+        &format!("Dropped directions {}", names.join(",")),
+    )
+}
+
+/// Rename directions in all SolTabs of `solset` and the `source` table. Every entry of `renames`
+/// holds the old and the new name of a direction.
+pub fn rename_directions(
+    solset: &mut SolSet,
+    renames: &[(String, String)],
+) -> Result<(), anyhow::Error> {
+    let existing = get_direction_names(solset)?;
+    for (old, _) in renames.iter() {
+        find_direction(&existing, old, &solset.name)?;
+    }
+    let plan: Vec<(String, String)> = existing
+        .iter()
+        .map(
+            |e| match renames.iter().find(|(old, _)| same_direction(e, old)) {
+                Some((_, new)) => (e.clone(), new.clone()),
+                None => (e.clone(), e.clone()),
+            },
+        )
+        .collect();
+    let description = renames
+        .iter()
+        .map(|(old, new)| format!("{} to {}", old, new))
+        .collect::<Vec<String>>()
+        .join(", ");
+    apply_plan(
+        solset,
+        &plan,
+        &format!("Renamed directions {}", description),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Dimension;
+
+    use super::*;
+    use crate::testing::{digits, fixture, names, temp_h5parm, times};
+    use crate::{H5parm, SolTabKind, SourceEntry};
+
+    fn patches(patches: &[usize]) -> AxisValues {
+        AxisValues::Text(patches.iter().map(|i| format!("[Patch_{}]", i)).collect())
+    }
+
+    /// A solset with a source table of three directions, a phase SolTab with all of them, an
+    /// amplitude SolTab without a dir axis and a TEC SolTab with the first two directions only.
+    fn create_solset(h5: &mut H5parm) -> &mut SolSet {
+        let solset = h5.create_solset("sol000").unwrap();
+        let sources: Vec<SourceEntry> = (0..3)
+            .map(|i| SourceEntry {
+                name: FixedAscii::from_ascii(&format!("[Patch_{}]", i)).unwrap(),
+                dir: [i as f64, 0.5],
+            })
+            .collect();
+        solset.set_source_table(&sources).unwrap();
+        let phases = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(2)),
+                ("dir", patches(&[0, 1, 2])),
+                ("ant", names("CS", 2)),
+            ],
+            digits,
+        );
+        solset
+            .create_soltab("phase000", &phases)
+            .unwrap()
+            .write_history_entry("solved")
+            .unwrap();
+        let amplitudes = fixture(
+            SolTabKind::Amplitude,
+            vec![("time", times(2)), ("ant", names("CS", 2))],
+            digits,
+        );
+        solset.create_soltab("amplitude000", &amplitudes).unwrap();
+        let mut tec = phases.clone();
+        tec.kind = SolTabKind::Tec;
+        tec.axes[1].1 = patches(&[0, 1]);
+        tec.values = tec.values.slice_axis(Axis(1), (0..2).into()).to_owned();
+        tec.weights = tec.weights.slice_axis(Axis(1), (0..2).into()).to_owned();
+        solset.create_soltab("tec000", &tec).unwrap();
+        solset
+    }
+
+    fn source_names(solset: &SolSet) -> Vec<String> {
+        solset
+            .get_source_table()
+            .unwrap()
+            .iter()
+            .map(|s| s.name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn select_and_drop_directions() {
+        let path = temp_h5parm("directions");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = create_solset(&mut h5);
+        let order = vec!["phase000", "amplitude000", "tec000"];
+
+        select_directions(solset, &["Patch_1".to_string(), "[Patch_0]".to_string()]).unwrap();
+        assert_eq!(solset.get_soltab_names(), order);
+        assert_eq!(source_names(solset), vec!["[Patch_1]", "[Patch_0]"]);
+        let phases = solset.get_soltab("phase000".to_string()).unwrap();
+        let read = phases.read_data().unwrap();
+        assert_eq!(read.axes[1].1, patches(&[1, 0]));
+        for (i, v) in read.values.indexed_iter() {
+            let i = i.slice();
+            assert_eq!(*v, digits(&[i[0], 1 - i[1], i[2]]));
+        }
+        let history = phases.get_history();
+        assert_eq!(history[0], "solved");
+        assert!(history[1].ends_with("Selected directions Patch_1,[Patch_0]"));
+        let tec = solset.get_soltab("tec000".to_string()).unwrap();
+        assert_eq!(
+            tec.get_direction_names().unwrap(),
+            vec!["[Patch_1]", "[Patch_0]"]
+        );
+
+        drop_directions(solset, &["Patch_1".to_string()]).unwrap();
+        assert_eq!(solset.get_soltab_names(), order);
+        assert_eq!(source_names(solset), vec!["[Patch_0]"]);
+        let read = solset
+            .get_soltab("phase000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(read.values.shape(), &[2, 1, 2]);
+        assert_eq!(read.values[[1, 0, 1]], digits(&[1, 0, 1]));
+
+        rename_directions(solset, &[("Patch_0".to_string(), "[3C196]".to_string())]).unwrap();
+        assert_eq!(source_names(solset), vec!["[3C196]"]);
+        assert_eq!(get_direction_names(solset).unwrap(), vec!["[3C196]"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_selection_leaves_solset_unchanged() {
+        let path = temp_h5parm("directions_failed");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = create_solset(&mut h5);
+        // Patch_2 is missing from tec000, which is only found after phase000 was rewritten.
+        assert!(select_directions(solset, &["Patch_2".to_string()]).is_err());
+        assert_eq!(
+            solset.get_soltab_names(),
+            vec!["phase000", "amplitude000", "tec000"]
+        );
+        assert_eq!(
+            source_names(solset),
+            vec!["[Patch_0]", "[Patch_1]", "[Patch_2]"]
+        );
+        let phases = solset.get_soltab("phase000".to_string()).unwrap();
+        assert_eq!(phases.get_values().shape(), &[2, 3, 2]);
+        assert_eq!(phases.get_history(), vec!["solved"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
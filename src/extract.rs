@@ -0,0 +1,263 @@
+// Extraction of a subset of an H5parm into a new file.
+
+use ndarray::Axis;
+
+use crate::{AxisValues, H5parm, SolTabData};
+
+/// Selection of solsets, SolTabs and axis ranges to extract. Fields left at `None` select
+/// everything.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// Names of the solsets to extract.
+    pub solsets: Option<Vec<String>>,
+    /// Names of the SolTabs to extract from every selected solset.
+    pub soltabs: Option<Vec<String>>,
+    /// Station names or prefixes, e.g. `CS` and `RS` to select only Dutch stations.
+    pub antennas: Option<Vec<String>>,
+    /// Direction names, with or without the surrounding brackets.
+    pub directions: Option<Vec<String>>,
+    /// Polarisations, e.g. `XX` and `YY`.
+    pub polarisations: Option<Vec<String>>,
+    /// Inclusive time range in MJD seconds.
+    pub time_range: Option<(f64, f64)>,
+    /// Inclusive frequency range in Hz.
+    pub freq_range: Option<(f64, f64)>,
+}
+
+impl Selection {
+    fn selects_antenna(&self, name: &str) -> bool {
+        match &self.antennas {
+            Some(patterns) => patterns.iter().any(|p| name.starts_with(p.as_str())),
+            None => true,
+        }
+    }
+
+    fn selects_direction(&self, name: &str) -> bool {
+        match &self.directions {
+            Some(dirs) => {
+                let bare = name.trim_start_matches('[').trim_end_matches(']');
+                dirs.iter().any(|d| d == name || d == bare)
+            }
+            None => true,
+        }
+    }
+
+    fn selects_polarisation(&self, name: &str) -> bool {
+        match &self.polarisations {
+            Some(pols) => pols.iter().any(|p| p == name),
+            None => true,
+        }
+    }
+
+    /// Indices along an axis that are selected.
+    fn indices(&self, axis: &str, coords: &AxisValues) -> Vec<usize> {
+        match coords {
+            AxisValues::Float(x) => {
+                let range = match axis {
+                    "time" => self.time_range,
+                    "freq" => self.freq_range,
+                    _ => None,
+                };
+                (0..x.len())
+                    .filter(|&i| range.map_or(true, |(lo, hi)| x[i] >= lo && x[i] <= hi))
+                    .collect()
+            }
+            AxisValues::Text(x) => (0..x.len())
+                .filter(|&i| match axis {
+                    "ant" => self.selects_antenna(&x[i]),
+                    "dir" => self.selects_direction(&x[i]),
+                    "pol" => self.selects_polarisation(&x[i]),
+                    _ => true,
+                })
+                .collect(),
+        }
+    }
+
+    /// Apply this selection to the axes, values and weights of a SolTab. The selected values and
+    /// weights are in standard layout.
+    pub fn apply(&self, data: &SolTabData) -> SolTabData {
+        let mut selected = data.clone();
+        for (i, (axis, coords)) in data.axes.iter().enumerate() {
+            let indices = self.indices(axis, coords);
+            selected.values = selected.values.select(Axis(i), &indices);
+            selected.weights = selected.weights.select(Axis(i), &indices);
+            selected.axes[i].1 = match coords {
+                AxisValues::Float(x) => AxisValues::Float(x.select(Axis(0), &indices)),
+                AxisValues::Text(x) => {
+                    AxisValues::Text(indices.iter().map(|&j| x[j].clone()).collect())
+                }
+            };
+        }
+        selected.values = selected.values.as_standard_layout().into_owned();
+        selected.weights = selected.weights.as_standard_layout().into_owned();
+        selected
+    }
+}
+
+/// Copy the selected part of `h5parm` to a new H5parm `out`, which must not exist yet.
+///
+/// The `antenna` and `source` tables of every solset are reduced to the selected stations and
+/// directions. Every SolTab keeps its history, followed by an entry that records the extraction.
+pub fn extract(
+    h5parm: &H5parm,
+    out: &String,
+    selection: &Selection,
+) -> Result<H5parm, anyhow::Error> {
+    let mut extracted = H5parm::create(out)?;
+    for solset in h5parm.get_solsets() {
+        if let Some(names) = &selection.solsets {
+            if !names.contains(&solset.name) {
+                continue;
+            }
+        }
+        let new_solset = extracted.create_solset(&solset.name)?;
+        if solset.has_table("antenna") {
+            let antennas: Vec<_> = solset
+                .get_antenna_table()?
+                .into_iter()
+                .filter(|a| selection.selects_antenna(a.name.as_str()))
+                .collect();
+            new_solset.set_antenna_table(&antennas)?;
+        }
+        if solset.has_table("source") {
+            let sources: Vec<_> = solset
+                .get_source_table()?
+                .into_iter()
+                .filter(|s| selection.selects_direction(s.name.as_str()))
+                .collect();
+            new_solset.set_source_table(&sources)?;
+        }
+        for soltab in solset.get_soltabs() {
+            if let Some(names) = &selection.soltabs {
+                if !names.contains(&soltab.name) {
+                    continue;
+                }
+            }
+            let data = selection.apply(&soltab.read_data()?);
+            new_solset
+                .create_soltab(&soltab.name, &data)?
+                .add_derived_history(
+                    &soltab.get_history(),
+                    &format!("Extracted from {}", h5parm.name),
+                )?;
+        }
+    }
+    extracted.file.flush()?;
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use hdf5::types::FixedAscii;
+    use ndarray::Dimension;
+
+    use super::*;
+    use crate::testing::{digits, fixture, freqs, temp_h5parm, times};
+    use crate::{AntennaEntry, SolTabKind, SourceEntry};
+
+    fn directions(patches: &[usize]) -> AxisValues {
+        AxisValues::Text(patches.iter().map(|i| format!("[Patch_{}]", i)).collect())
+    }
+
+    fn soltab_data() -> SolTabData {
+        fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(3)),
+                ("freq", freqs(4)),
+                (
+                    "ant",
+                    AxisValues::Text(vec![
+                        "CS001HBA0".to_string(),
+                        "CS002HBA0".to_string(),
+                        "RS106HBA".to_string(),
+                    ]),
+                ),
+                ("dir", directions(&[0, 1, 2])),
+            ],
+            digits,
+        )
+    }
+
+    fn selection() -> Selection {
+        let f = match freqs(4) {
+            AxisValues::Float(f) => f,
+            AxisValues::Text(_) => unreachable!(),
+        };
+        Selection {
+            antennas: Some(vec!["CS".to_string()]),
+            directions: Some(vec!["Patch_2".to_string(), "[Patch_0]".to_string()]),
+            freq_range: Some((f[1], f[2])),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apply_selects_every_axis() {
+        let data = soltab_data();
+        let selected = selection().apply(&data);
+        assert_eq!(selected.values.shape(), &[3, 2, 2, 2]);
+        assert!(selected.values.is_standard_layout());
+        assert!(selected.weights.is_standard_layout());
+        assert_eq!(selected.axes[3].1, directions(&[0, 2]));
+        for (i, v) in selected.values.indexed_iter() {
+            let i = i.slice();
+            assert_eq!(*v, digits(&[i[0], i[1] + 1, i[2], 2 * i[3]]));
+        }
+    }
+
+    #[test]
+    fn extract_and_read_back() {
+        let path = temp_h5parm("extract_in");
+        let out = temp_h5parm("extract_out");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = soltab_data();
+        let antennas: Vec<AntennaEntry> = ["CS001HBA0", "CS002HBA0", "RS106HBA"]
+            .iter()
+            .map(|n| AntennaEntry {
+                name: FixedAscii::from_ascii(n).unwrap(),
+                position: [0.0; 3],
+            })
+            .collect();
+        solset.set_antenna_table(&antennas).unwrap();
+        let sources: Vec<SourceEntry> = (0..3)
+            .map(|i| SourceEntry {
+                name: FixedAscii::from_ascii(&format!("[Patch_{}]", i)).unwrap(),
+                dir: [i as f64, 0.5],
+            })
+            .collect();
+        solset.set_source_table(&sources).unwrap();
+        solset
+            .create_soltab("phase000", &data)
+            .unwrap()
+            .add_history("created")
+            .unwrap();
+        solset.create_soltab("phase001", &data).unwrap();
+
+        let selection = Selection {
+            soltabs: Some(vec!["phase000".to_string()]),
+            ..selection()
+        };
+        extract(&h5, &out, &selection).unwrap();
+        let extracted = H5parm::open(&out, true).unwrap();
+        let solset = extracted.get_solset("sol000".to_string()).unwrap();
+        assert_eq!(solset.get_soltab_names(), vec!["phase000"]);
+        let soltab = solset.get_soltab("phase000".to_string()).unwrap();
+        let read = soltab.read_data().unwrap();
+        let expected = selection.apply(&data);
+        assert_eq!(read.axes, expected.axes);
+        assert_eq!(read.values, expected.values);
+        let history = soltab.get_history();
+        assert_eq!(history.len(), 2);
+        assert!(history[0].ends_with("created"));
+        assert!(history[1].ends_with(&format!("Extracted from {}", path)));
+        assert_eq!(solset.get_antenna_table().unwrap(), antennas[..2].to_vec());
+        assert_eq!(
+            solset.get_source_table().unwrap(),
+            vec![sources[0].clone(), sources[2].clone()]
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&out).unwrap();
+    }
+}
@@ -0,0 +1,371 @@
+// Faraday rotation fitting of polarisation phase differences.
+
+use anyhow::bail;
+use ndarray::parallel::prelude::*;
+use ndarray::{Array2, ArrayD, Axis, IxDyn};
+use num::complex::Complex;
+use thiserror::Error;
+
+use crate::convert::SPEED_OF_LIGHT;
+use crate::fit::{move_axis_last, restore_axis, weighted_lstsq, wrap_phase};
+use crate::{AxisValues, SolSet, SolTabData, SolTabKind};
+
+#[derive(Debug, Error)]
+#[error("Faraday rotation fitting requires a phase SolTab with freq and pol axes, but {0} is not.")]
+struct NotAPolPhaseSoltabError(String);
+
+#[derive(Debug, Error)]
+#[error("SolTab {0} has no XX and YY or RR and LL polarisations.")]
+struct MissingPolarisationsError(String);
+
+/// Settings for the Faraday rotation fit.
+#[derive(Debug, Clone)]
+pub struct FaradayOptions {
+    /// Range of rotation measures in rad/m^2 searched for the initial guess.
+    pub rm_range: (f64, f64),
+    /// Grid spacing of the rotation measure search in rad/m^2.
+    pub rm_step: f64,
+    /// Also fit a constant phase offset between the polarisations.
+    pub fit_offset: bool,
+    /// Maximum number of iterations used to refine the initial guess.
+    pub max_iterations: usize,
+    /// Name of the output rotation measure SolTab.
+    pub rm_soltab: String,
+    /// Name of the output SolTab holding the phase residuals of the fit, if any.
+    pub residual_soltab: Option<String>,
+}
+
+impl Default for FaradayOptions {
+    fn default() -> Self {
+        FaradayOptions {
+            rm_range: (-5.0, 5.0),
+            rm_step: 0.005,
+            fit_offset: false,
+            max_iterations: 20,
+            rm_soltab: "rotationmeasure000".to_string(),
+            residual_soltab: Some("rotationmeasureresiduals000".to_string()),
+        }
+    }
+}
+
+/// Result of a rotation measure fit to a single phase difference spectrum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaradaySolution {
+    /// Rotation measure in rad/m^2.
+    pub rm: f64,
+    /// Constant phase offset in rad, zero if not fitted.
+    pub offset: f64,
+    /// Wrapped phase residuals in rad for every channel, NaN for ignored channels.
+    pub residuals: Vec<f64>,
+}
+
+/// Fit `phase_diff = 2 RM lambda^2 [+ offset]` to a phase difference spectrum.
+///
+/// The initial guess is found by a grid search maximising the phase coherence and then refined by
+/// iterative least squares on the wrapped residuals. Channels with zero weight or non-finite
+/// phases are ignored. Returns `None` if too few channels remain.
+pub fn fit_rotation_measure(
+    freqs: &[f64],
+    phase_diff: &[f64],
+    weights: &[f64],
+    opts: &FaradayOptions,
+) -> Option<FaradaySolution> {
+    let valid: Vec<usize> = (0..freqs.len())
+        .filter(|&i| weights[i] > 0.0 && phase_diff[i].is_finite())
+        .collect();
+    let nparams = if opts.fit_offset { 2 } else { 1 };
+    if valid.len() <= nparams {
+        return None;
+    }
+    let lambda2: Vec<f64> = valid
+        .iter()
+        .map(|&i| (SPEED_OF_LIGHT / freqs[i]).powi(2))
+        .collect();
+    let phi: Vec<f64> = valid.iter().map(|&i| phase_diff[i]).collect();
+    let w: Vec<f64> = valid.iter().map(|&i| weights[i]).collect();
+
+    let nrm = ((opts.rm_range.1 - opts.rm_range.0) / opts.rm_step).floor() as usize + 1;
+    let mut best = (f64::NEG_INFINITY, 0.0, 0.0);
+    for k in 0..nrm {
+        let rm = opts.rm_range.0 + k as f64 * opts.rm_step;
+        let sum: Complex<f64> = (0..phi.len())
+            .map(|i| Complex::from_polar(w[i], phi[i] - 2.0 * rm * lambda2[i]))
+            .sum();
+        // With a free offset only the coherence matters, not the mean phase.
+        let coherence = if opts.fit_offset { sum.norm() } else { sum.re };
+        if coherence > best.0 {
+            best = (coherence, rm, if opts.fit_offset { sum.arg() } else { 0.0 });
+        }
+    }
+
+    let design = Array2::from_shape_fn((phi.len(), nparams), |(i, j)| match j {
+        0 => 2.0 * lambda2[i],
+        _ => 1.0,
+    });
+    let mut params = vec![best.1, best.2];
+    let residuals = |p: &[f64]| -> Vec<f64> {
+        (0..phi.len())
+            .map(|i| wrap_phase(phi[i] - 2.0 * p[0] * lambda2[i] - p[1]))
+            .collect()
+    };
+    for _ in 0..opts.max_iterations {
+        let delta = weighted_lstsq(&design, &residuals(&params), &w)?;
+        for (p, d) in params.iter_mut().zip(delta.iter()) {
+            *p += d;
+        }
+        if delta[0].abs() < 1e-3 * opts.rm_step {
+            break;
+        }
+    }
+
+    let mut all_residuals = vec![f64::NAN; freqs.len()];
+    for (i, r) in valid.iter().zip(residuals(&params)) {
+        all_residuals[*i] = r;
+    }
+    Some(FaradaySolution {
+        rm: params[0],
+        offset: params[1],
+        residuals: all_residuals,
+    })
+}
+
+/// Fit rotation measures to the XX-YY or RR-LL phase differences of SolTab `st_name`.
+///
+/// Writes a `rotationmeasure` SolTab with the axes of the input without `freq` and `pol`, and
+/// optionally a phase SolTab with the fit residuals on the input axes without `pol`. A channel is
+/// only used if both polarisations have a non-zero weight.
+pub fn fit_faraday(
+    solset: &mut SolSet,
+    st_name: &str,
+    opts: &FaradayOptions,
+) -> Result<(), anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let axis_names = soltab.get_axes();
+    let pol_axis = axis_names.iter().position(|a| a == "pol");
+    let (pol_axis, freq_axis) = match (pol_axis, axis_names.iter().position(|a| a == "freq")) {
+        (Some(p), Some(f)) if matches!(soltab.kind, SolTabKind::Phase) => (p, f),
+        _ => bail!(NotAPolPhaseSoltabError(st_name.to_string())),
+    };
+    let pols: Vec<String> = soltab
+        .get_polarisations()
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+    let find = |name: &str| pols.iter().position(|p| p == name);
+    let (p0, p1) = match (find("XX"), find("YY"), find("RR"), find("LL")) {
+        (Some(xx), Some(yy), _, _) => (xx, yy),
+        (_, _, Some(rr), Some(ll)) => (rr, ll),
+        _ => bail!(MissingPolarisationsError(st_name.to_string())),
+    };
+    let freqs = soltab.get_frequencies()?.to_vec();
+
+    let values = soltab.get_values();
+    let weights = soltab.get_weights();
+    let diff = &values.index_axis(Axis(pol_axis), p0) - &values.index_axis(Axis(pol_axis), p1);
+    let diff_weights =
+        &weights.index_axis(Axis(pol_axis), p0) * &weights.index_axis(Axis(pol_axis), p1);
+    // Removing the pol axis shifts the freq axis if it came after it.
+    let freq_axis = if freq_axis > pol_axis {
+        freq_axis - 1
+    } else {
+        freq_axis
+    };
+    let (diff, out_shape) = move_axis_last(diff, freq_axis)?;
+    let (diff_weights, _) = move_axis_last(diff_weights, freq_axis)?;
+
+    let solutions: Vec<Option<FaradaySolution>> = (0..diff.nrows())
+        .into_par_iter()
+        .map(|i| {
+            fit_rotation_measure(
+                &freqs,
+                diff.row(i).as_slice().unwrap(),
+                diff_weights.row(i).as_slice().unwrap(),
+                opts,
+            )
+        })
+        .collect();
+
+    let mut axes: Vec<(String, AxisValues)> = vec![];
+    for name in axis_names.iter().filter(|a| *a != "pol") {
+        axes.push((name.clone(), soltab.get_axis_values(name)?));
+    }
+    let residual_axes = axes.clone();
+    axes.remove(freq_axis);
+
+    let rm = SolTabData {
+        kind: SolTabKind::RotationMeasure,
+        axes,
+        values: ArrayD::from_shape_vec(
+            IxDyn(&out_shape),
+            solutions
+                .iter()
+                .map(|s| s.as_ref().map_or(f64::NAN, |s| s.rm))
+                .collect(),
+        )?,
+        weights: ArrayD::from_shape_vec(
+            IxDyn(&out_shape),
+            solutions
+                .iter()
+                .map(|s| if s.is_some() { 1.0 } else { 0.0 })
+                .collect(),
+        )?,
+    };
+    let mut outputs = vec![(opts.rm_soltab.clone(), rm)];
+
+    if let Some(name) = &opts.residual_soltab {
+        let mut residuals = Array2::<f64>::from_elem(diff.dim(), f64::NAN);
+        for (mut row, s) in residuals.rows_mut().into_iter().zip(solutions.iter()) {
+            if let Some(s) = s {
+                row.assign(&ndarray::ArrayView1::from(&s.residuals));
+            }
+        }
+        let residual_weights = residuals.mapv(|r| if r.is_finite() { 1.0 } else { 0.0 });
+        let data = SolTabData {
+            kind: SolTabKind::Phase,
+            axes: residual_axes,
+            values: restore_axis(residuals, &out_shape, freq_axis)?,
+            weights: restore_axis(residual_weights, &out_shape, freq_axis)?,
+        };
+        outputs.push((name.clone(), data));
+    }
+    let history = soltab.get_history();
+    // Only write once every output has been computed, so a failure leaves the solset unchanged.
+    solset.create_soltabs(&outputs)?;
+    for (name, _) in outputs.iter() {
+        solset.get_soltab(name.clone())?.add_derived_history(
+            &history,
+            &format!("Fitted rotation measures to {}", st_name),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, freqs, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    fn xx_yy() -> AxisValues {
+        AxisValues::Text(vec!["XX".to_string(), "YY".to_string()])
+    }
+
+    fn channels() -> Vec<f64> {
+        (0..40).map(|i| 120e6 + 1e6 * i as f64).collect()
+    }
+
+    fn rotation(freqs: &[f64], rm: f64, offset: f64) -> Vec<f64> {
+        freqs
+            .iter()
+            .map(|f| wrap_phase(2.0 * rm * (SPEED_OF_LIGHT / f).powi(2) + offset))
+            .collect()
+    }
+
+    #[test]
+    fn recovers_rotation_measure() {
+        let freqs = channels();
+        let weights = vec![1.0; freqs.len()];
+        let s = fit_rotation_measure(
+            &freqs,
+            &rotation(&freqs, 1.234, 0.0),
+            &weights,
+            &FaradayOptions::default(),
+        )
+        .unwrap();
+        assert!((s.rm - 1.234).abs() < 1e-6);
+        assert!(s.residuals.iter().all(|r| r.abs() < 1e-6));
+
+        let opts = FaradayOptions {
+            fit_offset: true,
+            ..Default::default()
+        };
+        let s =
+            fit_rotation_measure(&freqs, &rotation(&freqs, -0.8, 0.5), &weights, &opts).unwrap();
+        assert!((s.rm + 0.8).abs() < 1e-6);
+        assert!((s.offset - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ignores_flagged_channels() {
+        let freqs = channels();
+        let mut phases = rotation(&freqs, 0.3, 0.0);
+        let mut weights = vec![1.0; freqs.len()];
+        phases[3] = 2.0;
+        weights[3] = 0.0;
+        phases[7] = f64::NAN;
+        let s =
+            fit_rotation_measure(&freqs, &phases, &weights, &FaradayOptions::default()).unwrap();
+        assert!((s.rm - 0.3).abs() < 1e-6);
+        assert!(s.residuals[3].is_nan() && s.residuals[7].is_nan());
+        assert!(fit_rotation_measure(
+            &freqs[..1],
+            &phases[..1],
+            &weights[..1],
+            &FaradayOptions::default()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn writes_outputs_with_freq_first() {
+        let path = temp_h5parm("faraday");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let f = match freqs(30) {
+            AxisValues::Float(f) => f.to_vec(),
+            AxisValues::Text(_) => unreachable!(),
+        };
+        let xx = rotation(&f, 0.5, 0.0);
+        let phases = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("freq", freqs(30)),
+                ("pol", xx_yy()),
+                ("time", times(2)),
+                ("ant", names("CS", 3)),
+            ],
+            |i| if i[1] == 0 { xx[i[0]] } else { 0.0 },
+        );
+        solset.create_soltab("phase000", &phases).unwrap();
+
+        fit_faraday(solset, "phase000", &FaradayOptions::default()).unwrap();
+        let rm = solset
+            .get_soltab("rotationmeasure000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(rm.values.shape(), &[2, 3]);
+        assert!(rm.values.iter().all(|v| (v - 0.5).abs() < 1e-6));
+        let residuals = solset
+            .get_soltab("rotationmeasureresiduals000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(residuals.values.shape(), &[30, 2, 3]);
+        assert!(residuals.values.iter().all(|r| r.abs() < 1e-6));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn existing_output_leaves_solset_unchanged() {
+        let path = temp_h5parm("faraday_clash");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let phases = fixture(
+            SolTabKind::Phase,
+            vec![("time", times(2)), ("freq", freqs(8)), ("pol", xx_yy())],
+            |_| 0.0,
+        );
+        solset.create_soltab("phase000", &phases).unwrap();
+        solset
+            .create_soltab("rotationmeasureresiduals000", &phases)
+            .unwrap();
+        assert!(fit_faraday(solset, "phase000", &FaradayOptions::default()).is_err());
+        assert!(!solset.has_soltab("rotationmeasure000"));
+        assert_eq!(
+            solset.get_soltab_names(),
+            vec!["phase000", "rotationmeasureresiduals000"]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
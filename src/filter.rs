@@ -0,0 +1,270 @@
+// Running-median filtering of arrays along an axis.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use anyhow::bail;
+use ndarray::{Array, ArrayBase, ArrayView1, ArrayViewMut1, Axis, Data, Dimension, Zip};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Axis {0} is out of bounds for an array with {1} dimensions")]
+struct AxisOutOfBoundsError(usize, usize);
+
+#[derive(Debug, Error)]
+#[error("Window size must be odd, got {0}")]
+struct WindowSizeError(usize);
+
+/// How samples beyond the edges of the array are filled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Mirror the samples about the edge (`d c b a | a b c d | d c b a`).
+    Reflect,
+    /// Repeat the edge sample (`a a a a | a b c d | d d d d`).
+    Nearest,
+    /// Pad with zeros (`0 0 0 0 | a b c d | 0 0 0 0`).
+    Zero,
+}
+
+/// A sample in the window, ordered by value and then by position so that equal values can be
+/// told apart.
+#[derive(Debug, Clone, Copy)]
+struct Sample(f64, isize);
+
+impl PartialEq for Sample {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Sample {}
+
+impl PartialOrd for Sample {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Sample {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Sorted window split into a lower and upper half, so that samples can be added and removed in
+/// O(log k) time and the median is found at the boundary.
+#[derive(Default)]
+struct Window {
+    low: BTreeSet<Sample>,
+    high: BTreeSet<Sample>,
+}
+
+impl Window {
+    fn insert(&mut self, s: Sample) {
+        match self.low.last() {
+            Some(l) if s <= *l => self.low.insert(s),
+            _ => self.high.insert(s),
+        };
+        self.rebalance();
+    }
+
+    fn remove(&mut self, s: Sample) {
+        if !self.low.remove(&s) {
+            self.high.remove(&s);
+        }
+        self.rebalance();
+    }
+
+    /// Keep the lower half equal in size to or one larger than the upper half.
+    fn rebalance(&mut self) {
+        while self.low.len() > self.high.len() + 1 {
+            let s = self.low.pop_last().unwrap();
+            self.high.insert(s);
+        }
+        while self.high.len() > self.low.len() {
+            let s = self.high.pop_first().unwrap();
+            self.low.insert(s);
+        }
+    }
+
+    fn median(&self) -> f64 {
+        match (self.low.last(), self.high.first()) {
+            (None, _) => f64::NAN,
+            (Some(l), Some(h)) if self.low.len() == self.high.len() => (l.0 + h.0) / 2.0,
+            (Some(l), _) => l.0,
+        }
+    }
+}
+
+/// Value at position `j` of `x` extended beyond its edges according to `mode`.
+fn padded(x: &ArrayView1<f64>, j: isize, mode: EdgeMode) -> f64 {
+    let n = x.len() as isize;
+    match mode {
+        _ if (0..n).contains(&j) => x[j as usize],
+        EdgeMode::Zero => 0.0,
+        EdgeMode::Nearest => x[j.clamp(0, n - 1) as usize],
+        EdgeMode::Reflect => {
+            let m = j.rem_euclid(2 * n);
+            x[if m < n { m } else { 2 * n - 1 - m } as usize]
+        }
+    }
+}
+
+fn filter_lane(x: ArrayView1<f64>, mut out: ArrayViewMut1<f64>, window: usize, mode: EdgeMode) {
+    if x.is_empty() {
+        return;
+    }
+    let half = (window / 2) as isize;
+    let mut w = Window::default();
+    let sample = |j: isize| Sample(padded(&x, j, mode), j);
+    for j in -half..half {
+        let s = sample(j);
+        if s.0.is_finite() {
+            w.insert(s);
+        }
+    }
+    for (i, o) in out.iter_mut().enumerate() {
+        let i = i as isize;
+        let entering = sample(i + half);
+        if entering.0.is_finite() {
+            w.insert(entering);
+        }
+        *o = w.median();
+        let leaving = sample(i - half);
+        if leaving.0.is_finite() {
+            w.remove(leaving);
+        }
+    }
+}
+
+/// Running median of `x` along `axis` over windows of `window` samples, centred on each sample.
+///
+/// Non-finite samples are skipped, so a window only takes the median of its finite samples and
+/// is NaN if it has none. Samples beyond the edges of the array are filled in according to
+/// `mode`. Each lane is filtered in O(n log k) time for n samples and a window of k samples.
+pub fn running_median<S, D>(
+    x: &ArrayBase<S, D>,
+    window: usize,
+    axis: Axis,
+    mode: EdgeMode,
+) -> Result<Array<f64, D>, anyhow::Error>
+where
+    S: Data<Elem = f64>,
+    D: Dimension,
+{
+    if axis.index() >= x.ndim() {
+        bail!(AxisOutOfBoundsError(axis.index(), x.ndim()));
+    }
+    if window % 2 == 0 {
+        bail!(WindowSizeError(window));
+    }
+    let mut out = Array::from_elem(x.raw_dim(), f64::NAN);
+    Zip::from(x.lanes(axis))
+        .and(out.lanes_mut(axis))
+        .for_each(|x, out| filter_lane(x, out, window, mode));
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array1};
+
+    use super::*;
+
+    /// Median of the finite samples in the window around every sample, computed directly.
+    fn brute_force(x: &Array1<f64>, window: usize, mode: EdgeMode) -> Array1<f64> {
+        let half = (window / 2) as isize;
+        Array1::from_iter((0..x.len() as isize).map(|i| {
+            let mut w: Vec<f64> = (i - half..=i + half)
+                .map(|j| padded(&x.view(), j, mode))
+                .filter(|v| v.is_finite())
+                .collect();
+            w.sort_by(|a, b| a.total_cmp(b));
+            match w.len() {
+                0 => f64::NAN,
+                n if n % 2 == 1 => w[n / 2],
+                n => (w[n / 2 - 1] + w[n / 2]) / 2.0,
+            }
+        }))
+    }
+
+    fn same(a: &Array1<f64>, b: &Array1<f64>) -> bool {
+        a.iter()
+            .zip(b.iter())
+            .all(|(a, b)| a == b || (a.is_nan() && b.is_nan()))
+    }
+
+    #[test]
+    fn edge_modes() {
+        let x = array![1.0, 5.0, 2.0, 8.0, 3.0];
+        let filter = |mode| running_median(&x, 5, Axis(0), mode).unwrap();
+        assert_eq!(filter(EdgeMode::Reflect), array![2.0, 2.0, 3.0, 3.0, 3.0]);
+        assert_eq!(filter(EdgeMode::Nearest), array![1.0, 2.0, 3.0, 3.0, 3.0]);
+        assert_eq!(filter(EdgeMode::Zero), array![1.0, 2.0, 3.0, 3.0, 2.0]);
+    }
+
+    #[test]
+    fn odd_windows_match_brute_force() {
+        let x = Array1::from_iter((0..23).map(|i| ((i * 7) % 11) as f64 - 0.5 * (i % 3) as f64));
+        for mode in [EdgeMode::Reflect, EdgeMode::Nearest, EdgeMode::Zero] {
+            // Windows longer than the array reach beyond the reflected copy at the edges.
+            for window in [1, 3, 5, 9, 21, 61] {
+                let filtered = running_median(&x, window, Axis(0), mode).unwrap();
+                assert!(
+                    same(&filtered, &brute_force(&x, window, mode)),
+                    "{:?} {}",
+                    mode,
+                    window
+                );
+            }
+        }
+        assert_eq!(running_median(&x, 1, Axis(0), EdgeMode::Zero).unwrap(), x);
+    }
+
+    #[test]
+    fn even_windows_are_rejected() {
+        let x = array![1.0, 2.0, 3.0];
+        for window in [0, 2, 4] {
+            assert!(running_median(&x, window, Axis(0), EdgeMode::Reflect).is_err());
+        }
+        assert!(running_median(&x, 3, Axis(1), EdgeMode::Reflect).is_err());
+    }
+
+    #[test]
+    fn non_finite_samples_are_skipped() {
+        let x = array![1.0, f64::NAN, 4.0];
+        let filtered = running_median(&x, 3, Axis(0), EdgeMode::Nearest).unwrap();
+        // The middle window has two finite samples, so its median is their mean.
+        assert_eq!(filtered, array![1.0, 2.5, 4.0]);
+
+        let x = array![
+            f64::NAN,
+            2.0,
+            f64::INFINITY,
+            6.0,
+            f64::NAN,
+            f64::NAN,
+            f64::NAN
+        ];
+        for mode in [EdgeMode::Reflect, EdgeMode::Nearest, EdgeMode::Zero] {
+            for window in [1, 3, 5] {
+                let filtered = running_median(&x, window, Axis(0), mode).unwrap();
+                assert!(same(&filtered, &brute_force(&x, window, mode)));
+            }
+        }
+        let filtered = running_median(&x, 1, Axis(0), EdgeMode::Reflect).unwrap();
+        assert!(filtered[0].is_nan() && filtered[2].is_nan());
+        let filtered = running_median(&x, 3, Axis(0), EdgeMode::Nearest).unwrap();
+        assert!(filtered[5].is_nan());
+        assert_eq!(filtered[4], 6.0);
+    }
+
+    #[test]
+    fn filters_each_lane() {
+        let x = array![[1.0, 9.0, 2.0], [7.0, 3.0, 5.0]];
+        let along_rows = running_median(&x, 3, Axis(1), EdgeMode::Nearest).unwrap();
+        assert_eq!(along_rows, array![[1.0, 2.0, 2.0], [7.0, 5.0, 5.0]]);
+        let along_columns = running_median(&x, 3, Axis(0), EdgeMode::Zero).unwrap();
+        assert_eq!(along_columns, array![[1.0, 3.0, 2.0], [1.0, 3.0, 2.0]]);
+    }
+}
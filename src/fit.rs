@@ -0,0 +1,168 @@
+// Small linear least-squares helpers shared by the fitting operations.
+
+use ndarray::{Array1, Array2, ArrayD, IxDyn, ShapeError};
+
+/// Wrap a phase to the interval [-pi, pi).
+pub(crate) fn wrap_phase(x: f64) -> f64 {
+    (x + std::f64::consts::PI).rem_euclid(2.0 * std::f64::consts::PI) - std::f64::consts::PI
+}
+
+/// Move `axis` of `array` to the end and flatten the remaining axes, so that every row holds
+/// e.g. a single spectrum. Also returns the shape of the flattened axes.
+pub(crate) fn move_axis_last(
+    array: ArrayD<f64>,
+    axis: usize,
+) -> Result<(Array2<f64>, Vec<usize>), ShapeError> {
+    let mut perm: Vec<usize> = (0..array.ndim()).filter(|&i| i != axis).collect();
+    perm.push(axis);
+    let array = array.permuted_axes(IxDyn(&perm));
+    let outer: Vec<usize> = array.shape()[..perm.len() - 1].to_vec();
+    let rows = array
+        .as_standard_layout()
+        .into_owned()
+        .into_shape((outer.iter().product(), array.shape()[perm.len() - 1]))?;
+    Ok((rows, outer))
+}
+
+/// Inverse of [`move_axis_last`]: restore rows to an array with the last axis at `axis`. The
+/// result is in standard layout, so that it can be written to an H5parm directly.
+pub(crate) fn restore_axis(
+    rows: Array2<f64>,
+    outer: &[usize],
+    axis: usize,
+) -> Result<ArrayD<f64>, ShapeError> {
+    let mut shape = outer.to_vec();
+    shape.push(rows.ncols());
+    let array = rows.into_shape(IxDyn(&shape))?;
+    let mut perm: Vec<usize> = (0..outer.len()).collect();
+    perm.insert(axis, outer.len());
+    Ok(array
+        .permuted_axes(IxDyn(&perm))
+        .as_standard_layout()
+        .into_owned())
+}
+
+/// Solve the weighted linear least-squares problem `design * x = y`.
+///
+/// Columns of the design matrix are normalised before forming the normal equations, so that
+/// parameters of very different magnitude (e.g. clock and TEC) can be fitted together. Returns
+/// `None` if the problem is singular.
+pub(crate) fn weighted_lstsq(design: &Array2<f64>, y: &[f64], w: &[f64]) -> Option<Array1<f64>> {
+    let (nrows, ncols) = design.dim();
+    let scale: Vec<f64> = (0..ncols)
+        .map(|j| {
+            (0..nrows)
+                .map(|i| w[i] * design[[i, j]] * design[[i, j]])
+                .sum::<f64>()
+                .sqrt()
+        })
+        .collect();
+    if scale.iter().any(|s| !s.is_normal()) {
+        return None;
+    }
+
+    let mut normal = Array2::<f64>::zeros((ncols, ncols + 1));
+    for i in 0..nrows {
+        if w[i] == 0.0 {
+            continue;
+        }
+        for j in 0..ncols {
+            let aj = design[[i, j]] / scale[j];
+            for k in 0..ncols {
+                normal[[j, k]] += w[i] * aj * design[[i, k]] / scale[k];
+            }
+            normal[[j, ncols]] += w[i] * aj * y[i];
+        }
+    }
+
+    // Gaussian elimination with partial pivoting on the augmented matrix.
+    for col in 0..ncols {
+        let pivot = (col..ncols)
+            .max_by(|a, b| normal[[*a, col]].abs().total_cmp(&normal[[*b, col]].abs()))
+            .unwrap();
+        if normal[[pivot, col]].abs() < 1e-12 {
+            return None;
+        }
+        for k in 0..=ncols {
+            normal.swap([col, k], [pivot, k]);
+        }
+        for row in (col + 1)..ncols {
+            let factor = normal[[row, col]] / normal[[col, col]];
+            for k in col..=ncols {
+                normal[[row, k]] -= factor * normal[[col, k]];
+            }
+        }
+    }
+    let mut x = Array1::<f64>::zeros(ncols);
+    for row in (0..ncols).rev() {
+        let mut acc = normal[[row, ncols]];
+        for k in (row + 1)..ncols {
+            acc -= normal[[row, k]] * x[k];
+        }
+        x[row] = acc / normal[[row, row]];
+    }
+    Some(x / Array1::from(scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, ArrayD, IxDyn};
+
+    use super::*;
+
+    #[test]
+    fn weighted_lstsq_recovers_line() {
+        let x = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let design = Array2::from_shape_fn((5, 2), |(i, j)| if j == 0 { 1.0 } else { x[i] });
+        // The last point is an outlier with zero weight.
+        let y = [2.0, 5.0, 8.0, 11.0, 100.0];
+        let w = [1.0, 2.0, 1.0, 0.5, 0.0];
+        let p = weighted_lstsq(&design, &y, &w).unwrap();
+        assert!((p[0] - 2.0).abs() < 1e-9);
+        assert!((p[1] - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weighted_lstsq_scales_columns() {
+        // Parameters of very different magnitude, as for clock and TEC.
+        let nu: Vec<f64> = (0..20).map(|i| 120e6 + i as f64 * 3e6).collect();
+        let design = Array2::from_shape_fn((20, 2), |(i, j)| match j {
+            0 => 2.0 * std::f64::consts::PI * nu[i],
+            _ => -8.44797245e9 / nu[i],
+        });
+        let y: Vec<f64> = (0..20)
+            .map(|i| design[[i, 0]] * 3e-9 + design[[i, 1]] * 0.02)
+            .collect();
+        let p = weighted_lstsq(&design, &y, &[1.0; 20]).unwrap();
+        assert!((p[0] - 3e-9).abs() < 1e-15);
+        assert!((p[1] - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weighted_lstsq_singular() {
+        let design = array![[1.0, 2.0], [2.0, 4.0], [3.0, 6.0]];
+        assert!(weighted_lstsq(&design, &[1.0, 2.0, 3.0], &[1.0; 3]).is_none());
+        let design = array![[1.0, 0.0], [1.0, 0.0]];
+        assert!(weighted_lstsq(&design, &[1.0, 2.0], &[1.0; 2]).is_none());
+    }
+
+    #[test]
+    fn move_and_restore_axis() {
+        let array = ArrayD::from_shape_fn(IxDyn(&[2, 3, 4]), |i| {
+            (100 * i[0] + 10 * i[1] + i[2]) as f64
+        });
+        let (rows, outer) = move_axis_last(array.clone(), 1).unwrap();
+        assert_eq!(rows.dim(), (8, 3));
+        assert_eq!(rows.row(5).to_vec(), vec![101.0, 111.0, 121.0]);
+        let restored = restore_axis(rows, &outer, 1).unwrap();
+        assert_eq!(restored, array);
+        assert!(restored.is_standard_layout());
+    }
+
+    #[test]
+    fn wrap_phase_range() {
+        assert!((wrap_phase(3.0 * std::f64::consts::PI) + std::f64::consts::PI).abs() < 1e-12);
+        assert!((wrap_phase(-0.5) + 0.5).abs() < 1e-12);
+        assert!((wrap_phase(7.0) - (7.0 - 2.0 * std::f64::consts::PI)).abs() < 1e-12);
+    }
+}
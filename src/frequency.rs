@@ -0,0 +1,236 @@
+// Inspection of frequency axes and detection of the LOFAR observing setup.
+
+use medians::Medianf64;
+use ndarray::Array1;
+
+/// Sampling clock of the LOFAR station digitisers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    Mhz160,
+    Mhz200,
+}
+
+impl Clock {
+    /// Sampling rate in Hz.
+    pub fn rate(&self) -> f64 {
+        match self {
+            Clock::Mhz160 => 160e6,
+            Clock::Mhz200 => 200e6,
+        }
+    }
+
+    /// Width of a subband in Hz: the Nyquist bandwidth split into 512 subbands.
+    pub fn subband_width(&self) -> f64 {
+        self.rate() / 1024.0
+    }
+}
+
+/// LOFAR antenna type and HBA filter band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Band {
+    /// Low Band Antennas, 10 to 90 MHz.
+    Lba,
+    /// HBA with the 110 to 190 MHz filter.
+    HbaLow,
+    /// HBA with the 170 to 230 MHz filter.
+    HbaMid,
+    /// HBA with the 210 to 250 MHz filter.
+    HbaHigh,
+}
+
+impl Band {
+    /// Clock that the band is normally observed with. LBA can also be observed with the 160 MHz
+    /// clock.
+    pub fn clock(&self) -> Clock {
+        match self {
+            Band::HbaMid => Clock::Mhz160,
+            _ => Clock::Mhz200,
+        }
+    }
+
+    /// Nyquist zone that the band is sampled in.
+    pub fn nyquist_zone(&self) -> usize {
+        match self {
+            Band::Lba => 1,
+            Band::HbaLow => 2,
+            Band::HbaMid | Band::HbaHigh => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for Band {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Band::Lba => "LBA",
+            Band::HbaLow => "HBA low",
+            Band::HbaMid => "HBA mid",
+            Band::HbaHigh => "HBA high",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Channel width in Hz, taken as the median spacing between consecutive channels. Returns `None`
+/// for fewer than two channels.
+pub fn channel_width(freqs: &Array1<f64>) -> Option<f64> {
+    let steps: Vec<f64> = freqs
+        .windows(2)
+        .into_iter()
+        .map(|w| w[1] - w[0])
+        .filter(|d| d.is_finite())
+        .collect();
+    match steps.is_empty() {
+        true => None,
+        false => Some(steps.medf_unchecked()),
+    }
+}
+
+/// Lower and upper edge in Hz of the band covered by the channels, assuming that every channel
+/// is `channel_width` wide.
+pub fn band_edges(freqs: &Array1<f64>) -> Option<(f64, f64)> {
+    let width = channel_width(freqs)?;
+    let lowest = freqs.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = freqs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    Some((lowest - width / 2.0, highest + width / 2.0))
+}
+
+/// Subband that contains `freq` in Hz for the given clock and Nyquist zone, or `None` if the
+/// frequency lies outside the zone.
+pub fn frequency_to_subband(freq: f64, clock: Clock, zone: usize) -> Option<usize> {
+    let offset = zone.checked_sub(1)? as f64 * clock.rate() / 2.0;
+    let subband = ((freq - offset) / clock.subband_width()).round();
+    match (0.0..512.0).contains(&subband) {
+        true => Some(subband as usize),
+        false => None,
+    }
+}
+
+/// Central frequency in Hz of `subband` for the given clock and Nyquist zone.
+pub fn subband_to_frequency(subband: usize, clock: Clock, zone: usize) -> f64 {
+    zone.saturating_sub(1) as f64 * clock.rate() / 2.0 + subband as f64 * clock.subband_width()
+}
+
+/// LOFAR band that the channels were observed in, or `None` if their range does not fit a
+/// single band.
+///
+/// A band fits if all channels lie in the Nyquist zone that it is sampled in. The HBA zones
+/// overlap, e.g. channels between 170 and 200 MHz can be HBA low or HBA mid, so such ranges are
+/// only resolved if the sampling `clock` is given and are `None` otherwise.
+pub fn detect_band(freqs: &Array1<f64>, clock: Option<Clock>) -> Option<Band> {
+    let lowest = freqs.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = freqs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if !lowest.is_finite() || !highest.is_finite() {
+        return None;
+    }
+    let fits = |band: Band| {
+        // LBA is observed with either clock.
+        let clock = match (band, clock) {
+            (Band::Lba, Some(c)) => c,
+            (_, Some(c)) if c != band.clock() => return false,
+            _ => band.clock(),
+        };
+        let start = (band.nyquist_zone() - 1) as f64 * clock.rate() / 2.0;
+        lowest >= start && highest <= start + clock.rate() / 2.0
+    };
+    let mut bands = [Band::Lba, Band::HbaLow, Band::HbaMid, Band::HbaHigh]
+        .into_iter()
+        .filter(|b| fits(*b));
+    match (bands.next(), bands.next()) {
+        (Some(band), None) => Some(band),
+        _ => None,
+    }
+}
+
+/// A gap in a frequency axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyGap {
+    /// Index of the first channel after the gap.
+    pub index: usize,
+    /// Last channel before the gap in Hz.
+    pub start: f64,
+    /// First channel after the gap in Hz.
+    pub end: f64,
+}
+
+/// Gaps where consecutive channels are more than `tolerance` channel widths apart, e.g. 1.5 to
+/// find every missing channel of a non-contiguous band.
+pub fn find_gaps(freqs: &Array1<f64>, tolerance: f64) -> Vec<FrequencyGap> {
+    let width = match channel_width(freqs) {
+        Some(w) => w,
+        None => return vec![],
+    };
+    (1..freqs.len())
+        .filter(|&i| freqs[i] - freqs[i - 1] > tolerance * width)
+        .map(|i| FrequencyGap {
+            index: i,
+            start: freqs[i - 1],
+            end: freqs[i],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Band detected for channels of 48.828125 kHz from `start` up to `end` in MHz.
+    fn band(start: f64, end: f64, clock: Option<Clock>) -> Option<Band> {
+        detect_band(&Array1::range(start * 1e6, end * 1e6, 48828.125), clock)
+    }
+
+    #[test]
+    fn bands_without_clock() {
+        assert_eq!(band(30.0, 78.0, None), Some(Band::Lba));
+        assert_eq!(band(0.0, 100.0, None), Some(Band::Lba));
+        assert_eq!(band(100.0, 160.0, None), Some(Band::HbaLow));
+        assert_eq!(band(120.0, 168.0, None), Some(Band::HbaLow));
+        // Only HBA mid is sampled on both sides of 200 MHz.
+        assert_eq!(band(185.0, 215.0, None), Some(Band::HbaMid));
+        assert_eq!(band(240.5, 300.0, None), Some(Band::HbaHigh));
+        assert_eq!(band(241.0, 250.0, None), Some(Band::HbaHigh));
+        // Ranges covered by two HBA zones.
+        assert_eq!(band(160.0, 168.0, None), None);
+        assert_eq!(band(170.0, 190.0, None), None);
+        assert_eq!(band(190.0, 200.0, None), None);
+        assert_eq!(band(200.0, 240.0, None), None);
+        assert_eq!(band(210.0, 230.0, None), None);
+        // Ranges that cross the edges of all zones.
+        assert_eq!(band(90.0, 110.0, None), None);
+        assert_eq!(band(150.0, 210.0, None), None);
+        assert_eq!(band(230.0, 310.0, None), None);
+        assert_eq!(detect_band(&Array1::from(vec![f64::NAN]), None), None);
+        assert_eq!(detect_band(&Array1::from(vec![]), None), None);
+    }
+
+    #[test]
+    fn bands_with_clock() {
+        let (mhz160, mhz200) = (Some(Clock::Mhz160), Some(Clock::Mhz200));
+        assert_eq!(band(30.0, 78.0, mhz160), Some(Band::Lba));
+        assert_eq!(band(30.0, 90.0, mhz160), None);
+        assert_eq!(band(30.0, 90.0, mhz200), Some(Band::Lba));
+        assert_eq!(band(120.0, 160.0, mhz160), None);
+        assert_eq!(band(160.0, 168.0, mhz160), Some(Band::HbaMid));
+        assert_eq!(band(160.0, 168.0, mhz200), Some(Band::HbaLow));
+        assert_eq!(band(170.0, 190.0, mhz160), Some(Band::HbaMid));
+        assert_eq!(band(170.0, 190.0, mhz200), Some(Band::HbaLow));
+        assert_eq!(band(190.0, 200.0, mhz160), Some(Band::HbaMid));
+        assert_eq!(band(190.0, 200.0, mhz200), Some(Band::HbaLow));
+        assert_eq!(band(185.0, 215.0, mhz160), Some(Band::HbaMid));
+        assert_eq!(band(185.0, 215.0, mhz200), None);
+        assert_eq!(band(210.0, 230.0, mhz160), Some(Band::HbaMid));
+        assert_eq!(band(210.0, 230.0, mhz200), Some(Band::HbaHigh));
+        assert_eq!(band(241.0, 250.0, mhz160), None);
+    }
+
+    #[test]
+    fn subbands() {
+        let clock = Clock::Mhz200;
+        assert_eq!(frequency_to_subband(120e6, clock, 2), Some(102));
+        assert_eq!(subband_to_frequency(102, clock, 2), 119921875.0);
+        assert_eq!(frequency_to_subband(90e6, clock, 2), None);
+        assert_eq!(frequency_to_subband(90e6, clock, 0), None);
+        let clock = Clock::Mhz160;
+        let frequency = subband_to_frequency(77, clock, 3);
+        assert_eq!(frequency_to_subband(frequency, clock, 3), Some(77));
+    }
+}
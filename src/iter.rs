@@ -0,0 +1,207 @@
+// Iteration over slices of a SolTab along one of its axes.
+
+use anyhow::bail;
+use hdf5::{Hyperslab, Selection, SliceOrIndex};
+use ndarray::parallel::prelude::*;
+use ndarray::{ArrayD, IxDyn};
+use thiserror::Error;
+
+use crate::{Axis, AxisValues, SolTab};
+
+#[derive(Debug, Error)]
+#[error("SolTab {0} has no {1} axis!")]
+struct MissingAxisError(String, String);
+
+/// A single coordinate along a SolTab axis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coordinate {
+    Float(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Coordinate::Float(x) => write!(f, "{}", x),
+            Coordinate::Text(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// Values and weights of a SolTab at a single coordinate along an axis. The arrays have all
+/// other axes, in their original order.
+#[derive(Debug, Clone)]
+pub struct AxisSlice {
+    pub coord: Coordinate,
+    /// Position of the coordinate along the axis.
+    pub index: usize,
+    pub values: ArrayD<f64>,
+    pub weights: ArrayD<f64>,
+}
+
+/// Iterator over the slices of a SolTab along an axis, created by [`SolTab::iter_axis`].
+pub struct AxisIter<'a> {
+    soltab: &'a SolTab,
+    dim: usize,
+    ndim: usize,
+    coords: AxisValues,
+    next: usize,
+}
+
+impl Iterator for AxisIter<'_> {
+    type Item = Result<AxisSlice, hdf5::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.coords.len() {
+            return None;
+        }
+        self.next += 1;
+        Some(
+            self.soltab
+                .read_axis_slice(self.dim, self.ndim, &self.coords, self.next - 1),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.coords.len() - self.next;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for AxisIter<'_> {}
+
+impl SolTab {
+    /// Position of `axis` among the dimensions, the number of dimensions and the coordinates.
+    fn locate_axis(&self, axis: &Axis) -> Result<(usize, usize, AxisValues), anyhow::Error> {
+        let axes = self.get_axes();
+        match axes.iter().position(|a| a == axis.name()) {
+            Some(dim) => Ok((dim, axes.len(), self.get_axis_values(axis.name())?)),
+            None => bail!(MissingAxisError(self.name.clone(), axis.to_string())),
+        }
+    }
+
+    /// Read the values and weights at position `index` along dimension `dim` from disk.
+    fn read_axis_slice(
+        &self,
+        dim: usize,
+        ndim: usize,
+        coords: &AxisValues,
+        index: usize,
+    ) -> Result<AxisSlice, hdf5::Error> {
+        let hyperslab: Vec<SliceOrIndex> = (0..ndim)
+            .map(|d| match d == dim {
+                true => SliceOrIndex::Index(index),
+                false => SliceOrIndex::from(..),
+            })
+            .collect();
+        let selection = Selection::from(Hyperslab::from(hyperslab));
+        let group = self._h5parm.group(&self.get_full_name())?;
+        let values = group
+            .dataset("val")?
+            .read_slice::<f64, _, IxDyn>(&selection)?;
+        let weights = group
+            .dataset("weight")?
+            .read_slice::<f64, _, IxDyn>(&selection)?;
+        Ok(AxisSlice {
+            coord: match coords {
+                AxisValues::Float(x) => Coordinate::Float(x[index]),
+                AxisValues::Text(x) => Coordinate::Text(x[index].clone()),
+            },
+            index,
+            values,
+            weights,
+        })
+    }
+
+    /// Iterate over the slices of this SolTab along `axis`, e.g. over the stations for
+    /// [`Axis::Ant`]. Each slice is only read from disk when the iterator reaches it.
+    pub fn iter_axis(&self, axis: Axis) -> Result<AxisIter<'_>, anyhow::Error> {
+        let (dim, ndim, coords) = self.locate_axis(&axis)?;
+        Ok(AxisIter {
+            soltab: self,
+            dim,
+            ndim,
+            coords,
+            next: 0,
+        })
+    }
+
+    /// Parallel version of [`SolTab::iter_axis`], which reads and hands out the slices on the
+    /// rayon thread pool.
+    pub fn par_iter_axis(
+        &self,
+        axis: Axis,
+    ) -> Result<
+        impl IndexedParallelIterator<Item = Result<AxisSlice, hdf5::Error>> + '_,
+        anyhow::Error,
+    > {
+        let (dim, ndim, coords) = self.locate_axis(&axis)?;
+        Ok((0..coords.len())
+            .into_par_iter()
+            .map(move |index| self.read_axis_slice(dim, ndim, &coords, index)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Axis as ArrayAxis;
+
+    use super::*;
+    use crate::testing::{digits, fixture, names, temp_h5parm, times};
+    use crate::{H5parm, SolTabKind};
+
+    #[test]
+    fn slices_match_the_full_data() {
+        let path = temp_h5parm("iter_axis");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let mut data = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(3)),
+                ("ant", names("CS", 2)),
+                ("pol", names("P", 2)),
+            ],
+            digits,
+        );
+        data.weights[[2, 1, 0]] = 0.0;
+        let soltab = solset.create_soltab("phase000", &data).unwrap();
+        let full = soltab.read_data().unwrap();
+
+        let slices = soltab.iter_axis(Axis::Ant).unwrap();
+        assert_eq!(slices.len(), 2);
+        for (a, slice) in slices.enumerate() {
+            let slice = slice.unwrap();
+            assert_eq!(slice.index, a);
+            assert_eq!(slice.coord, Coordinate::Text(format!("CS{}", a)));
+            assert_eq!(slice.values, full.values.index_axis(ArrayAxis(1), a));
+            assert_eq!(slice.weights, full.weights.index_axis(ArrayAxis(1), a));
+        }
+
+        // The parallel iterator hands out the same slices, in order when collected.
+        let sequential: Vec<AxisSlice> = soltab
+            .iter_axis(Axis::Time)
+            .unwrap()
+            .map(|s| s.unwrap())
+            .collect();
+        let parallel: Vec<AxisSlice> = soltab
+            .par_iter_axis(Axis::Time)
+            .unwrap()
+            .map(|s| s.unwrap())
+            .collect();
+        assert_eq!(parallel.len(), 3);
+        for (s, p) in sequential.iter().zip(parallel.iter()) {
+            assert_eq!(p.coord, Coordinate::Float(4.8e9 + 10.0 * p.index as f64));
+            assert_eq!((s.index, &s.coord), (p.index, &p.coord));
+            assert_eq!(s.values, p.values);
+            assert_eq!(s.weights, p.weights);
+        }
+        assert_eq!(parallel[2].weights[[1, 0]], 0.0);
+
+        assert!(soltab.iter_axis(Axis::Dir).is_err());
+        assert!(soltab
+            .par_iter_axis(Axis::Other("sub".to_string()))
+            .is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,731 @@
+// Conversion between killMS/DDFacet solution files and H5parm solsets.
+
+use anyhow::bail;
+use hdf5::types::FixedAscii;
+use ndarray::{s, Array1, Array2, ArrayD, Axis, Ix5, IxDyn};
+use num::complex::Complex;
+use thiserror::Error;
+
+use crate::directions::same_direction;
+use crate::npy::{read_npz, write_npz, NpyArray};
+use crate::{AntennaEntry, AxisValues, SolSet, SolTabData, SolTabKind, SourceEntry};
+
+#[derive(Debug, Error)]
+#[error("Invalid killMS solutions: {0}")]
+struct InvalidKillMsError(String);
+
+#[derive(Debug, Error)]
+#[error("Cannot convert SolTab {0} to killMS solutions: {1}")]
+struct UnsupportedSoltabError(String, String);
+
+#[derive(Debug, Error)]
+#[error("Direction {0} of the killMS solutions is at {1:?}, but at {2:?} in the source table")]
+struct MovedDirectionError(String, [f64; 2], [f64; 2]);
+
+/// Axis order of killMS gains, without the trailing 2x2 Jones axes.
+const KILLMS_AXES: [&str; 5] = ["time", "freq", "ant", "dir", "pol"];
+
+/// Names of the SolTabs that killMS gains are converted from or to.
+#[derive(Debug, Clone)]
+pub struct KillMsOptions {
+    /// Amplitude SolTab, or `None` to use unit amplitudes when exporting.
+    pub amplitude_soltab: Option<String>,
+    /// Phase SolTab, or `None` to use zero phases when exporting.
+    pub phase_soltab: Option<String>,
+}
+
+impl Default for KillMsOptions {
+    fn default() -> Self {
+        KillMsOptions {
+            amplitude_soltab: Some("amplitude000".to_string()),
+            phase_soltab: Some("phase000".to_string()),
+        }
+    }
+}
+
+/// Elements of the 2x2 Jones matrix that a polarisation describes.
+fn jones_elements(pol: &str) -> Option<&'static [(usize, usize)]> {
+    match pol {
+        "XX" | "RR" => Some(&[(0, 0)]),
+        "XY" | "RL" => Some(&[(0, 1)]),
+        "YX" | "LR" => Some(&[(1, 0)]),
+        "YY" | "LL" => Some(&[(1, 1)]),
+        "I" => Some(&[(0, 0), (1, 1)]),
+        _ => None,
+    }
+}
+
+/// H5parm direction names are enclosed in brackets, killMS names are not.
+fn h5parm_direction_name(name: &str) -> String {
+    format!("[{}]", name.trim_start_matches('[').trim_end_matches(']'))
+}
+
+/// Interval boundaries around sorted sample centres. A single sample gets width `default_width`.
+fn domains(centres: &Array1<f64>, default_width: f64) -> Vec<(f64, f64)> {
+    let n = centres.len();
+    let half = |i: usize| match n {
+        1 => default_width / 2.0,
+        _ if i + 1 < n => (centres[i + 1] - centres[i]) / 2.0,
+        _ => (centres[i] - centres[i - 1]) / 2.0,
+    };
+    (0..n)
+        .map(|i| {
+            let lower = if i == 0 { half(0) } else { half(i - 1) };
+            (centres[i] - lower, centres[i] + half(i))
+        })
+        .collect()
+}
+
+/// Read killMS/DDFacet solutions from `path` into amplitude and phase SolTabs of `solset`.
+///
+/// The full-Jones gains `G` of the `Sols` record array become SolTabs with axes time, freq, ant,
+/// dir and pol. If all off-diagonal gains are zero only the XX and YY polarisations are stored.
+/// Clusters that are not yet in the `source` table are added to it, and clusters that are must
+/// have the same position; an `antenna` table is only created, without positions, if the solset
+/// has none.
+pub fn import_killms(
+    path: &str,
+    solset: &mut SolSet,
+    opts: &KillMsOptions,
+) -> Result<(), anyhow::Error> {
+    let arrays = read_npz(path)?;
+    let get = |name: &str| match arrays.get(name) {
+        Some(a) => Ok(a),
+        None => Err(InvalidKillMsError(format!("missing {}", name))),
+    };
+    let sols = get("Sols")?;
+    let t0 = sols.field("t0")?.to_f64()?;
+    let t1 = sols.field("t1")?.to_f64()?;
+    let mut gains = sols.field("G")?.to_complex()?;
+    // Old killMS versions store a single frequency domain without a frequency axis.
+    if gains.ndim() == 5 {
+        gains = gains.insert_axis(Axis(1));
+    }
+    if gains.ndim() != 6 || gains.shape()[4..] != [2, 2] {
+        bail!(InvalidKillMsError(format!(
+            "gains of shape {:?} are not full-Jones",
+            gains.shape()
+        )));
+    }
+    let (nt, nf, na, nd) = (
+        gains.shape()[0],
+        gains.shape()[1],
+        gains.shape()[2],
+        gains.shape()[3],
+    );
+
+    let times = Array1::from_iter(t0.iter().zip(t1.iter()).map(|(a, b)| (a + b) / 2.0));
+    let freq_domains = get("FreqDomains")?.to_f64()?;
+    if freq_domains.shape() != [nf, 2] {
+        bail!(InvalidKillMsError(format!(
+            "frequency domains of shape {:?} for {} channels",
+            freq_domains.shape(),
+            nf
+        )));
+    }
+    let freqs = freq_domains.sum_axis(Axis(1)) / 2.0;
+    let antennas = get("StationNames")?.to_strings()?;
+    if antennas.len() != na {
+        bail!(InvalidKillMsError(format!(
+            "{} station names for {} stations",
+            antennas.len(),
+            na
+        )));
+    }
+    let cluster_cat = get("ClusterCat")?;
+    let ra = cluster_cat.field("ra")?.to_f64()?;
+    let dec = cluster_cat.field("dec")?.to_f64()?;
+    let names = match cluster_cat.field("Name") {
+        Ok(names) => names.to_strings()?,
+        Err(_) => vec![String::new(); ra.len()],
+    };
+    if names.len() != nd {
+        bail!(InvalidKillMsError(format!(
+            "{} clusters for {} directions",
+            names.len(),
+            nd
+        )));
+    }
+    let directions: Vec<String> = names
+        .iter()
+        .enumerate()
+        .map(|(i, n)| match n.is_empty() {
+            true => h5parm_direction_name(&format!("Dir{:02}", i)),
+            false => h5parm_direction_name(n),
+        })
+        .collect();
+
+    let full_jones = gains
+        .slice(s![.., .., .., .., 0, 1])
+        .iter()
+        .chain(gains.slice(s![.., .., .., .., 1, 0]).iter())
+        .any(|g| *g != Complex::new(0.0, 0.0));
+    let gains = gains.into_shape(IxDyn(&[nt, nf, na, nd, 4]))?;
+    let (pols, gains) = match full_jones {
+        true => (vec!["XX", "XY", "YX", "YY"], gains),
+        // Selecting gives an array in Fortran order, which HDF5 cannot write as is.
+        false => (
+            vec!["XX", "YY"],
+            gains
+                .select(Axis(4), &[0, 3])
+                .as_standard_layout()
+                .into_owned(),
+        ),
+    };
+    let axes = vec![
+        ("time".to_string(), AxisValues::Float(times)),
+        (
+            "freq".to_string(),
+            AxisValues::Float(freqs.into_dimensionality()?),
+        ),
+        ("ant".to_string(), AxisValues::Text(antennas.clone())),
+        ("dir".to_string(), AxisValues::Text(directions.clone())),
+        (
+            "pol".to_string(),
+            AxisValues::Text(pols.iter().map(|p| p.to_string()).collect()),
+        ),
+    ];
+    let weights = gains.mapv(|g| if g.is_finite() { 1.0 } else { 0.0 });
+
+    let sources = directions
+        .iter()
+        .zip(ra.iter().zip(dec.iter()))
+        .map(|(name, (r, d))| {
+            Ok(SourceEntry {
+                name: FixedAscii::<128>::from_ascii(name)?,
+                dir: [*r, *d],
+            })
+        })
+        .collect::<Result<Vec<SourceEntry>, anyhow::Error>>()?;
+    // Other SolTabs of the solset may refer to the directions in the source table, so existing
+    // entries are kept and cannot be moved.
+    let mut source_table = match solset.has_table("source") {
+        true => solset.get_source_table()?,
+        false => vec![],
+    };
+    for source in sources {
+        match source_table
+            .iter()
+            .find(|s| same_direction(s.name.as_str(), source.name.as_str()))
+        {
+            Some(s) if s.dir != source.dir => bail!(MovedDirectionError(
+                source.name.as_str().to_string(),
+                source.dir,
+                s.dir
+            )),
+            Some(_) => {}
+            None => source_table.push(source),
+        }
+    }
+    let antenna_table = antennas
+        .iter()
+        .map(|name| {
+            Ok(AntennaEntry {
+                name: FixedAscii::<16>::from_ascii(name)?,
+                position: [0.0; 3],
+            })
+        })
+        .collect::<Result<Vec<AntennaEntry>, anyhow::Error>>()?;
+
+    let parts: [(&Option<String>, SolTabKind, ArrayD<f64>); 2] = [
+        (
+            &opts.amplitude_soltab,
+            SolTabKind::Amplitude,
+            gains.mapv(|g| g.norm()),
+        ),
+        (
+            &opts.phase_soltab,
+            SolTabKind::Phase,
+            gains.mapv(|g| g.arg()),
+        ),
+    ];
+    let mut outputs = vec![];
+    for (name, kind, values) in parts {
+        if let Some(name) = name {
+            let data = SolTabData {
+                kind,
+                axes: axes.clone(),
+                values,
+                weights: weights.clone(),
+            };
+            outputs.push((name.clone(), data));
+        }
+    }
+    // Write the SolTabs before the tables, so that a failure leaves the tables untouched.
+    solset.create_soltabs(&outputs)?;
+    for (name, _) in outputs.iter() {
+        solset
+            .get_soltab(name.clone())?
+            .add_history(&format!("Imported from killMS solutions {}", path))?;
+    }
+    solset.set_source_table(&source_table)?;
+    if !solset.has_table("antenna") {
+        solset.set_antenna_table(&antenna_table)?;
+    }
+    Ok(())
+}
+
+/// Values and weights of `data` with axes in killMS order, inserting missing dir and pol axes.
+fn to_killms_order(
+    name: &str,
+    data: &SolTabData,
+) -> Result<(ArrayD<f64>, ArrayD<f64>), anyhow::Error> {
+    for axis in &KILLMS_AXES[..3] {
+        if !data.axes.iter().any(|(a, _)| a == axis) {
+            bail!(UnsupportedSoltabError(
+                name.to_string(),
+                format!("no {} axis", axis)
+            ));
+        }
+    }
+    match data.to_axis_order(&KILLMS_AXES) {
+        Ok(arrays) => Ok(arrays),
+        Err(e) => bail!(UnsupportedSoltabError(name.to_string(), e.to_string())),
+    }
+}
+
+/// Write the amplitude and phase SolTabs of `solset` as killMS/DDFacet solutions to `path`.
+///
+/// Flagged solutions are written as identity matrices. SolTabs without a pol axis are taken to
+/// be scalar and SolTabs without a dir axis to hold a single direction. Direction positions are
+/// taken from the `source` table.
+pub fn export_killms(
+    solset: &SolSet,
+    path: &str,
+    opts: &KillMsOptions,
+) -> Result<(), anyhow::Error> {
+    let amplitude = match &opts.amplitude_soltab {
+        Some(name) => Some((name, solset.get_soltab(name.clone())?.read_data()?)),
+        None => None,
+    };
+    let phase = match &opts.phase_soltab {
+        Some(name) => Some((name, solset.get_soltab(name.clone())?.read_data()?)),
+        None => None,
+    };
+    let (reference_name, reference) = match (&phase, &amplitude) {
+        (Some(p), _) => p,
+        (None, Some(a)) => a,
+        (None, None) => bail!(InvalidKillMsError(
+            "no amplitude or phase SolTab given".to_string()
+        )),
+    };
+    let coords = |axis: &str| {
+        reference
+            .axes
+            .iter()
+            .find(|(a, _)| a == axis)
+            .map(|(_, c)| c.clone())
+    };
+    let times = match coords("time") {
+        Some(AxisValues::Float(t)) => t,
+        _ => bail!(UnsupportedSoltabError(
+            reference_name.to_string(),
+            "no time axis".to_string()
+        )),
+    };
+    let freqs = match coords("freq") {
+        Some(AxisValues::Float(f)) => f,
+        _ => bail!(UnsupportedSoltabError(
+            reference_name.to_string(),
+            "no freq axis".to_string()
+        )),
+    };
+    let antennas = match coords("ant") {
+        Some(AxisValues::Text(a)) => a,
+        _ => bail!(UnsupportedSoltabError(
+            reference_name.to_string(),
+            "no ant axis".to_string()
+        )),
+    };
+    let directions = match coords("dir") {
+        Some(AxisValues::Text(d)) => d,
+        _ => vec!["[Dir00]".to_string()],
+    };
+    let pols = match coords("pol") {
+        Some(AxisValues::Text(p)) => p,
+        _ => vec!["I".to_string()],
+    };
+    let elements = pols
+        .iter()
+        .map(|p| match jones_elements(p) {
+            Some(e) => Ok(e),
+            None => Err(UnsupportedSoltabError(
+                reference_name.to_string(),
+                format!("unknown polarisation {}", p),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (ref_values, ref_weights) = to_killms_order(reference_name, reference)?;
+    let shape = ref_values.shape().to_vec();
+    let ordered = |part: &Option<(&String, SolTabData)>| match part {
+        Some((name, data)) => {
+            let (values, weights) = to_killms_order(name, data)?;
+            if values.shape() != shape.as_slice() {
+                bail!(UnsupportedSoltabError(
+                    name.to_string(),
+                    format!("shape {:?} differs from {:?}", values.shape(), shape)
+                ));
+            }
+            Ok(Some((values, weights)))
+        }
+        None => Ok(None),
+    };
+    let amplitude = ordered(&amplitude)?;
+    let phase = ordered(&phase)?;
+    let weights = match (&amplitude, &phase) {
+        (Some((_, wa)), Some((_, wp))) => wa * wp,
+        _ => ref_weights,
+    };
+    let values = ref_values.into_dimensionality::<Ix5>()?;
+    let (nt, nf, na, nd, _) = values.dim();
+
+    let mut gains = ArrayD::<Complex<f64>>::zeros(IxDyn(&[nt, nf, na, nd, 2, 2]));
+    for i in 0..2 {
+        gains
+            .slice_mut(s![.., .., .., .., i, i])
+            .fill(Complex::new(1.0, 0.0));
+    }
+    for ((t, f, a, d, p), w) in weights.into_dimensionality::<Ix5>()?.indexed_iter() {
+        let amp = amplitude.as_ref().map_or(1.0, |(v, _)| v[[t, f, a, d, p]]);
+        let phi = phase.as_ref().map_or(0.0, |(v, _)| v[[t, f, a, d, p]]);
+        if *w > 0.0 && amp.is_finite() && phi.is_finite() {
+            for &(i, j) in elements[p] {
+                gains[[t, f, a, d, i, j]] = Complex::from_polar(amp, phi);
+            }
+        }
+    }
+
+    let time_domains = domains(&times, 1.0);
+    let t0 = Array1::from_iter(time_domains.iter().map(|d| d.0)).into_dyn();
+    let t1 = Array1::from_iter(time_domains.iter().map(|d| d.1)).into_dyn();
+    let sols = NpyArray::from_fields(vec![
+        ("t0".to_string(), NpyArray::from_f64(&t0)),
+        ("t1".to_string(), NpyArray::from_f64(&t1)),
+        ("G".to_string(), NpyArray::from_complex64(&gains)),
+    ])?;
+    let freq_domains = Array2::from_shape_vec(
+        (nf, 2),
+        domains(&freqs, 0.0)
+            .iter()
+            .flat_map(|d| [d.0, d.1])
+            .collect(),
+    )?
+    .into_dyn();
+
+    let sources = match solset.has_table("source") {
+        true => solset.get_source_table()?,
+        false => vec![],
+    };
+    let mut ra = vec![];
+    let mut dec = vec![];
+    for (i, name) in directions.iter().enumerate() {
+        match sources
+            .iter()
+            .find(|s| same_direction(s.name.as_str(), name))
+        {
+            Some(s) => {
+                ra.push(s.dir[0]);
+                dec.push(s.dir[1]);
+            }
+            // A solset without a dir axis may still describe its single direction.
+            None if directions.len() == 1 && sources.len() == 1 => {
+                ra.push(sources[0].dir[0]);
+                dec.push(sources[0].dir[1]);
+            }
+            None => bail!(UnsupportedSoltabError(
+                reference_name.to_string(),
+                format!("direction {} is not in the source table", directions[i])
+            )),
+        }
+    }
+    let names: Vec<String> = directions
+        .iter()
+        .map(|d| d.trim_start_matches('[').trim_end_matches(']').to_string())
+        .collect();
+    let cluster_cat = NpyArray::from_fields(vec![
+        ("Name".to_string(), NpyArray::from_bytes(&names, 200)),
+        (
+            "ra".to_string(),
+            NpyArray::from_f64(&Array1::from(ra).into_dyn()),
+        ),
+        (
+            "dec".to_string(),
+            NpyArray::from_f64(&Array1::from(dec).into_dyn()),
+        ),
+        (
+            "SumI".to_string(),
+            NpyArray::from_f64(&ArrayD::zeros(IxDyn(&[nd]))),
+        ),
+        (
+            "Cluster".to_string(),
+            NpyArray::from_i64(&Array1::from_iter(0..nd as i64).into_dyn()),
+        ),
+    ])?;
+
+    write_npz(
+        path,
+        &[
+            ("Sols".to_string(), sols),
+            (
+                "StationNames".to_string(),
+                NpyArray::from_strings(&antennas),
+            ),
+            ("ClusterCat".to_string(), cluster_cat),
+            ("FreqDomains".to_string(), NpyArray::from_f64(&freq_domains)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, freqs, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    const SHAPE: [usize; 4] = [2, 3, 2, 2];
+
+    fn gain(t: usize, f: usize, a: usize, d: usize, p: usize) -> Complex<f64> {
+        Complex::from_polar(
+            1.0 + 0.25 * (t + f + a + d) as f64,
+            0.1 * (p + 1) as f64 * (d + 1) as f64,
+        )
+    }
+
+    /// Write killMS solutions with the gains of [`gain`] and the given off-diagonal gain.
+    fn write_solutions(path: &str, off_diagonal: f64) {
+        let [nt, nf, na, nd] = SHAPE;
+        let gains = ArrayD::from_shape_fn(IxDyn(&[nt, nf, na, nd, 2, 2]), |i| match (i[4], i[5]) {
+            (0, 0) => gain(i[0], i[1], i[2], i[3], 0),
+            (1, 1) => gain(i[0], i[1], i[2], i[3], 1),
+            _ => Complex::new(off_diagonal, 0.0),
+        });
+        let t0 = Array1::from(vec![4.8e9, 4.8e9 + 10.0]).into_dyn();
+        let t1 = &t0 + 10.0;
+        let sols = NpyArray::from_fields(vec![
+            ("t0".to_string(), NpyArray::from_f64(&t0)),
+            ("t1".to_string(), NpyArray::from_f64(&t1)),
+            ("G".to_string(), NpyArray::from_complex64(&gains)),
+        ])
+        .unwrap();
+        let freq_domains =
+            Array2::from_shape_fn((nf, 2), |(f, e)| 119e6 + 2e6 * (f + e) as f64).into_dyn();
+        let names = vec!["P0".to_string(), "P1".to_string()];
+        let cluster_cat = NpyArray::from_fields(vec![
+            ("Name".to_string(), NpyArray::from_bytes(&names, 200)),
+            (
+                "ra".to_string(),
+                NpyArray::from_f64(&Array1::from(vec![1.0, 1.1]).into_dyn()),
+            ),
+            (
+                "dec".to_string(),
+                NpyArray::from_f64(&Array1::from(vec![0.5, 0.6]).into_dyn()),
+            ),
+        ])
+        .unwrap();
+        let stations = vec!["CS001HBA0".to_string(), "RS106HBA".to_string()];
+        write_npz(
+            path,
+            &[
+                ("Sols".to_string(), sols),
+                (
+                    "StationNames".to_string(),
+                    NpyArray::from_strings(&stations),
+                ),
+                ("ClusterCat".to_string(), cluster_cat),
+                ("FreqDomains".to_string(), NpyArray::from_f64(&freq_domains)),
+            ],
+        )
+        .unwrap();
+    }
+
+    /// Path of a fresh killMS solution file in the temporary directory.
+    fn temp_npz(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("h5o3-test-{}-{}.npz", std::process::id(), name))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn read(solset: &SolSet, name: &str) -> SolTabData {
+        solset
+            .get_soltab(name.to_string())
+            .unwrap()
+            .read_data()
+            .unwrap()
+    }
+
+    #[test]
+    fn import_diagonal_gains() {
+        let npz = temp_npz("killms");
+        write_solutions(&npz, 0.0);
+        let path = temp_h5parm("killms");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        import_killms(&npz, solset, &KillMsOptions::default()).unwrap();
+
+        let amplitudes = solset
+            .get_soltab("amplitude000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        let phases = solset
+            .get_soltab("phase000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(amplitudes.values.shape(), &[2, 3, 2, 2, 2]);
+        assert_eq!(
+            amplitudes.axes[0].1,
+            AxisValues::Float(Array1::from(vec![4.8e9 + 5.0, 4.8e9 + 15.0]))
+        );
+        assert_eq!(
+            amplitudes.axes[1].1,
+            AxisValues::Float(Array1::from(vec![120e6, 122e6, 124e6]))
+        );
+        assert_eq!(
+            amplitudes.axes[4].1,
+            AxisValues::Text(vec!["XX".to_string(), "YY".to_string()])
+        );
+        for ((i, a), p) in amplitudes.values.indexed_iter().zip(phases.values.iter()) {
+            let g = gain(i[0], i[1], i[2], i[3], i[4]);
+            assert!((a - g.norm()).abs() < 1e-6);
+            assert!((p - g.arg()).abs() < 1e-6);
+        }
+        let sources = solset.get_source_table().unwrap();
+        assert_eq!(sources[1].name.as_str(), "[P1]");
+        assert_eq!(sources[1].dir, [1.1, 0.6]);
+        assert_eq!(solset.get_antenna_table().unwrap().len(), 2);
+
+        // Directions that are not in the killMS solutions stay in the source table.
+        let mut sources = solset.get_source_table().unwrap();
+        sources.push(SourceEntry {
+            name: FixedAscii::<128>::from_ascii("[Q]").unwrap(),
+            dir: [2.0, 0.1],
+        });
+        solset.set_source_table(&sources).unwrap();
+
+        // Off-diagonal gains give all four polarisations.
+        write_solutions(&npz, 0.1);
+        let opts = KillMsOptions {
+            amplitude_soltab: Some("amplitude001".to_string()),
+            phase_soltab: None,
+        };
+        import_killms(&npz, solset, &opts).unwrap();
+        let amplitudes = solset
+            .get_soltab("amplitude001".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(amplitudes.values.shape(), &[2, 3, 2, 2, 4]);
+        assert!((amplitudes.values[[0, 0, 0, 0, 1]] - 0.1).abs() < 1e-6);
+        assert_eq!(solset.get_source_table().unwrap(), sources);
+
+        // A direction cannot be moved by importing, since other SolTabs may refer to it.
+        sources[1].dir = [0.0, 0.0];
+        solset.set_source_table(&sources).unwrap();
+        let opts = KillMsOptions {
+            amplitude_soltab: Some("amplitude002".to_string()),
+            phase_soltab: None,
+        };
+        assert!(import_killms(&npz, solset, &opts).is_err());
+        assert!(!solset.has_soltab("amplitude002"));
+        assert_eq!(solset.get_source_table().unwrap(), sources);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&npz).unwrap();
+    }
+
+    #[test]
+    fn export_and_import_full_jones() {
+        let npz = temp_npz("killms_full_jones");
+        let exported = temp_npz("killms_full_jones_exported");
+        write_solutions(&npz, 0.1);
+        let path = temp_h5parm("killms_full_jones");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        import_killms(&npz, solset, &KillMsOptions::default()).unwrap();
+        export_killms(solset, &exported, &KillMsOptions::default()).unwrap();
+        let solset = h5.create_solset("sol001").unwrap();
+        import_killms(&exported, solset, &KillMsOptions::default()).unwrap();
+
+        let solset = h5.get_solset("sol000".to_string()).unwrap();
+        let (amplitudes, phases) = (read(solset, "amplitude000"), read(solset, "phase000"));
+        let solset = h5.get_solset("sol001".to_string()).unwrap();
+        let read_back = read(solset, "amplitude000");
+        assert_eq!(read_back.axes, amplitudes.axes);
+        for (a, b) in read_back.values.iter().zip(amplitudes.values.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+        for (a, b) in read(solset, "phase000")
+            .values
+            .iter()
+            .zip(phases.values.iter())
+        {
+            assert!((a - b).abs() < 1e-6);
+        }
+        assert_eq!(solset.get_source_table().unwrap()[1].dir, [1.1, 0.6]);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&npz).unwrap();
+        std::fs::remove_file(&exported).unwrap();
+    }
+
+    #[test]
+    fn export_scalar_gains_without_directions() {
+        let npz = temp_npz("killms_scalar");
+        let path = temp_h5parm("killms_scalar");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let axes = vec![
+            ("time", times(2)),
+            ("freq", freqs(3)),
+            ("ant", names("CS", 2)),
+        ];
+        let amplitude =
+            |i: &[usize]| 1.0 + 0.1 * i[0] as f64 + 0.2 * i[1] as f64 + 0.3 * i[2] as f64;
+        let phase = |i: &[usize]| 0.1 * (i[0] + i[1] + i[2]) as f64 - 0.2;
+        let mut phases = fixture(SolTabKind::Phase, axes.clone(), phase);
+        phases.weights[[0, 1, 1]] = 0.0;
+        solset
+            .create_soltab(
+                "amplitude000",
+                &fixture(SolTabKind::Amplitude, axes, amplitude),
+            )
+            .unwrap();
+        solset.create_soltab("phase000", &phases).unwrap();
+        solset
+            .set_source_table(&[SourceEntry {
+                name: FixedAscii::<128>::from_ascii("[P0]").unwrap(),
+                dir: [1.0, 0.5],
+            }])
+            .unwrap();
+        export_killms(solset, &npz, &KillMsOptions::default()).unwrap();
+        let solset = h5.create_solset("sol001").unwrap();
+        import_killms(&npz, solset, &KillMsOptions::default()).unwrap();
+
+        // Scalar gains end up on both diagonal elements of a single direction, flagged gains as
+        // the identity.
+        let amplitudes = read(solset, "amplitude000");
+        let phases = read(solset, "phase000");
+        assert_eq!(amplitudes.values.shape(), &[2, 3, 2, 1, 2]);
+        assert_eq!(
+            amplitudes.axes[3].1,
+            AxisValues::Text(vec!["[Dir00]".to_string()])
+        );
+        assert_eq!(amplitudes.axes[0].1, times(2));
+        assert_eq!(amplitudes.axes[1].1, freqs(3));
+        for ((i, a), p) in amplitudes.values.indexed_iter().zip(phases.values.iter()) {
+            let (expected_amplitude, expected_phase) = match (i[0], i[1], i[2]) {
+                (0, 1, 1) => (1.0, 0.0),
+                _ => (amplitude(&[i[0], i[1], i[2]]), phase(&[i[0], i[1], i[2]])),
+            };
+            assert!((a - expected_amplitude).abs() < 1e-6);
+            assert!((p - expected_phase).abs() < 1e-6);
+        }
+        let sources = solset.get_source_table().unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].name.as_str(), "[Dir00]");
+        assert_eq!(sources[0].dir, [1.0, 0.5]);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&npz).unwrap();
+    }
+}
@@ -1,11 +1,38 @@
 #![allow(non_snake_case)]
 // H5parm interface.
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use hdf5::file;
-use ndarray::{array, Array1, ArrayD};
+use hdf5::types::{FixedAscii, FixedUnicode, TypeDescriptor};
+use ndarray::{array, Array1, ArrayD, IxDyn};
 use thiserror::Error;
 
+pub mod average;
+pub mod bandpass;
+pub mod circular;
+pub mod clocktec;
+pub mod concat;
+pub mod convert;
+pub mod directions;
+pub mod extract;
+pub mod faraday;
+pub mod filter;
+mod fit;
+pub mod frequency;
+pub mod iter;
+pub mod killms;
+pub mod normalise;
+pub mod npy;
+pub mod plot;
+pub mod regions;
+pub mod stats;
+pub mod tabular;
+#[cfg(test)]
+mod testing;
+pub mod time;
+
+pub use tables::{AntennaEntry, SourceEntry};
+
 #[derive(Debug, Clone)]
 pub struct H5parm {
     pub name: String,
@@ -43,6 +70,30 @@ impl H5parm {
         });
     }
 
+    /// Create a new, empty H5parm. Fails if the file already exists.
+    pub fn create(h5parm_out: &String) -> Result<Self, anyhow::Error> {
+        let outfile = file::File::create_excl(h5parm_out)?;
+        Ok(H5parm {
+            name: h5parm_out.to_string(),
+            file: outfile,
+            solsets: vec![],
+        })
+    }
+
+    /// Create a new, empty solset in this H5parm.
+    pub fn create_solset(&mut self, ssname: &str) -> Result<&mut SolSet, anyhow::Error> {
+        if self.has_solset(ssname) {
+            bail!(DuplicateSolsetError(ssname.to_string()));
+        }
+        self.file.create_group(ssname)?;
+        self.solsets.push(SolSet {
+            name: ssname.to_string(),
+            soltabs: vec![],
+            _h5parm: self.file.clone(),
+        });
+        Ok(self.solsets.last_mut().unwrap())
+    }
+
     pub fn get_solset(&self, ssname: String) -> Option<&SolSet> {
         if self.get_solset_names().contains(&ssname) {
             let index = self.solsets.iter().position(|r| r.name == ssname)?;
@@ -52,6 +103,10 @@ impl H5parm {
         }
     }
 
+    pub fn get_solset_mut(&mut self, ssname: String) -> Option<&mut SolSet> {
+        self.solsets.iter_mut().find(|r| r.name == ssname)
+    }
+
     pub fn get_solsets(&self) -> &Vec<SolSet> {
         return &self.solsets;
     }
@@ -66,6 +121,69 @@ impl H5parm {
         return names;
     }
 
+    /// Copy solset `src` to a new solset `dst` in this H5parm.
+    pub fn copy_solset(&mut self, src: &str, dst: &str) -> Result<&SolSet, anyhow::Error> {
+        let source = self.file.clone();
+        self.copy_solset_from_file(&source, src, dst)
+    }
+
+    /// Copy solset `src` of another H5parm `other` to a new solset `dst` in this H5parm.
+    pub fn copy_solset_from(
+        &mut self,
+        other: &H5parm,
+        src: &str,
+        dst: &str,
+    ) -> Result<&SolSet, anyhow::Error> {
+        self.copy_solset_from_file(&other.file, src, dst)
+    }
+
+    fn copy_solset_from_file(
+        &mut self,
+        other: &hdf5::File,
+        src: &str,
+        dst: &str,
+    ) -> Result<&SolSet, anyhow::Error> {
+        if !other.link_exists(src) {
+            bail!(MissingSolsetError(src.to_string()));
+        }
+        if self.has_solset(dst) {
+            bail!(DuplicateSolsetError(dst.to_string()));
+        }
+        copy_object(other, src, &self.file, dst)?;
+        self.solsets
+            .push(SolSet::init(&self.file, dst.to_string()).map_err(|e| anyhow!("{}", e))?);
+        Ok(self.solsets.last().unwrap())
+    }
+
+    /// Rename solset `src` to `dst`.
+    pub fn rename_solset(&mut self, src: &str, dst: &str) -> Result<(), anyhow::Error> {
+        if !self.has_solset(src) {
+            bail!(MissingSolsetError(src.to_string()));
+        }
+        if self.has_solset(dst) {
+            bail!(DuplicateSolsetError(dst.to_string()));
+        }
+        self.file.relink(src, dst)?;
+        let solset = self.solsets.iter_mut().find(|s| s.name == src).unwrap();
+        solset.name = dst.to_string();
+        for st in solset.soltabs.iter_mut() {
+            st._solset = dst.to_string();
+        }
+        Ok(())
+    }
+
+    /// Delete solset `name` from this H5parm.
+    ///
+    /// Note that HDF5 does not reclaim the freed space; use e.g. `h5repack` to shrink the file.
+    pub fn delete_solset(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        if !self.has_solset(name) {
+            bail!(MissingSolsetError(name.to_string()));
+        }
+        self.file.unlink(name)?;
+        self.solsets.retain(|s| s.name != name);
+        Ok(())
+    }
+
     pub fn has_solset(&self, ssname: &str) -> bool {
         let result = &self.solsets.iter().find(|s| s.name == ssname);
         return match result {
@@ -79,10 +197,215 @@ impl H5parm {
 #[error("No soltab named {0} in h5parm!")]
 struct MissingSoltabError(String);
 
+#[derive(Debug, Error)]
+#[error("No solset named {0} in h5parm!")]
+struct MissingSolsetError(String);
+
+#[derive(Debug, Error)]
+#[error("Solset {0} already exists in h5parm!")]
+struct DuplicateSolsetError(String);
+
+#[derive(Debug, Error)]
+#[error("SolTab {0} already exists in solset!")]
+struct DuplicateSoltabError(String);
+
+#[derive(Debug, Error)]
+#[error("SolTab {0} is written more than once!")]
+struct RepeatedSoltabError(String);
+
+#[derive(Debug, Error)]
+#[error("Shape {0:?} of values or weights does not match the axes of SolTab {1}!")]
+struct ShapeMismatchError(Vec<usize>, String);
+
+#[derive(Debug, Error)]
+#[error("Axis {0} appears more than once in the requested axis order!")]
+struct DuplicateAxisError(String);
+
+#[derive(Debug, Error)]
+#[error("Axis {0} of length {1} is not in the requested axis order {2:?}!")]
+struct UnorderedAxisError(String, usize, Vec<String>);
+
+#[derive(Debug, Error)]
+#[error("Axis {0} is neither in the SolTab nor a standard H5parm axis!")]
+struct UnknownAxisError(String);
+
+/// Coordinate values along a single SolTab axis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AxisValues {
+    /// Numerical coordinates, e.g. for the `time` and `freq` axes.
+    Float(Array1<f64>),
+    /// Named coordinates, e.g. for the `ant`, `dir` and `pol` axes.
+    Text(Vec<String>),
+}
+
+impl AxisValues {
+    pub fn len(&self) -> usize {
+        match self {
+            AxisValues::Float(x) => x.len(),
+            AxisValues::Text(x) => x.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A SolTab axis. The standard H5parm axes have their own variant, any other axis is kept by
+/// name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Time,
+    Freq,
+    Ant,
+    Dir,
+    Pol,
+    Other(String),
+}
+
+impl Axis {
+    /// Name of the axis as stored in the `AXES` attribute.
+    pub fn name(&self) -> &str {
+        match self {
+            Axis::Time => "time",
+            Axis::Freq => "freq",
+            Axis::Ant => "ant",
+            Axis::Dir => "dir",
+            Axis::Pol => "pol",
+            Axis::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for Axis {
+    fn from(name: &str) -> Self {
+        match name {
+            "time" => Axis::Time,
+            "freq" => Axis::Freq,
+            "ant" => Axis::Ant,
+            "dir" => Axis::Dir,
+            "pol" => Axis::Pol,
+            other => Axis::Other(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Axis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Description of a single SolTab axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisInfo {
+    pub axis: Axis,
+    /// Position of the axis in the dimensions of the values and weights.
+    pub index: usize,
+    pub length: usize,
+    pub coords: AxisValues,
+}
+
+impl AxisInfo {
+    pub fn name(&self) -> &str {
+        self.axis.name()
+    }
+}
+
+mod tables {
+    // The H5Type derive of hdf5 0.8 places its impls inside a function.
+    #![allow(non_local_definitions)]
+
+    use hdf5::types::FixedAscii;
+
+    /// An entry of the `antenna` table of a solset.
+    #[derive(hdf5::H5Type, Debug, Clone, PartialEq)]
+    #[repr(C)]
+    pub struct AntennaEntry {
+        pub name: FixedAscii<16>,
+        /// ITRF position in m.
+        pub position: [f64; 3],
+    }
+
+    /// An entry of the `source` table of a solset.
+    #[derive(hdf5::H5Type, Debug, Clone, PartialEq)]
+    #[repr(C)]
+    pub struct SourceEntry {
+        pub name: FixedAscii<128>,
+        /// Right ascension and declination in rad.
+        pub dir: [f64; 2],
+    }
+}
+
+/// The type, axes, values and weights of a SolTab held in memory.
+#[derive(Debug, Clone)]
+pub struct SolTabData {
+    pub kind: SolTabKind,
+    /// Axis names and coordinates, in the order of the dimensions of `values` and `weights`.
+    pub axes: Vec<(String, AxisValues)>,
+    pub values: ArrayD<f64>,
+    pub weights: ArrayD<f64>,
+}
+
+/// Axes that the SolTabs written by DP3 and LoSoTo may have.
+const STANDARD_AXES: [&str; 5] = ["time", "freq", "ant", "dir", "pol"];
+
+impl SolTabData {
+    /// Values and weights with their axes permuted to `order`, e.g. `["time", "freq", "ant",
+    /// "dir", "pol"]`. Standard axes in `order` that this SolTab lacks are inserted with length 1
+    /// and axes of length 1 that are not in `order` are dropped. Other axes missing from `order`,
+    /// and non-standard axes in `order` that this SolTab lacks, are an error.
+    pub fn to_axis_order(
+        &self,
+        order: &[&str],
+    ) -> Result<(ArrayD<f64>, ArrayD<f64>), anyhow::Error> {
+        for (i, axis) in order.iter().enumerate() {
+            if order[..i].contains(axis) {
+                bail!(DuplicateAxisError(axis.to_string()));
+            }
+            if !STANDARD_AXES.contains(axis) && !self.axes.iter().any(|(a, _)| a == axis) {
+                bail!(UnknownAxisError(axis.to_string()));
+            }
+        }
+        let mut values = self.values.clone();
+        let mut weights = self.weights.clone();
+        let mut remaining = vec![];
+        // Drop axes from the back, so that the indices of earlier axes stay valid.
+        for (k, (axis, coords)) in self.axes.iter().enumerate().rev() {
+            if order.contains(&axis.as_str()) {
+                remaining.insert(0, axis.as_str());
+            } else if values.shape()[k] == 1 {
+                values = values.index_axis_move(ndarray::Axis(k), 0);
+                weights = weights.index_axis_move(ndarray::Axis(k), 0);
+            } else {
+                bail!(UnorderedAxisError(
+                    axis.clone(),
+                    coords.len(),
+                    order.iter().map(|a| a.to_string()).collect()
+                ));
+            }
+        }
+        let perm: Vec<usize> = order
+            .iter()
+            .filter_map(|axis| remaining.iter().position(|a| a == axis))
+            .collect();
+        values = values.permuted_axes(IxDyn(&perm));
+        weights = weights.permuted_axes(IxDyn(&perm));
+        for (i, axis) in order.iter().enumerate() {
+            if !remaining.contains(axis) {
+                values = values.insert_axis(ndarray::Axis(i));
+                weights = weights.insert_axis(ndarray::Axis(i));
+            }
+        }
+        Ok((values, weights))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SolSet {
     pub name: String,
     pub soltabs: Vec<SolTab>,
+    _h5parm: hdf5::File,
 }
 
 impl SolSet {
@@ -120,15 +443,7 @@ impl SolSet {
             let stname = st.name().rsplit_once("/").unwrap().1.to_string();
             let x = SolTab {
                 name: stname,
-                kind: match st_type.as_str() {
-                    "amplitude" => SolTabKind::Amplitude,
-                    "phase" => SolTabKind::Phase,
-                    "clock" => SolTabKind::Clock,
-                    "error" => SolTabKind::Error,
-                    "rotationmeasure" => SolTabKind::RotationMeasure,
-                    "tec" => SolTabKind::Tec,
-                    _ => SolTabKind::Unknown,
-                },
+                kind: SolTabKind::from_title(&st_type),
                 is_fulljones: false,
                 _solset: name.clone(),
                 _h5parm: h5parm.clone(),
@@ -139,7 +454,122 @@ impl SolSet {
         return Ok(SolSet {
             name: name,
             soltabs: soltablist,
+            _h5parm: h5parm.clone(),
+        });
+    }
+
+    /// Whether this solset has a table, e.g. `antenna` or `source`.
+    pub fn has_table(&self, name: &str) -> bool {
+        self._h5parm
+            .group(&self.name)
+            .is_ok_and(|g| g.link_exists(name))
+    }
+
+    /// Read the `antenna` table of this solset.
+    pub fn get_antenna_table(&self) -> Result<Vec<AntennaEntry>, hdf5::Error> {
+        self._h5parm
+            .group(&self.name)?
+            .dataset("antenna")?
+            .read_raw::<AntennaEntry>()
+    }
+
+    /// Read the `source` table of this solset.
+    pub fn get_source_table(&self) -> Result<Vec<SourceEntry>, hdf5::Error> {
+        self._h5parm
+            .group(&self.name)?
+            .dataset("source")?
+            .read_raw::<SourceEntry>()
+    }
+
+    /// Replace the `antenna` table of this solset.
+    pub fn set_antenna_table(&self, entries: &[AntennaEntry]) -> Result<(), hdf5::Error> {
+        self.replace_table("antenna", entries)
+    }
+
+    /// Replace the `source` table of this solset.
+    pub fn set_source_table(&self, entries: &[SourceEntry]) -> Result<(), hdf5::Error> {
+        self.replace_table("source", entries)
+    }
+
+    fn replace_table<T: hdf5::H5Type>(&self, name: &str, entries: &[T]) -> Result<(), hdf5::Error> {
+        let group = self._h5parm.group(&self.name)?;
+        if group.link_exists(name) {
+            group.unlink(name)?;
+        }
+        group
+            .new_dataset_builder()
+            .with_data(entries)
+            .create(name)?;
+        Ok(())
+    }
+
+    /// Create a new SolTab in this solset from the given axes, values and weights.
+    ///
+    /// The H5parm must have been opened in read-write mode.
+    pub fn create_soltab(
+        &mut self,
+        name: &str,
+        data: &SolTabData,
+    ) -> Result<&SolTab, anyhow::Error> {
+        if self.has_soltab(name) {
+            bail!(DuplicateSoltabError(name.to_string()));
+        }
+        let shape: Vec<usize> = data.axes.iter().map(|(_, v)| v.len()).collect();
+        if data.values.shape() != shape.as_slice() {
+            bail!(ShapeMismatchError(
+                data.values.shape().to_vec(),
+                name.to_string()
+            ));
+        }
+        if data.weights.shape() != shape.as_slice() {
+            bail!(ShapeMismatchError(
+                data.weights.shape().to_vec(),
+                name.to_string()
+            ));
+        }
+
+        let group = self._h5parm.group(&self.name)?.create_group(name)?;
+        // Do not leave a partially written SolTab behind.
+        if let Err(e) = write_soltab_group(&group, data) {
+            self._h5parm.group(&self.name)?.unlink(name)?;
+            return Err(e);
+        }
+
+        self.soltabs.push(SolTab {
+            name: name.to_string(),
+            kind: data.kind.clone(),
+            is_fulljones: false,
+            _solset: self.name.clone(),
+            _h5parm: self._h5parm.clone(),
         });
+        Ok(self.soltabs.last().unwrap())
+    }
+
+    /// Create several SolTabs at once, e.g. all outputs of a fit.
+    ///
+    /// All names are checked before anything is written, and if writing one of the SolTabs fails
+    /// the ones already written are deleted again, so that the solset is left as it was.
+    pub fn create_soltabs(
+        &mut self,
+        soltabs: &[(String, SolTabData)],
+    ) -> Result<(), anyhow::Error> {
+        for (i, (name, _)) in soltabs.iter().enumerate() {
+            if self.has_soltab(name) {
+                bail!(DuplicateSoltabError(name.clone()));
+            }
+            if soltabs[..i].iter().any(|(n, _)| n == name) {
+                bail!(RepeatedSoltabError(name.clone()));
+            }
+        }
+        for (i, (name, data)) in soltabs.iter().enumerate() {
+            if let Err(e) = self.create_soltab(name, data) {
+                for (written, _) in &soltabs[..i] {
+                    self.delete_soltab(written)?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
     }
 
     pub fn get_soltabs(&self) -> &Vec<SolTab> {
@@ -173,6 +603,46 @@ impl SolSet {
         return Ok(&self.soltabs[index as usize]);
     }
 
+    /// Copy SolTab `src` to a new SolTab `dst` in this solset, including its history.
+    pub fn copy_soltab(&mut self, src: &str, dst: &str) -> Result<&SolTab, anyhow::Error> {
+        let kind = self.get_soltab(src.to_string())?.kind.clone();
+        if self.has_soltab(dst) {
+            bail!(DuplicateSoltabError(dst.to_string()));
+        }
+        let group = self._h5parm.group(&self.name)?;
+        copy_object(&group, src, &group, dst)?;
+        self.soltabs.push(SolTab {
+            name: dst.to_string(),
+            kind,
+            is_fulljones: false,
+            _solset: self.name.clone(),
+            _h5parm: self._h5parm.clone(),
+        });
+        Ok(self.soltabs.last().unwrap())
+    }
+
+    /// Rename SolTab `src` to `dst`.
+    pub fn rename_soltab(&mut self, src: &str, dst: &str) -> Result<(), anyhow::Error> {
+        self.get_soltab(src.to_string())?;
+        if self.has_soltab(dst) {
+            bail!(DuplicateSoltabError(dst.to_string()));
+        }
+        self._h5parm.group(&self.name)?.relink(src, dst)?;
+        let soltab = self.soltabs.iter_mut().find(|s| s.name == src).unwrap();
+        soltab.name = dst.to_string();
+        Ok(())
+    }
+
+    /// Delete SolTab `name` from this solset.
+    ///
+    /// Note that HDF5 does not reclaim the freed space; use e.g. `h5repack` to shrink the file.
+    pub fn delete_soltab(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        self.get_soltab(name.to_string())?;
+        self._h5parm.group(&self.name)?.unlink(name)?;
+        self.soltabs.retain(|s| s.name != name);
+        Ok(())
+    }
+
     pub fn has_soltab(&self, stname: &str) -> bool {
         let result = &self.soltabs.iter().find(|s| s.name == stname);
         return match result {
@@ -191,6 +661,133 @@ pub struct SolTab {
     _h5parm: hdf5::File,
 }
 
+/// Write the title, axes, values and weights of `data` into the SolTab `group`.
+fn write_soltab_group(group: &hdf5::Group, data: &SolTabData) -> Result<(), anyhow::Error> {
+    group
+        .new_attr::<FixedAscii<32>>()
+        .create("TITLE")?
+        .write_scalar(&FixedAscii::<32>::from_ascii(data.kind.title())?)?;
+    for (axis, coords) in data.axes.iter() {
+        match coords {
+            AxisValues::Float(x) => {
+                group
+                    .new_dataset_builder()
+                    .with_data(x.as_standard_layout().view())
+                    .create(axis.as_str())?;
+            }
+            // Direction names can be long, e.g. [Patch_1,Patch_2].
+            AxisValues::Text(x) if axis == "dir" => write_text_dataset::<128>(group, axis, x)?,
+            AxisValues::Text(x) => write_text_dataset::<16>(group, axis, x)?,
+        }
+    }
+    let axes_string = FixedAscii::<23>::from_ascii(
+        &data
+            .axes
+            .iter()
+            .map(|(a, _)| a.as_str())
+            .collect::<Vec<&str>>()
+            .join(","),
+    )?;
+    // HDF5 only writes C-order data, while arrays built with e.g. `select` or
+    // `permuted_axes` are often in another layout.
+    for (dsname, array) in [("val", &data.values), ("weight", &data.weights)] {
+        group
+            .new_dataset_builder()
+            .with_data(array.as_standard_layout().view())
+            .create(dsname)?
+            .new_attr::<FixedAscii<23>>()
+            .create("AXES")?
+            .write_scalar(&axes_string)?;
+    }
+    Ok(())
+}
+
+/// Maximum length in bytes of a history entry.
+pub const HISTORY_LENGTH: usize = 8192;
+
+/// Make `entry` fit in a history attribute: characters that are not ASCII are escaped as e.g.
+/// `\u{e9}` and entries longer than [`HISTORY_LENGTH`] bytes are cut, marked with `...`.
+fn history_text(entry: &str) -> String {
+    let mut text = String::with_capacity(entry.len());
+    for c in entry.chars() {
+        match c.is_ascii() {
+            true => text.push(c),
+            false => text.extend(c.escape_unicode()),
+        }
+    }
+    if text.len() > HISTORY_LENGTH {
+        text.truncate(HISTORY_LENGTH - 3);
+        text.push_str("...");
+    }
+    text
+}
+
+/// Recursively copy object `src_name` in `src` to `dst_name` in `dst`, which may be in another file.
+fn copy_object(
+    src: &hdf5::Group,
+    src_name: &str,
+    dst: &hdf5::Group,
+    dst_name: &str,
+) -> Result<(), anyhow::Error> {
+    let src_name = std::ffi::CString::new(src_name)?;
+    let dst_name = std::ffi::CString::new(dst_name)?;
+    hdf5::sync::sync(|| {
+        hdf5::h5check(unsafe {
+            hdf5_sys::h5o::H5Ocopy(
+                src.id(),
+                src_name.as_ptr(),
+                dst.id(),
+                dst_name.as_ptr(),
+                hdf5_sys::h5p::H5P_DEFAULT,
+                hdf5_sys::h5p::H5P_DEFAULT,
+            )
+        })
+    })?;
+    Ok(())
+}
+
+fn write_text_dataset<const N: usize>(
+    group: &hdf5::Group,
+    name: &str,
+    values: &[String],
+) -> Result<(), anyhow::Error> {
+    let data = values
+        .iter()
+        .map(FixedAscii::<N>::from_ascii)
+        .collect::<Result<Vec<_>, _>>()?;
+    group.new_dataset_builder().with_data(&data).create(name)?;
+    Ok(())
+}
+
+/// Read a dataset of strings, whatever their stored length.
+///
+/// Names are padded differently by different writers, so trailing whitespace is removed.
+fn read_text_dataset(ds: &hdf5::Dataset) -> Result<Vec<String>, hdf5::Error> {
+    let values: Vec<String> = match ds.dtype()?.to_descriptor()? {
+        TypeDescriptor::FixedUnicode(n) if n <= 128 => ds
+            .read_raw::<FixedUnicode<128>>()?
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect(),
+        TypeDescriptor::FixedUnicode(_) | TypeDescriptor::VarLenUnicode => ds
+            .read_raw::<FixedUnicode<1024>>()?
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect(),
+        TypeDescriptor::FixedAscii(n) if n <= 128 => ds
+            .read_raw::<FixedAscii<128>>()?
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect(),
+        _ => ds
+            .read_raw::<FixedAscii<1024>>()?
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect(),
+    };
+    Ok(values.iter().map(|v| v.trim_end().to_string()).collect())
+}
+
 impl SolTab {
     /*
     pub fn new(&mut self) -> Self {
@@ -224,6 +821,64 @@ impl SolTab {
         _axes_string.split(",").map(str::to_string).collect()
     }
 
+    /// Read the coordinate values of the given axis.
+    pub fn get_axis_values(&self, axis: &str) -> Result<AxisValues, hdf5::Error> {
+        let ds = self._h5parm.group(&self.get_full_name())?.dataset(axis)?;
+        let values = match ds.dtype()?.to_descriptor()? {
+            TypeDescriptor::FixedUnicode(_)
+            | TypeDescriptor::VarLenUnicode
+            | TypeDescriptor::FixedAscii(_)
+            | TypeDescriptor::VarLenAscii => AxisValues::Text(read_text_dataset(&ds)?),
+            _ => AxisValues::Float(ds.read_1d::<f64>()?),
+        };
+        Ok(values)
+    }
+
+    /// Read the type, axes, values and weights of this SolTab into memory.
+    pub fn read_data(&self) -> Result<SolTabData, hdf5::Error> {
+        let mut axes = vec![];
+        for axis in self.get_axes() {
+            let coords = self.get_axis_values(&axis)?;
+            axes.push((axis, coords));
+        }
+        Ok(SolTabData {
+            kind: self.kind.clone(),
+            axes,
+            values: self.get_values(),
+            weights: self.get_weights(),
+        })
+    }
+
+    /// Name, position, length and coordinates of every axis, in the order of the dimensions of
+    /// the values and weights.
+    pub fn axis_info(&self) -> Result<Vec<AxisInfo>, hdf5::Error> {
+        let mut info = vec![];
+        for (index, name) in self.get_axes().iter().enumerate() {
+            let coords = self.get_axis_values(name)?;
+            info.push(AxisInfo {
+                axis: Axis::from(name.as_str()),
+                index,
+                length: coords.len(),
+                coords,
+            });
+        }
+        Ok(info)
+    }
+
+    /// Description of `axis`, or `None` if this SolTab does not have it.
+    pub fn find_axis(&self, axis: &Axis) -> Result<Option<AxisInfo>, hdf5::Error> {
+        Ok(self.axis_info()?.into_iter().find(|a| a.axis == *axis))
+    }
+
+    /// Values and weights of this SolTab with their axes permuted to `order`. See
+    /// [`SolTabData::to_axis_order`].
+    pub fn read_in_axis_order(
+        &self,
+        order: &[&str],
+    ) -> Result<(ArrayD<f64>, ArrayD<f64>), anyhow::Error> {
+        self.read_data()?.to_axis_order(order)
+    }
+
     pub fn get_name(&self) -> &String {
         &self.name
     }
@@ -239,8 +894,13 @@ impl SolTab {
         format!("/{}/{}", self._solset, self.name)
     }
 
+    /// Name of the kind of this SolTab, e.g. `Phase`. Every unknown kind is `Unknown`, as it was
+    /// before the kinds kept their title; use [`SolTabKind::title`] to tell them apart.
     pub fn get_type(&self) -> String {
-        format!("{:?}", self.kind)
+        match &self.kind {
+            SolTabKind::Unknown(_) => "Unknown".to_string(),
+            kind => format!("{:?}", kind),
+        }
     }
 
     pub fn get_times(&self) -> Array1<f64> {
@@ -297,18 +957,98 @@ impl SolTab {
         st
     }
 
-    pub fn get_history(&self) -> hdf5::types::FixedAscii<8192> {
-        let x = self
-            ._h5parm
-            .group(&self.get_full_name())
-            .unwrap()
-            .dataset("val")
-            .unwrap()
-            .attr("HISTORY000");
-        match x {
-            Ok(x) => x.read_scalar::<hdf5::types::FixedAscii<8192>>().unwrap(),
-            Err(_) => hdf5::types::FixedAscii::<8192>::from_ascii("").unwrap(),
+    /// Read the direction names of this SolTab, whatever length they were stored with.
+    pub fn get_direction_names(&self) -> Result<Vec<String>, hdf5::Error> {
+        read_text_dataset(&self._h5parm.group(&self.get_full_name())?.dataset("dir")?)
+    }
+
+    /// Read all `HISTORYnnn` entries of this SolTab in order.
+    ///
+    /// LoSoTo stores the history on the SolTab group, while older files may have it on the `val`
+    /// dataset, so both are read.
+    ///
+    /// This used to return only `HISTORY000` of the `val` dataset as a `FixedAscii<8192>`.
+    /// Callers of that version can use the first entry, if any.
+    pub fn get_history(&self) -> Vec<String> {
+        let group = self._h5parm.group(&self.get_full_name()).unwrap();
+        let val = group.dataset("val").unwrap();
+        let mut entries: Vec<(usize, String)> = vec![];
+        let locations: [&hdf5::Location; 2] = [&group, &val];
+        for location in locations {
+            for name in location.attr_names().unwrap_or_default() {
+                let number = match name.strip_prefix("HISTORY").map(str::parse::<usize>) {
+                    Some(Ok(n)) => n,
+                    _ => continue,
+                };
+                let attr = location.attr(&name).unwrap();
+                // Try both ASCII and Unicode, as for the SolTab type.
+                let entry = match attr.read_scalar::<FixedAscii<HISTORY_LENGTH>>() {
+                    Ok(f) => f.to_string(),
+                    Err(_) => match attr.read_scalar::<FixedUnicode<HISTORY_LENGTH>>() {
+                        Ok(f) => f.to_string(),
+                        Err(_) => continue,
+                    },
+                };
+                entries.push((number, entry));
+            }
         }
+        entries.sort_by_key(|(n, _)| *n);
+        entries.into_iter().map(|(_, e)| e).collect()
+    }
+
+    /// Append an entry to the history of this SolTab as the next `HISTORYnnn` attribute on the
+    /// SolTab group, prefixed with the current UTC date and time as LoSoTo does. Characters that
+    /// are not ASCII are escaped and entries longer than [`HISTORY_LENGTH`] are truncated.
+    pub fn add_history(&self, entry: &str) -> Result<(), anyhow::Error> {
+        self.write_history_entry(&format!(
+            "{} {}",
+            chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
+            entry
+        ))
+    }
+
+    /// Append an entry to the history as is, e.g. when copying the history of another SolTab.
+    pub(crate) fn write_history_entry(&self, entry: &str) -> Result<(), anyhow::Error> {
+        let group = self._h5parm.group(&self.get_full_name())?;
+        let val = group.dataset("val")?;
+        let next = group
+            .attr_names()?
+            .iter()
+            .chain(val.attr_names()?.iter())
+            .filter_map(|a| a.strip_prefix("HISTORY")?.parse::<usize>().ok())
+            .max()
+            .map_or(0, |n| n + 1);
+        group
+            .new_attr::<FixedAscii<HISTORY_LENGTH>>()
+            .create(format!("HISTORY{:03}", next).as_str())?
+            .write_scalar(&FixedAscii::<HISTORY_LENGTH>::from_ascii(
+                history_text(entry).as_bytes(),
+            )?)?;
+        Ok(())
+    }
+
+    /// Append the entries of `inherited` as they are, e.g. the history of the SolTab that this
+    /// one was derived from, followed by `entry` as with [`SolTab::add_history`].
+    pub(crate) fn add_derived_history(
+        &self,
+        inherited: &[String],
+        entry: &str,
+    ) -> Result<(), anyhow::Error> {
+        for e in inherited {
+            self.write_history_entry(e)?;
+        }
+        self.add_history(entry)
+    }
+
+    /// Record in the history that `tool` of the given version modified this SolTab, called with
+    /// arguments `args`.
+    pub fn record_history(
+        &self,
+        tool: &str,
+        version: &str,
+        args: &[String],
+    ) -> Result<(), anyhow::Error> {
+        self.add_history(&format!("{} {}: {}", tool, version, args.join(" ")))
     }
 
     pub fn get_polarisations(&self) -> Array1<hdf5::types::FixedAscii<2>> {
@@ -365,15 +1105,452 @@ impl SolTab {
         st.read_dyn::<f64>()
             .expect("Reading SolTab into array failed!")
     }
+
+    /// Overwrite the values of this SolTab. The H5parm must have been opened in read-write mode.
+    pub fn set_values(&self, values: &ArrayD<f64>) -> Result<(), hdf5::Error> {
+        self._h5parm
+            .group(&self.get_full_name())?
+            .dataset("val")?
+            .write(values.as_standard_layout().view())
+    }
+
+    /// Overwrite the weights of this SolTab. The H5parm must have been opened in read-write mode.
+    pub fn set_weights(&self, weights: &ArrayD<f64>) -> Result<(), hdf5::Error> {
+        self._h5parm
+            .group(&self.get_full_name())?
+            .dataset("weight")?
+            .write(weights.as_standard_layout().view())
+    }
 }
 
-#[derive(Debug, Clone)]
+/// The type of solutions stored in a SolTab, as given by its `TITLE` attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SolTabKind {
     Amplitude,
     Clock,
+    ComplexGain,
     Error,
+    FullJones,
     Phase,
+    PhaseOffset,
+    Rotation,
     RotationMeasure,
+    ScalarAmplitude,
+    ScalarPhase,
     Tec,
-    Unknown,
+    Tec3rd,
+    /// Any type not listed above, keeping the original `TITLE` string.
+    Unknown(String),
+}
+
+impl SolTabKind {
+    /// Determine the kind from a SolTab `TITLE` attribute as written by DP3 or LoSoTo.
+    pub fn from_title(title: &str) -> Self {
+        match title {
+            "amplitude" => SolTabKind::Amplitude,
+            "clock" => SolTabKind::Clock,
+            "complexgain" => SolTabKind::ComplexGain,
+            "error" => SolTabKind::Error,
+            "fulljones" => SolTabKind::FullJones,
+            "phase" => SolTabKind::Phase,
+            "phase_offset" => SolTabKind::PhaseOffset,
+            "rotation" => SolTabKind::Rotation,
+            "rotationmeasure" => SolTabKind::RotationMeasure,
+            "scalaramplitude" => SolTabKind::ScalarAmplitude,
+            "scalarphase" => SolTabKind::ScalarPhase,
+            "tec" => SolTabKind::Tec,
+            "tec3rd" => SolTabKind::Tec3rd,
+            _ => SolTabKind::Unknown(title.to_string()),
+        }
+    }
+
+    /// The `TITLE` string used for this kind in an H5parm.
+    pub fn title(&self) -> &str {
+        match self {
+            SolTabKind::Amplitude => "amplitude",
+            SolTabKind::Clock => "clock",
+            SolTabKind::ComplexGain => "complexgain",
+            SolTabKind::Error => "error",
+            SolTabKind::FullJones => "fulljones",
+            SolTabKind::Phase => "phase",
+            SolTabKind::PhaseOffset => "phase_offset",
+            SolTabKind::Rotation => "rotation",
+            SolTabKind::RotationMeasure => "rotationmeasure",
+            SolTabKind::ScalarAmplitude => "scalaramplitude",
+            SolTabKind::ScalarPhase => "scalarphase",
+            SolTabKind::Tec => "tec",
+            SolTabKind::Tec3rd => "tec3rd",
+            SolTabKind::Unknown(title) => title.as_str(),
+        }
+    }
+
+    /// Physical unit of the solution values. Dimensionless quantities return an empty string.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            SolTabKind::Clock => "s",
+            SolTabKind::Phase
+            | SolTabKind::PhaseOffset
+            | SolTabKind::Rotation
+            | SolTabKind::ScalarPhase => "rad",
+            SolTabKind::RotationMeasure => "rad/m^2",
+            SolTabKind::Tec | SolTabKind::Tec3rd => "TECU",
+            _ => "",
+        }
+    }
+
+    /// Whether the solution values are angles that wrap around.
+    pub fn is_phase(&self) -> bool {
+        self.wrap_period().is_some()
+    }
+
+    /// Period after which the solution values wrap, if they do.
+    pub fn wrap_period(&self) -> Option<f64> {
+        match self {
+            SolTabKind::Phase
+            | SolTabKind::PhaseOffset
+            | SolTabKind::Rotation
+            | SolTabKind::ScalarPhase => Some(2.0 * std::f64::consts::PI),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SolTabKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Axis, Dimension, IxDyn};
+
+    use super::*;
+    use crate::testing::{digits, fixture, freqs, names, temp_h5parm, times};
+
+    #[test]
+    fn create_soltab_from_non_standard_arrays() {
+        let path = temp_h5parm("create_soltab");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(3)),
+                ("freq", freqs(4)),
+                ("ant", names("CS", 2)),
+                ("pol", names("P", 2)),
+            ],
+            digits,
+        );
+
+        // Selecting along the freq axis and permuting axes both give non-C-order arrays.
+        let mut selected = data.clone();
+        selected.values = data.values.select(Axis(1), &[3, 1]);
+        selected.weights = data.weights.select(Axis(1), &[3, 1]);
+        if let AxisValues::Float(f) = &data.axes[1].1 {
+            selected.axes[1].1 = AxisValues::Float(f.select(Axis(0), &[3, 1]));
+        }
+        assert!(!selected.values.is_standard_layout());
+        let mut permuted = data.clone();
+        permuted.values = data.values.clone().permuted_axes(IxDyn(&[2, 0, 1, 3]));
+        permuted.weights = data.weights.clone().permuted_axes(IxDyn(&[2, 0, 1, 3]));
+        permuted.axes = vec![
+            data.axes[2].clone(),
+            data.axes[0].clone(),
+            data.axes[1].clone(),
+            data.axes[3].clone(),
+        ];
+
+        solset.create_soltab("phase000", &selected).unwrap();
+        solset.create_soltab("phase001", &permuted).unwrap();
+        let read = solset
+            .get_soltab("phase000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(read.values, selected.values);
+        assert_eq!(read.axes, selected.axes);
+        assert_eq!(read.values[[2, 0, 1, 1]], 2311.0);
+        let read = solset
+            .get_soltab("phase001".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(read.values, permuted.values);
+        assert_eq!(read.values[[1, 2, 3, 0]], 2310.0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_create_soltab_leaves_no_group() {
+        let path = temp_h5parm("create_soltab_failed");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let mut data = fixture(
+            SolTabKind::Phase,
+            vec![("time", times(2)), ("ant", names("CS", 2))],
+            digits,
+        );
+        let valid = data.clone();
+        // Station names must be ASCII, so writing the ant axis fails after the group exists.
+        data.axes[1].1 = AxisValues::Text(vec!["CS001".to_string(), "CSø02".to_string()]);
+        assert!(solset.create_soltab("phase000", &data).is_err());
+        assert!(!solset.has_soltab("phase000"));
+        solset.create_soltab("phase000", &valid).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn axis_order_from_dp3_to_linc() {
+        let path = temp_h5parm("axis_order");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(2)),
+                ("freq", freqs(3)),
+                ("ant", names("CS", 4)),
+                ("dir", names("D", 1)),
+                ("pol", names("P", 2)),
+            ],
+            digits,
+        );
+        let soltab = solset.create_soltab("phase000", &data).unwrap();
+        let (values, weights) = soltab
+            .read_in_axis_order(&["pol", "dir", "ant", "freq", "time"])
+            .unwrap();
+        assert_eq!(values.shape(), &[2, 1, 4, 3, 2]);
+        assert_eq!(weights.shape(), values.shape());
+        for (i, v) in values.indexed_iter() {
+            assert_eq!(*v, digits(&[i[4], i[3], i[2], i[1], i[0]]));
+        }
+
+        // The single direction can be dropped and a missing axis is inserted.
+        let (values, _) = data.to_axis_order(&["ant", "time", "freq", "pol"]).unwrap();
+        assert_eq!(values.shape(), &[4, 2, 3, 2]);
+        assert_eq!(values[[3, 1, 2, 0]], digits(&[1, 2, 3, 0, 0]));
+        let mut scalar = data.clone();
+        scalar.axes.remove(4);
+        scalar.values = data.values.index_axis(Axis(4), 1).to_owned();
+        scalar.weights = data.weights.index_axis(Axis(4), 1).to_owned();
+        let (values, weights) = scalar
+            .to_axis_order(&["time", "freq", "ant", "dir", "pol"])
+            .unwrap();
+        assert_eq!(values.shape(), &[2, 3, 4, 1, 1]);
+        assert_eq!(weights.shape(), values.shape());
+        assert_eq!(values[[1, 2, 3, 0, 0]], digits(&[1, 2, 3, 0, 1]));
+
+        // Unknown and duplicate axes, and longer axes missing from the order, are errors.
+        assert!(data
+            .to_axis_order(&["time", "freq", "ant", "dir", "pol", "sub"])
+            .unwrap_err()
+            .is::<UnknownAxisError>());
+        assert!(data
+            .to_axis_order(&["time", "freq", "ant", "pol", "time"])
+            .unwrap_err()
+            .is::<DuplicateAxisError>());
+        assert!(soltab
+            .read_in_axis_order(&["time", "freq", "dir", "pol"])
+            .unwrap_err()
+            .is::<UnorderedAxisError>());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn kinds_by_title() {
+        use SolTabKind::*;
+
+        let turn = Some(2.0 * std::f64::consts::PI);
+        let kinds = [
+            ("amplitude", Amplitude, "", None),
+            ("clock", Clock, "s", None),
+            ("complexgain", ComplexGain, "", None),
+            ("error", Error, "", None),
+            ("fulljones", FullJones, "", None),
+            ("phase", Phase, "rad", turn),
+            ("phase_offset", PhaseOffset, "rad", turn),
+            ("rotation", Rotation, "rad", turn),
+            ("rotationmeasure", RotationMeasure, "rad/m^2", None),
+            ("scalaramplitude", ScalarAmplitude, "", None),
+            ("scalarphase", ScalarPhase, "rad", turn),
+            ("tec", Tec, "TECU", None),
+            ("tec3rd", Tec3rd, "TECU", None),
+            ("gain", Unknown("gain".to_string()), "", None),
+        ];
+        for (title, kind, unit, period) in kinds {
+            assert_eq!(SolTabKind::from_title(title), kind);
+            assert_eq!(kind.title(), title);
+            assert_eq!(SolTabKind::from_title(kind.title()), kind);
+            assert_eq!(kind.to_string(), title);
+            assert_eq!(kind.unit(), unit);
+            assert_eq!(kind.wrap_period(), period);
+            assert_eq!(kind.is_phase(), period.is_some());
+        }
+    }
+
+    #[test]
+    fn axes_by_name() {
+        use crate::Axis;
+
+        for name in ["time", "freq", "ant", "dir", "pol", "sub"] {
+            assert_eq!(Axis::from(name).name(), name);
+            assert_eq!(Axis::from(name).to_string(), name);
+        }
+        assert_eq!(Axis::from("dir"), Axis::Dir);
+        assert_eq!(Axis::from("sub"), Axis::Other("sub".to_string()));
+        assert_eq!(Axis::from("Time"), Axis::Other("Time".to_string()));
+
+        let path = temp_h5parm("axis_info");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Amplitude,
+            vec![("ant", names("CS", 3)), ("time", times(2))],
+            digits,
+        );
+        let soltab = solset.create_soltab("amplitude000", &data).unwrap();
+        let info = soltab.axis_info().unwrap();
+        assert_eq!(info.len(), 2);
+        assert_eq!(
+            (&info[0].axis, info[0].index, info[0].length),
+            (&Axis::Ant, 0, 3)
+        );
+        assert_eq!(info[1].coords, times(2));
+        assert_eq!(
+            soltab.find_axis(&Axis::Time).unwrap(),
+            Some(info[1].clone())
+        );
+        assert_eq!(soltab.find_axis(&Axis::Freq).unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn copy_rename_and_delete_soltabs() {
+        let path = temp_h5parm("copy_soltab");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Phase,
+            vec![("time", times(3)), ("ant", names("CS", 2))],
+            digits,
+        );
+        solset.create_soltab("phase000", &data).unwrap();
+        solset
+            .get_soltab("phase000".to_string())
+            .unwrap()
+            .add_history("created")
+            .unwrap();
+        solset.create_soltab("phase001", &data).unwrap();
+
+        let copy = solset.copy_soltab("phase000", "phase002").unwrap();
+        assert_eq!(copy.read_data().unwrap().values, data.values);
+        assert_eq!(copy.get_history().len(), 1);
+        assert!(solset.copy_soltab("phase000", "phase001").is_err());
+        assert!(solset.copy_soltab("phase009", "phase003").is_err());
+        assert!(solset.rename_soltab("phase002", "phase001").is_err());
+        solset.rename_soltab("phase002", "phase003").unwrap();
+        solset.delete_soltab("phase000").unwrap();
+        assert!(solset.delete_soltab("phase000").is_err());
+        drop(h5);
+
+        // The changes are in the file rather than only in the loaded SolSet.
+        let h5 = H5parm::open(&path, true).unwrap();
+        let solset = h5.get_solset("sol000".to_string()).unwrap();
+        let mut names = solset.get_soltab_names();
+        names.sort();
+        assert_eq!(names, vec!["phase001", "phase003"]);
+        let renamed = solset.get_soltab("phase003".to_string()).unwrap();
+        assert_eq!(renamed.read_data().unwrap().values, data.values);
+        assert!(renamed.get_history()[0].ends_with("created"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn copy_rename_and_delete_solsets() {
+        let path = temp_h5parm("copy_solset");
+        let other_path = temp_h5parm("copy_solset_other");
+        let data = fixture(SolTabKind::Amplitude, vec![("freq", freqs(4))], digits);
+        let mut other = H5parm::create(&other_path).unwrap();
+        other
+            .create_solset("sol000")
+            .unwrap()
+            .create_soltab("amplitude000", &data)
+            .unwrap();
+        let mut h5 = H5parm::create(&path).unwrap();
+        h5.create_solset("sol000").unwrap();
+
+        // A copy from another file cannot overwrite an existing solset.
+        assert!(h5.copy_solset_from(&other, "sol000", "sol000").is_err());
+        let copy = h5.copy_solset_from(&other, "sol000", "sol001").unwrap();
+        assert_eq!(copy.get_soltab_names(), vec!["amplitude000"]);
+        h5.copy_solset("sol001", "sol002").unwrap();
+        assert!(h5.copy_solset("sol001", "sol002").is_err());
+        assert!(h5.rename_solset("sol002", "sol000").is_err());
+        h5.rename_solset("sol002", "sol003").unwrap();
+        let renamed = h5.get_solset("sol003".to_string()).unwrap();
+        let read = renamed
+            .get_soltab("amplitude000".to_string())
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(read.values, data.values);
+        h5.delete_solset("sol001").unwrap();
+        assert!(h5.delete_solset("sol001").is_err());
+        drop(h5);
+
+        let h5 = H5parm::open(&path, true).unwrap();
+        let mut names = h5.get_solset_names();
+        names.sort();
+        assert_eq!(names, vec!["sol000", "sol003"]);
+        assert!(h5
+            .get_solset("sol003".to_string())
+            .unwrap()
+            .has_soltab("amplitude000"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&other_path).unwrap();
+    }
+
+    #[test]
+    fn history_text_is_ascii_and_fits() {
+        assert_eq!(
+            history_text("h5o3-flag --soltab phase000"),
+            "h5o3-flag --soltab phase000"
+        );
+        assert_eq!(
+            history_text("--msout=3C196_ø.ms"),
+            "--msout=3C196_\\u{f8}.ms"
+        );
+        let long = history_text(&"é".repeat(HISTORY_LENGTH));
+        assert!(long.is_ascii());
+        assert_eq!(long.len(), HISTORY_LENGTH);
+        assert!(long.starts_with("\\u{e9}") && long.ends_with("\\u{e9..."));
+    }
+
+    #[test]
+    fn set_values_and_long_history() {
+        let path = temp_h5parm("set_values");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let data = fixture(
+            SolTabKind::Amplitude,
+            vec![("time", times(3)), ("freq", freqs(4))],
+            digits,
+        );
+        let soltab = solset.create_soltab("amplitude000", &data).unwrap();
+        let transposed = ArrayD::from_shape_fn(IxDyn(&[4, 3]), |i| digits(i.slice()))
+            .permuted_axes(IxDyn(&[1, 0]));
+        soltab.set_values(&transposed).unwrap();
+        soltab.set_weights(&transposed).unwrap();
+        let read = soltab.read_data().unwrap();
+        assert_eq!(read.values, transposed);
+        assert_eq!(read.weights[[2, 1]], 12.0);
+
+        soltab.add_history(&"x".repeat(2 * HISTORY_LENGTH)).unwrap();
+        let history = soltab.get_history();
+        assert_eq!(history.last().unwrap().len(), HISTORY_LENGTH);
+        assert!(history.last().unwrap().ends_with("x..."));
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,274 @@
+// Normalisation of amplitude solutions.
+
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use medians::Medianf64;
+use ndarray::Dimension;
+use thiserror::Error;
+
+use crate::{history_text, AxisValues, SolTab, SolTabKind, HISTORY_LENGTH};
+
+#[derive(Debug, Error)]
+#[error("Normalisation requires an amplitude SolTab, but {0} is not.")]
+struct NotAnAmplitudeSoltabError(String);
+
+#[derive(Debug, Error)]
+#[error("No unflagged amplitudes to normalise in SolTab {0}.")]
+struct NoValidSamplesError(String);
+
+/// Statistic that is normalised to the target value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormStatistic {
+    Mean,
+    Median,
+}
+
+/// Settings for the amplitude normalisation.
+#[derive(Debug, Clone)]
+pub struct NormaliseOptions {
+    pub statistic: NormStatistic,
+    /// Compute the statistic of the logarithm of the amplitudes, i.e. the geometric mean.
+    pub log: bool,
+    /// Value the statistic is normalised to.
+    pub target: f64,
+    /// Normalise every direction separately.
+    pub per_direction: bool,
+    /// Normalise every polarisation separately.
+    pub per_polarisation: bool,
+}
+
+impl Default for NormaliseOptions {
+    fn default() -> Self {
+        NormaliseOptions {
+            statistic: NormStatistic::Mean,
+            log: true,
+            target: 1.0,
+            per_direction: false,
+            per_polarisation: false,
+        }
+    }
+}
+
+/// Factor applied to the amplitudes of one direction and/or polarisation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormFactor {
+    /// Direction the factor applies to, or `None` if it applies to all directions.
+    pub direction: Option<String>,
+    /// Polarisation the factor applies to, or `None` if it applies to all polarisations.
+    pub polarisation: Option<String>,
+    pub factor: f64,
+}
+
+fn axis_labels(soltab: &SolTab, axis: Option<usize>) -> Result<Vec<String>, hdf5::Error> {
+    match axis {
+        Some(i) => match soltab.get_axis_values(&soltab.get_axes()[i])? {
+            AxisValues::Text(labels) => Ok(labels),
+            AxisValues::Float(x) => Ok(x.iter().map(|v| v.to_string()).collect()),
+        },
+        None => Ok(vec![]),
+    }
+}
+
+/// Normalise the amplitudes of SolTab `soltab` in place so that their mean or median is `target`.
+///
+/// Only unflagged, finite (and, in log space, positive) amplitudes are used to compute the
+/// statistic. The applied factors are returned and recorded in the history of the SolTab.
+pub fn normalise(
+    soltab: &SolTab,
+    opts: &NormaliseOptions,
+) -> Result<Vec<NormFactor>, anyhow::Error> {
+    if !matches!(
+        soltab.kind,
+        SolTabKind::Amplitude | SolTabKind::ScalarAmplitude
+    ) {
+        bail!(NotAnAmplitudeSoltabError(soltab.name.clone()));
+    }
+    let axes = soltab.get_axes();
+    let dir_axis = axes
+        .iter()
+        .position(|a| a == "dir")
+        .filter(|_| opts.per_direction);
+    let pol_axis = axes
+        .iter()
+        .position(|a| a == "pol")
+        .filter(|_| opts.per_polarisation);
+    let mut values = soltab.get_values();
+    let weights = soltab.get_weights();
+
+    // Group the samples by direction and polarisation, if requested.
+    let group_of = |index: &[usize]| (dir_axis.map(|i| index[i]), pol_axis.map(|i| index[i]));
+    let mut samples: BTreeMap<(Option<usize>, Option<usize>), Vec<f64>> = BTreeMap::new();
+    for ((index, v), w) in values.indexed_iter().zip(weights.iter()) {
+        if *w > 0.0 && v.is_finite() && (!opts.log || *v > 0.0) {
+            let sample = if opts.log { v.ln() } else { *v };
+            samples
+                .entry(group_of(index.slice()))
+                .or_default()
+                .push(sample);
+        }
+    }
+    if samples.is_empty() {
+        bail!(NoValidSamplesError(soltab.name.clone()));
+    }
+
+    let factors: BTreeMap<(Option<usize>, Option<usize>), f64> = samples
+        .into_iter()
+        .map(|(group, x)| {
+            let stat = match opts.statistic {
+                NormStatistic::Mean => x.iter().sum::<f64>() / x.len() as f64,
+                NormStatistic::Median => x.as_slice().medf_unchecked(),
+            };
+            let stat = if opts.log { stat.exp() } else { stat };
+            (group, opts.target / stat)
+        })
+        .collect();
+    for (index, v) in values.indexed_iter_mut() {
+        // Groups without any unflagged samples are left untouched.
+        if let Some(f) = factors.get(&group_of(index.slice())) {
+            *v *= f;
+        }
+    }
+
+    let dirs = axis_labels(soltab, dir_axis)?;
+    let pols = axis_labels(soltab, pol_axis)?;
+    let applied: Vec<NormFactor> = factors
+        .into_iter()
+        .map(|((d, p), factor)| NormFactor {
+            direction: d.map(|d| dirs[d].clone()),
+            polarisation: p.map(|p| pols[p].clone()),
+            factor,
+        })
+        .collect();
+    let description = format!(
+        "Normalised {:?}{} amplitude to {}, applied factors (dir/pol)",
+        opts.statistic,
+        if opts.log { " log" } else { "" },
+        opts.target,
+    );
+    let factors: Vec<String> = applied
+        .iter()
+        .map(|f| {
+            format!(
+                "{}/{}={}",
+                f.direction.as_deref().unwrap_or("all"),
+                f.polarisation.as_deref().unwrap_or("all"),
+                f.factor
+            )
+        })
+        .collect();
+    // The values are written before the history, so that the history never records factors
+    // that were not applied.
+    soltab.set_values(&values)?;
+    for entry in history_entries(&description, &factors) {
+        soltab.add_history(&entry)?;
+    }
+    Ok(applied)
+}
+
+/// Split `items` over as many history entries as needed to keep every entry within
+/// [`HISTORY_LENGTH`], each starting with `description` and the part number.
+fn history_entries(description: &str, items: &[String]) -> Vec<String> {
+    // Room for the timestamp that add_history prepends and the part number.
+    let room = HISTORY_LENGTH - "YYYY-mm-dd HH:MM:SS ".len() - ", part 9999: ".len();
+    let room = room.saturating_sub(history_text(description).len());
+    let mut parts: Vec<Vec<&str>> = vec![vec![]];
+    let mut length = 0;
+    for item in items {
+        let item_length = history_text(item).len() + ", ".len();
+        let part = parts.last_mut().unwrap();
+        if !part.is_empty() && length + item_length > room {
+            parts.push(vec![item]);
+            length = item_length;
+        } else {
+            part.push(item);
+            length += item_length;
+        }
+    }
+    match parts.len() {
+        1 => vec![format!("{}: {}", description, parts[0].join(", "))],
+        n => parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| format!("{}, part {}/{}: {}", description, i + 1, n, part.join(", ")))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    #[test]
+    fn normalise_many_directions() {
+        let path = temp_h5parm("normalise");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let ndir = 300;
+        let data = fixture(
+            SolTabKind::Amplitude,
+            vec![
+                ("time", times(4)),
+                ("ant", names("CS", 2)),
+                ("dir", names("[Patch_with_a_long_name_", ndir)),
+            ],
+            |i| (1 + i[2]) as f64,
+        );
+        let soltab = solset.create_soltab("amplitude000", &data).unwrap();
+        let opts = NormaliseOptions {
+            per_direction: true,
+            ..Default::default()
+        };
+        let factors = normalise(soltab, &opts).unwrap();
+        assert_eq!(factors.len(), ndir);
+        assert_eq!(
+            factors[0].direction.as_deref(),
+            Some("[Patch_with_a_long_name_0")
+        );
+        assert!((factors[1].factor - 0.5).abs() < 1e-12);
+        assert!(soltab.get_values().iter().all(|v| (v - 1.0).abs() < 1e-12));
+        // The factors of all directions are split over several history entries.
+        let history = soltab.get_history();
+        assert!(history.len() > 1);
+        let mut recorded = BTreeMap::new();
+        for (i, entry) in history.iter().enumerate() {
+            assert!(entry.len() <= HISTORY_LENGTH);
+            let part = format!(", part {}/{}: ", i + 1, history.len());
+            let (head, list) = entry.split_once(&part).unwrap();
+            assert!(head.ends_with("Normalised Mean log amplitude to 1, applied factors (dir/pol)"));
+            for item in list.split(", ") {
+                let (label, factor) = item.split_once('=').unwrap();
+                recorded.insert(label.to_string(), factor.parse::<f64>().unwrap());
+            }
+        }
+        assert_eq!(recorded.len(), ndir);
+        for f in &factors {
+            let label = format!("{}/all", f.direction.as_deref().unwrap());
+            assert_eq!(recorded[&label], f.factor);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn history_entries_fit() {
+        let one = vec!["all/all=0.5".to_string()];
+        assert_eq!(
+            history_entries("Normalised", &one),
+            vec!["Normalised: all/all=0.5".to_string()]
+        );
+        let items: Vec<String> = (0..2000).map(|i| format!("[ø{}]/XX={}", i, i)).collect();
+        let entries = history_entries("Normalised", &items);
+        let n = entries.len();
+        assert!(n > 1);
+        let mut read = vec![];
+        for (i, entry) in entries.iter().enumerate() {
+            let stamped = format!("2026-10-18 12:00:00 {}", entry);
+            assert!(history_text(&stamped).len() <= HISTORY_LENGTH);
+            let prefix = format!("Normalised, part {}/{}: ", i + 1, n);
+            read.extend(entry.strip_prefix(&prefix).unwrap().split(", "));
+        }
+        assert_eq!(read, items);
+    }
+}
@@ -0,0 +1,992 @@
+// Reading and writing of NumPy .npy files and .npz archives, and export of SolTabs to them.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use anyhow::bail;
+use ndarray::{ArrayD, IxDyn};
+use num::complex::Complex;
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+
+use crate::{AxisValues, SolTab};
+
+#[derive(Debug, Error)]
+#[error("Invalid NPY data: {0}")]
+struct InvalidNpyError(String);
+
+#[derive(Debug, Error)]
+#[error("Unsupported NPY data type {0}.")]
+struct UnsupportedDtypeError(String);
+
+/// Data type of an NPY array, either a scalar type or a record of named fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dtype {
+    /// Scalar of NumPy kind `kind` (`b`, `i`, `u`, `f`, `c`, `S` or `U`) taking `size` bytes.
+    Scalar {
+        kind: char,
+        size: usize,
+        big_endian: bool,
+    },
+    /// Record of named fields, each with a data type and a (possibly empty) subarray shape.
+    Record(Vec<(String, Dtype, Vec<usize>)>),
+}
+
+impl Dtype {
+    pub fn float64() -> Self {
+        Dtype::Scalar {
+            kind: 'f',
+            size: 8,
+            big_endian: false,
+        }
+    }
+
+    /// Size of one element in bytes.
+    pub fn itemsize(&self) -> usize {
+        match self {
+            Dtype::Scalar { size, .. } => *size,
+            Dtype::Record(fields) => fields
+                .iter()
+                .map(|(_, dtype, shape)| dtype.itemsize() * shape.iter().product::<usize>())
+                .sum(),
+        }
+    }
+
+    /// Python literal of this type as used in the NPY header.
+    fn descr(&self) -> String {
+        match self {
+            Dtype::Scalar {
+                kind,
+                size,
+                big_endian,
+            } => {
+                let order = match (kind, size, big_endian) {
+                    ('S', _, _) | ('b', _, _) | (_, 1, _) => '|',
+                    (_, _, true) => '>',
+                    _ => '<',
+                };
+                let size = if *kind == 'U' { size / 4 } else { *size };
+                format!("'{}{}{}'", order, kind, size)
+            }
+            Dtype::Record(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, dtype, shape)| match shape.len() {
+                        0 => format!("('{}', {})", name, dtype.descr()),
+                        _ => format!("('{}', {}, {})", name, dtype.descr(), shape_literal(shape)),
+                    })
+                    .collect();
+                format!("[{}]", fields.join(", "))
+            }
+        }
+    }
+
+    fn from_literal(value: &PyValue) -> Result<Self, anyhow::Error> {
+        match value {
+            PyValue::Str(s) => {
+                let (big_endian, rest) = match s.chars().next() {
+                    Some('>') => (true, &s[1..]),
+                    Some('<') | Some('|') | Some('=') => (false, &s[1..]),
+                    _ => (false, s.as_str()),
+                };
+                let kind = match rest.chars().next() {
+                    Some(k) if "biufcSU".contains(k) => k,
+                    _ => bail!(UnsupportedDtypeError(s.clone())),
+                };
+                let size: usize = match rest[1..].parse() {
+                    Ok(n) => n,
+                    Err(_) => bail!(UnsupportedDtypeError(s.clone())),
+                };
+                Ok(Dtype::Scalar {
+                    kind,
+                    size: if kind == 'U' { size * 4 } else { size },
+                    big_endian,
+                })
+            }
+            PyValue::List(fields) => {
+                let mut record = vec![];
+                for field in fields {
+                    match field {
+                        PyValue::Tuple(f) if f.len() == 2 || f.len() == 3 => {
+                            let name = match &f[0] {
+                                PyValue::Str(name) => name.clone(),
+                                _ => bail!(UnsupportedDtypeError(format!("{:?}", field))),
+                            };
+                            let shape = match f.get(2) {
+                                Some(s) => s.to_shape()?,
+                                None => vec![],
+                            };
+                            record.push((name, Dtype::from_literal(&f[1])?, shape));
+                        }
+                        _ => bail!(UnsupportedDtypeError(format!("{:?}", field))),
+                    }
+                }
+                Ok(Dtype::Record(record))
+            }
+            _ => bail!(UnsupportedDtypeError(format!("{:?}", value))),
+        }
+    }
+}
+
+fn shape_literal(shape: &[usize]) -> String {
+    match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// The subset of Python literals that occurs in NPY headers.
+#[derive(Debug, Clone, PartialEq)]
+enum PyValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    None,
+    Tuple(Vec<PyValue>),
+    List(Vec<PyValue>),
+    Dict(Vec<(PyValue, PyValue)>),
+}
+
+impl PyValue {
+    fn to_shape(&self) -> Result<Vec<usize>, anyhow::Error> {
+        match self {
+            PyValue::Int(n) if *n >= 0 => Ok(vec![*n as usize]),
+            PyValue::Tuple(items) | PyValue::List(items) => items
+                .iter()
+                .map(|i| match i {
+                    PyValue::Int(n) if *n >= 0 => Ok(*n as usize),
+                    _ => Err(InvalidNpyError(format!("invalid shape {:?}", self)).into()),
+                })
+                .collect(),
+            _ => bail!(InvalidNpyError(format!("invalid shape {:?}", self))),
+        }
+    }
+}
+
+struct LiteralParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl LiteralParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), anyhow::Error> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(n) if n == c => Ok(()),
+            n => bail!(InvalidNpyError(format!("expected {} but found {:?}", c, n))),
+        }
+    }
+
+    /// Parse comma-separated items up to `close`, allowing a trailing comma.
+    fn sequence(&mut self, close: char) -> Result<(Vec<PyValue>, bool), anyhow::Error> {
+        let mut items = vec![];
+        let mut trailing_comma = false;
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() == Some(&close) {
+                self.chars.next();
+                return Ok((items, trailing_comma));
+            }
+            items.push(self.value()?);
+            self.skip_whitespace();
+            trailing_comma = self.chars.peek() == Some(&',');
+            if trailing_comma {
+                self.chars.next();
+            } else {
+                self.expect(close)?;
+                return Ok((items, false));
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<PyValue, anyhow::Error> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(quote) if quote == '\'' || quote == '"' => {
+                let mut s = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some(c) if c == quote => return Ok(PyValue::Str(s)),
+                        Some('\\') => s.extend(self.chars.next()),
+                        Some(c) => s.push(c),
+                        None => bail!(InvalidNpyError("unterminated string".to_string())),
+                    }
+                }
+            }
+            Some('(') => {
+                let (items, trailing_comma) = self.sequence(')')?;
+                // A parenthesised single value without a comma is not a tuple.
+                if items.len() == 1 && !trailing_comma {
+                    Ok(items[0].clone())
+                } else {
+                    Ok(PyValue::Tuple(items))
+                }
+            }
+            Some('[') => Ok(PyValue::List(self.sequence(']')?.0)),
+            Some('{') => {
+                let mut entries = vec![];
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.peek() == Some(&'}') {
+                        self.chars.next();
+                        return Ok(PyValue::Dict(entries));
+                    }
+                    let key = self.value()?;
+                    self.expect(':')?;
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    if self.chars.peek() == Some(&',') {
+                        self.chars.next();
+                    } else {
+                        self.expect('}')?;
+                        return Ok(PyValue::Dict(entries));
+                    }
+                }
+            }
+            Some(c) if c.is_alphanumeric() || c == '-' => {
+                let mut token = c.to_string();
+                while self.chars.peek().is_some_and(|c| c.is_alphanumeric()) {
+                    token.extend(self.chars.next());
+                }
+                match token.as_str() {
+                    "True" => Ok(PyValue::Bool(true)),
+                    "False" => Ok(PyValue::Bool(false)),
+                    "None" => Ok(PyValue::None),
+                    _ => match token.trim_end_matches('L').parse() {
+                        Ok(n) => Ok(PyValue::Int(n)),
+                        Err(_) => bail!(InvalidNpyError(format!("unexpected token {}", token))),
+                    },
+                }
+            }
+            c => bail!(InvalidNpyError(format!("unexpected character {:?}", c))),
+        }
+    }
+}
+
+/// An array read from or to be written to an NPY file, holding its elements as raw bytes in C
+/// order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpyArray {
+    pub dtype: Dtype,
+    pub shape: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+impl NpyArray {
+    pub fn from_f64(array: &ArrayD<f64>) -> Self {
+        let mut data = Vec::with_capacity(array.len() * 8);
+        for v in array.iter() {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        NpyArray {
+            dtype: Dtype::float64(),
+            shape: array.shape().to_vec(),
+            data,
+        }
+    }
+
+    pub fn from_complex(array: &ArrayD<Complex<f64>>) -> Self {
+        let mut data = Vec::with_capacity(array.len() * 16);
+        for v in array.iter() {
+            data.extend_from_slice(&v.re.to_le_bytes());
+            data.extend_from_slice(&v.im.to_le_bytes());
+        }
+        NpyArray {
+            dtype: Dtype::Scalar {
+                kind: 'c',
+                size: 16,
+                big_endian: false,
+            },
+            shape: array.shape().to_vec(),
+            data,
+        }
+    }
+
+    /// Complex array stored in single precision, as e.g. killMS does.
+    pub fn from_complex64(array: &ArrayD<Complex<f64>>) -> Self {
+        let mut data = Vec::with_capacity(array.len() * 8);
+        for v in array.iter() {
+            data.extend_from_slice(&(v.re as f32).to_le_bytes());
+            data.extend_from_slice(&(v.im as f32).to_le_bytes());
+        }
+        NpyArray {
+            dtype: Dtype::Scalar {
+                kind: 'c',
+                size: 8,
+                big_endian: false,
+            },
+            shape: array.shape().to_vec(),
+            data,
+        }
+    }
+
+    pub fn from_i64(array: &ArrayD<i64>) -> Self {
+        let mut data = Vec::with_capacity(array.len() * 8);
+        for v in array.iter() {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        NpyArray {
+            dtype: Dtype::Scalar {
+                kind: 'i',
+                size: 8,
+                big_endian: false,
+            },
+            shape: array.shape().to_vec(),
+            data,
+        }
+    }
+
+    /// 1D array of fixed-length unicode strings, as NumPy uses for `str` arrays.
+    pub fn from_strings(values: &[String]) -> Self {
+        let width = values
+            .iter()
+            .map(|v| v.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut data = Vec::with_capacity(values.len() * width * 4);
+        for v in values {
+            let n = v.chars().count();
+            for c in v.chars().chain(std::iter::repeat('\0').take(width - n)) {
+                data.extend_from_slice(&(c as u32).to_le_bytes());
+            }
+        }
+        NpyArray {
+            dtype: Dtype::Scalar {
+                kind: 'U',
+                size: width * 4,
+                big_endian: false,
+            },
+            shape: vec![values.len()],
+            data,
+        }
+    }
+
+    /// 1D array of fixed-length byte strings, as NumPy uses for `bytes` arrays.
+    pub fn from_bytes(values: &[String], width: usize) -> Self {
+        let mut data = Vec::with_capacity(values.len() * width);
+        for v in values {
+            let mut bytes = v.as_bytes().to_vec();
+            bytes.resize(width, 0);
+            data.extend_from_slice(&bytes);
+        }
+        NpyArray {
+            dtype: Dtype::Scalar {
+                kind: 'S',
+                size: width,
+                big_endian: false,
+            },
+            shape: vec![values.len()],
+            data,
+        }
+    }
+
+    /// Combine arrays into a 1D record array. The first axis of every field becomes the record
+    /// axis and the remaining axes the subarray shape of the field.
+    pub fn from_fields(fields: Vec<(String, NpyArray)>) -> Result<Self, anyhow::Error> {
+        let n = match fields.first() {
+            Some((_, f)) if !f.shape.is_empty() => f.shape[0],
+            _ => bail!(InvalidNpyError(
+                "record fields must be at least 1D".to_string()
+            )),
+        };
+        if fields.iter().any(|(_, f)| f.shape.first() != Some(&n)) {
+            bail!(InvalidNpyError(
+                "record fields must have the same length".to_string()
+            ));
+        }
+        let dtype = Dtype::Record(
+            fields
+                .iter()
+                .map(|(name, f)| (name.clone(), f.dtype.clone(), f.shape[1..].to_vec()))
+                .collect(),
+        );
+        let mut data = Vec::with_capacity(n * dtype.itemsize());
+        for i in 0..n {
+            for (_, f) in fields.iter() {
+                let size = f.data.len() / n;
+                data.extend_from_slice(&f.data[i * size..(i + 1) * size]);
+            }
+        }
+        Ok(NpyArray {
+            dtype,
+            shape: vec![n],
+            data,
+        })
+    }
+
+    /// Extract field `name` of a record array. Its subarray shape is appended to the shape.
+    pub fn field(&self, name: &str) -> Result<NpyArray, anyhow::Error> {
+        let fields = match &self.dtype {
+            Dtype::Record(fields) => fields,
+            _ => bail!(InvalidNpyError(format!(
+                "no field {} in a scalar array",
+                name
+            ))),
+        };
+        let mut offset = 0;
+        for (field_name, dtype, subshape) in fields.iter() {
+            let size = dtype.itemsize() * subshape.iter().product::<usize>();
+            if field_name == name {
+                let itemsize = self.dtype.itemsize();
+                let n: usize = self.shape.iter().product();
+                let mut data = Vec::with_capacity(n * size);
+                for i in 0..n {
+                    let start = i * itemsize + offset;
+                    data.extend_from_slice(&self.data[start..start + size]);
+                }
+                let mut shape = self.shape.clone();
+                shape.extend_from_slice(subshape);
+                return Ok(NpyArray {
+                    dtype: dtype.clone(),
+                    shape,
+                    data,
+                });
+            }
+            offset += size;
+        }
+        bail!(InvalidNpyError(format!("no field {}", name)))
+    }
+
+    fn scalar_kind(&self) -> Result<(char, usize, bool), anyhow::Error> {
+        match self.dtype {
+            Dtype::Scalar {
+                kind,
+                size,
+                big_endian,
+            } => Ok((kind, size, big_endian)),
+            _ => bail!(UnsupportedDtypeError(self.dtype.descr())),
+        }
+    }
+
+    /// Element bytes in little-endian order.
+    fn elements(&self, size: usize, big_endian: bool) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.data.chunks_exact(size).map(move |c| {
+            let mut c = c.to_vec();
+            if big_endian {
+                c.reverse();
+            }
+            c
+        })
+    }
+
+    fn real_values(
+        &self,
+        kind: char,
+        size: usize,
+        big_endian: bool,
+    ) -> Result<Vec<f64>, anyhow::Error> {
+        self.elements(size, big_endian)
+            .map(|b| match (kind, size) {
+                ('f', 4) => Ok(f32::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('f', 8) => Ok(f64::from_le_bytes(b.try_into().unwrap())),
+                ('i', 1) => Ok(i8::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('i', 2) => Ok(i16::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('i', 4) => Ok(i32::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('i', 8) => Ok(i64::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('u', 1) | ('b', 1) => Ok(b[0] as f64),
+                ('u', 2) => Ok(u16::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('u', 4) => Ok(u32::from_le_bytes(b.try_into().unwrap()) as f64),
+                ('u', 8) => Ok(u64::from_le_bytes(b.try_into().unwrap()) as f64),
+                _ => Err(UnsupportedDtypeError(self.dtype.descr()).into()),
+            })
+            .collect()
+    }
+
+    /// Convert a real-valued numeric array to float64.
+    pub fn to_f64(&self) -> Result<ArrayD<f64>, anyhow::Error> {
+        let (kind, size, big_endian) = self.scalar_kind()?;
+        let values = self.real_values(kind, size, big_endian)?;
+        Ok(ArrayD::from_shape_vec(IxDyn(&self.shape), values)?)
+    }
+
+    /// Convert a numeric array to complex float64.
+    pub fn to_complex(&self) -> Result<ArrayD<Complex<f64>>, anyhow::Error> {
+        let (kind, size, big_endian) = self.scalar_kind()?;
+        let values: Vec<Complex<f64>> = match kind {
+            'c' => {
+                // Real and imaginary parts are stored, and byte swapped, separately.
+                let parts = self.real_values('f', size / 2, big_endian)?;
+                parts
+                    .chunks_exact(2)
+                    .map(|c| Complex::new(c[0], c[1]))
+                    .collect()
+            }
+            _ => self
+                .real_values(kind, size, big_endian)?
+                .into_iter()
+                .map(|v| Complex::new(v, 0.0))
+                .collect(),
+        };
+        Ok(ArrayD::from_shape_vec(IxDyn(&self.shape), values)?)
+    }
+
+    /// Convert an array of byte or unicode strings to strings, dropping the padding.
+    pub fn to_strings(&self) -> Result<Vec<String>, anyhow::Error> {
+        let (kind, size, big_endian) = self.scalar_kind()?;
+        match kind {
+            'S' => Ok(self
+                .data
+                .chunks_exact(size)
+                .map(|b| {
+                    String::from_utf8_lossy(b)
+                        .trim_end_matches('\0')
+                        .to_string()
+                })
+                .collect()),
+            'U' => Ok(self
+                .data
+                .chunks_exact(size)
+                .map(|b| {
+                    b.chunks_exact(4)
+                        .map(|c| {
+                            let c: [u8; 4] = c.try_into().unwrap();
+                            if big_endian {
+                                u32::from_be_bytes(c)
+                            } else {
+                                u32::from_le_bytes(c)
+                            }
+                        })
+                        .take_while(|&c| c != 0)
+                        .filter_map(char::from_u32)
+                        .collect()
+                })
+                .collect()),
+            _ => bail!(UnsupportedDtypeError(self.dtype.descr())),
+        }
+    }
+}
+
+/// Write `array` in NPY version 1.0 format.
+pub fn write_npy(out: &mut impl Write, array: &NpyArray) -> std::io::Result<()> {
+    let mut header = format!(
+        "{{'descr': {}, 'fortran_order': False, 'shape': {}, }}",
+        array.dtype.descr(),
+        shape_literal(&array.shape)
+    );
+    // Magic, version and header length take 10 bytes; NumPy aligns the data to 64 bytes.
+    let total = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - total % 64) % 64));
+    header.push('\n');
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+    out.write_all(&array.data)
+}
+
+/// Write `array` as a little-endian float64 NPY array in C order.
+pub fn write_npy_f64(out: &mut impl Write, array: &ArrayD<f64>) -> std::io::Result<()> {
+    write_npy(out, &NpyArray::from_f64(array))
+}
+
+/// Write `values` as a 1D NPY array of fixed-length unicode strings.
+pub fn write_npy_str(out: &mut impl Write, values: &[String]) -> std::io::Result<()> {
+    write_npy(out, &NpyArray::from_strings(values))
+}
+
+/// Read an NPY file of format version 1, 2 or 3. Fortran-ordered arrays are not supported.
+pub fn read_npy(input: &mut impl Read) -> Result<NpyArray, anyhow::Error> {
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic[..6] != b"\x93NUMPY" {
+        bail!(InvalidNpyError("not an NPY file".to_string()));
+    }
+    let header_len = match magic[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            input.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            input.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        v => bail!(InvalidNpyError(format!("unsupported version {}", v))),
+    };
+    let mut header = vec![0u8; header_len];
+    input.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+    let mut parser = LiteralParser {
+        chars: header.chars().peekable(),
+    };
+    let entries = match parser.value()? {
+        PyValue::Dict(entries) => entries,
+        _ => bail!(InvalidNpyError("header is not a dictionary".to_string())),
+    };
+    let get = |key: &str| {
+        entries
+            .iter()
+            .find(|(k, _)| *k == PyValue::Str(key.to_string()))
+            .map(|(_, v)| v)
+    };
+    if get("fortran_order") == Some(&PyValue::Bool(true)) {
+        bail!(InvalidNpyError(
+            "Fortran-ordered arrays are not supported".to_string()
+        ));
+    }
+    let dtype = match get("descr") {
+        Some(descr) => Dtype::from_literal(descr)?,
+        None => bail!(InvalidNpyError("missing descr".to_string())),
+    };
+    let shape = match get("shape") {
+        Some(shape) => shape.to_shape()?,
+        None => bail!(InvalidNpyError("missing shape".to_string())),
+    };
+    let mut data = vec![0u8; shape.iter().product::<usize>() * dtype.itemsize()];
+    input.read_exact(&mut data)?;
+    Ok(NpyArray { dtype, shape, data })
+}
+
+/// Read all arrays of an `.npz` archive, compressed or not, by name without `.npy` suffix.
+pub fn read_npz(path: &str) -> Result<BTreeMap<String, NpyArray>, anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut arrays = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().trim_end_matches(".npy").to_string();
+        arrays.insert(name, read_npy(&mut entry)?);
+    }
+    Ok(arrays)
+}
+
+/// Write `arrays` to an uncompressed `.npz` archive at `path`, as written by `numpy.savez`.
+pub fn write_npz(path: &str, arrays: &[(String, NpyArray)]) -> Result<(), anyhow::Error> {
+    let mut archive = zip::ZipWriter::new(std::fs::File::create(path)?);
+    for (name, array) in arrays {
+        let mut data = vec![];
+        write_npy(&mut data, array)?;
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .large_file(data.len() as u64 >= u32::MAX as u64);
+        archive.start_file(format!("{}.npy", name), options)?;
+        archive.write_all(&data)?;
+    }
+    archive.finish()?;
+    Ok(())
+}
+
+/// The values, weights, axis names and axis coordinates of `soltab` as named arrays: `val`,
+/// `weight`, `axes` and one array per axis.
+fn soltab_arrays(soltab: &SolTab) -> Result<Vec<(String, NpyArray)>, anyhow::Error> {
+    let axes = soltab.get_axes();
+    let mut arrays = vec![
+        ("val".to_string(), NpyArray::from_f64(&soltab.get_values())),
+        (
+            "weight".to_string(),
+            NpyArray::from_f64(&soltab.get_weights()),
+        ),
+        ("axes".to_string(), NpyArray::from_strings(&axes)),
+    ];
+    for axis in axes.iter() {
+        let array = match soltab.get_axis_values(axis)? {
+            AxisValues::Float(x) => NpyArray::from_f64(&x.into_dyn()),
+            AxisValues::Text(x) => NpyArray::from_strings(&x),
+        };
+        arrays.push((axis.clone(), array));
+    }
+    Ok(arrays)
+}
+
+/// Export `soltab` to `val.npy`, `weight.npy`, `axes.npy` and `<axis>.npy` for every axis in
+/// directory `dir`, which is created if needed. `axes.npy` holds the axis names in order.
+pub fn export_npy(soltab: &SolTab, dir: &str) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(dir)?;
+    for (name, array) in soltab_arrays(soltab)? {
+        let path = std::path::Path::new(dir).join(format!("{}.npy", name));
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        write_npy(&mut out, &array)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// Export `soltab` to an uncompressed `.npz` archive at `path`, as written by `numpy.savez`.
+///
+/// The archive holds the same arrays as written by [`export_npy`].
+pub fn export_npz(soltab: &SolTab, path: &str) -> Result<(), anyhow::Error> {
+    write_npz(path, &soltab_arrays(soltab)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use ndarray::{Array1, Array2};
+
+    use super::*;
+    use crate::testing::{digits, fixture, freqs, names, temp_h5parm, times};
+    use crate::{H5parm, SolTabKind};
+
+    fn round_trip(array: &NpyArray) -> NpyArray {
+        let mut bytes = vec![];
+        write_npy(&mut bytes, array).unwrap();
+        read_npy(&mut bytes.as_slice()).unwrap()
+    }
+
+    /// An NPY file of format `version` with `header` padded to 64 bytes, followed by `data`.
+    fn npy(version: u8, header: &str, data: &[u8]) -> Vec<u8> {
+        let len_size = if version == 1 { 2 } else { 4 };
+        let mut header = header.to_string();
+        let total = 8 + len_size + header.len() + 1;
+        header.push_str(&" ".repeat((64 - total % 64) % 64));
+        header.push('\n');
+        let mut bytes = b"\x93NUMPY".to_vec();
+        bytes.extend([version, 0]);
+        match version {
+            1 => bytes.extend((header.len() as u16).to_le_bytes()),
+            _ => bytes.extend((header.len() as u32).to_le_bytes()),
+        }
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    /// Split an NPY file into its header and data.
+    fn parse(bytes: &[u8]) -> (String, &[u8]) {
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + len]).unwrap();
+        (header.trim_end().to_string(), &bytes[10 + len..])
+    }
+
+    #[test]
+    fn scalar_arrays_round_trip() {
+        let values = ArrayD::from_shape_fn(IxDyn(&[2, 3]), |i| i[0] as f64 - 0.25 * i[1] as f64);
+        let read = round_trip(&NpyArray::from_f64(&values));
+        assert_eq!(read.dtype.descr(), "'<f8'");
+        assert_eq!(read.to_f64().unwrap(), values);
+
+        let gains = values.mapv(|v| Complex::new(v, -2.0 * v));
+        let read = round_trip(&NpyArray::from_complex(&gains));
+        assert_eq!(read.dtype.descr(), "'<c16'");
+        assert_eq!(read.to_complex().unwrap(), gains);
+
+        let stations = vec!["CS001HBA0".to_string(), "Dwingeloø".to_string()];
+        let read = round_trip(&NpyArray::from_strings(&stations));
+        assert_eq!(read.dtype.descr(), "'<U9'");
+        assert_eq!(read.to_strings().unwrap(), stations);
+
+        let read = round_trip(&NpyArray::from_bytes(&stations[..1], 16));
+        assert_eq!(read.dtype.descr(), "'|S16'");
+        assert_eq!(read.to_strings().unwrap(), &stations[..1]);
+    }
+
+    #[test]
+    fn record_arrays_round_trip() {
+        let t0 = Array1::from(vec![10.0, 20.0]).into_dyn();
+        let gains = ArrayD::from_shape_fn(IxDyn(&[2, 2, 2]), |i| {
+            Complex::new(i[0] as f64, (i[1] + 2 * i[2]) as f64)
+        });
+        let names = vec!["P0".to_string(), "P1".to_string()];
+        let record = NpyArray::from_fields(vec![
+            ("t0".to_string(), NpyArray::from_f64(&t0)),
+            ("G".to_string(), NpyArray::from_complex64(&gains)),
+            ("Name".to_string(), NpyArray::from_bytes(&names, 8)),
+        ])
+        .unwrap();
+        assert_eq!(
+            record.dtype.descr(),
+            "[('t0', '<f8'), ('G', '<c8', (2, 2)), ('Name', '|S8')]"
+        );
+        let read = round_trip(&record);
+        assert_eq!(read, record);
+        assert_eq!(read.field("t0").unwrap().to_f64().unwrap(), t0);
+        let g = read.field("G").unwrap();
+        assert_eq!(g.shape, vec![2, 2, 2]);
+        assert_eq!(g.to_complex().unwrap(), gains);
+        assert_eq!(read.field("Name").unwrap().to_strings().unwrap(), names);
+        assert!(read.field("t1").is_err());
+    }
+
+    #[test]
+    fn f64_arrays_are_written_in_c_order() {
+        let array = Array2::from_shape_vec((2, 3), vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0])
+            .unwrap()
+            .reversed_axes()
+            .into_dyn();
+        let mut bytes = vec![];
+        write_npy_f64(&mut bytes, &array).unwrap();
+        let (header, data) = parse(&bytes);
+        assert_eq!(
+            header,
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (3, 2), }"
+        );
+        let values: Vec<f64> = data
+            .chunks(8)
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0]);
+    }
+
+    #[test]
+    fn strings_are_padded_unicode() {
+        let stations = vec![
+            "CS001HBA0".to_string(),
+            "Dwingeloø".to_string(),
+            "".to_string(),
+        ];
+        let mut bytes = vec![];
+        write_npy_str(&mut bytes, &stations).unwrap();
+        let (header, data) = parse(&bytes);
+        assert_eq!(
+            header,
+            "{'descr': '<U9', 'fortran_order': False, 'shape': (3,), }"
+        );
+        let chars: Vec<u32> = data
+            .chunks(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(chars.len(), 3 * 9);
+        assert_eq!(chars[17], 'ø' as u32);
+        assert!(chars[18..].iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn data_is_aligned_to_64_bytes() {
+        for n in [0, 1, 7, 100] {
+            let array = NpyArray::from_strings(&vec!["x".repeat(n); 3]);
+            let mut bytes = vec![];
+            write_npy(&mut bytes, &array).unwrap();
+            let offset = bytes.len() - array.data.len();
+            assert_eq!(offset % 64, 0);
+            assert_eq!(bytes[offset - 1], b'\n');
+        }
+    }
+
+    #[test]
+    fn later_versions_and_big_endian_data() {
+        let data: Vec<u8> = [1.5f64, -2.0]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        let header = "{'descr': '>f8', 'fortran_order': False, 'shape': (2,), }";
+        let read = read_npy(&mut npy(2, header, &data).as_slice()).unwrap();
+        assert_eq!(read.to_f64().unwrap().into_raw_vec(), vec![1.5, -2.0]);
+
+        // Real and imaginary parts are swapped separately.
+        let data: Vec<u8> = [1.0f32, 3.0].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let header = "{'descr': '>c8', 'fortran_order': False, 'shape': (), }";
+        let read = read_npy(&mut npy(3, header, &data).as_slice()).unwrap();
+        assert_eq!(
+            read.to_complex().unwrap()[IxDyn(&[])],
+            Complex::new(1.0, 3.0)
+        );
+
+        let data: Vec<u8> = "RS106\0"
+            .chars()
+            .flat_map(|c| (c as u32).to_be_bytes())
+            .collect();
+        let header = "{'descr': '>U3', 'fortran_order': False, 'shape': (2,), }";
+        let read = read_npy(&mut npy(3, header, &data).as_slice()).unwrap();
+        assert_eq!(read.to_strings().unwrap(), vec!["RS1", "06"]);
+    }
+
+    #[test]
+    fn unsupported_files_are_rejected() {
+        let data = [0u8; 16];
+        let header = "{'descr': '<f8', 'fortran_order': True, 'shape': (2,), }";
+        assert!(read_npy(&mut npy(1, header, &data).as_slice()).is_err());
+        let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }";
+        assert!(read_npy(&mut npy(1, header, &data).as_slice()).is_ok());
+        assert!(read_npy(&mut npy(4, header, &data).as_slice()).is_err());
+        let header = "{'descr': '<m8', 'fortran_order': False, 'shape': (2,), }";
+        assert!(read_npy(&mut npy(1, header, &data).as_slice()).is_err());
+    }
+
+    #[test]
+    fn npz_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("h5o3-test-{}-npz.npz", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let arrays = vec![
+            (
+                "val".to_string(),
+                NpyArray::from_f64(&ArrayD::from_elem(IxDyn(&[2, 2]), 0.5)),
+            ),
+            (
+                "axes".to_string(),
+                NpyArray::from_strings(&["time".to_string(), "freq".to_string()]),
+            ),
+        ];
+        write_npz(&path, &arrays).unwrap();
+        let read = read_npz(&path).unwrap();
+        assert_eq!(read.len(), 2);
+        for (name, array) in arrays.iter() {
+            assert_eq!(&read[name], array);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn export_npy_and_npz() {
+        let path = temp_h5parm("export_npy");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let data = fixture(
+            SolTabKind::Phase,
+            vec![
+                ("time", times(3)),
+                ("freq", freqs(2)),
+                ("ant", names("CS", 2)),
+            ],
+            digits,
+        );
+        let soltab = h5
+            .create_solset("sol000")
+            .unwrap()
+            .create_soltab("phase000", &data)
+            .unwrap();
+        let dir = format!("{}.npy.d", path);
+        export_npy(soltab, &dir).unwrap();
+        let npz = format!("{}.npz", path);
+        export_npz(soltab, &npz).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&npz).unwrap()).unwrap();
+        let mut files: Vec<String> = archive.file_names().map(String::from).collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "ant.npy",
+                "axes.npy",
+                "freq.npy",
+                "time.npy",
+                "val.npy",
+                "weight.npy"
+            ]
+        );
+        for name in files {
+            let mut archived = vec![];
+            archive
+                .by_name(&name)
+                .unwrap()
+                .read_to_end(&mut archived)
+                .unwrap();
+            assert_eq!(
+                archived,
+                std::fs::read(format!("{}/{}", dir, name)).unwrap()
+            );
+        }
+        let val = std::fs::read(format!("{}/val.npy", dir)).unwrap();
+        let (header, values) = parse(&val);
+        assert!(header.contains("'shape': (3, 2, 2)"));
+        let expected: Vec<u8> = data.values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(values, expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&npz).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,516 @@
+// Plotting of SolTabs as per-station time series and time-frequency waterfalls.
+
+use std::path::Path;
+use std::sync::Once;
+
+use anyhow::bail;
+use ndarray::{Array4, IxDyn};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::register_font;
+use thiserror::Error;
+
+use crate::directions::same_direction;
+use crate::fit::wrap_phase;
+use crate::{AxisValues, SolTab, SolTabKind};
+
+#[derive(Debug, Error)]
+#[error("Cannot plot SolTab {0}: {1}")]
+struct UnplottableSoltabError(String, String);
+
+/// What to plot for every station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotKind {
+    /// Solutions against time for a single channel, one series per polarisation.
+    TimeSeries,
+    /// Solutions against time and frequency for a single polarisation.
+    Waterfall,
+}
+
+/// How to distribute the stations over output files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// One file per station.
+    PerStation,
+    /// One file with a panel per station.
+    Grid,
+}
+
+/// Settings for plotting a SolTab.
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    pub kind: PlotKind,
+    pub layout: Layout,
+    /// Station whose phases are subtracted from those of all stations.
+    pub reference_antenna: Option<String>,
+    /// Direction to plot. Defaults to the first direction.
+    pub direction: Option<String>,
+    /// Polarisations to plot. Defaults to all; waterfalls only show the first.
+    pub polarisations: Option<Vec<String>>,
+    /// Index of the channel plotted in time series.
+    pub channel: usize,
+    /// Size of a single panel in pixels.
+    pub panel_size: (u32, u32),
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions {
+            kind: PlotKind::TimeSeries,
+            layout: Layout::Grid,
+            reference_antenna: None,
+            direction: None,
+            polarisations: None,
+            channel: 0,
+            panel_size: (640, 480),
+        }
+    }
+}
+
+/// Solutions of a single direction prepared for plotting.
+struct PlotData {
+    name: String,
+    kind: SolTabKind,
+    /// Hours since the first time slot.
+    hours: Vec<f64>,
+    /// Frequencies in MHz.
+    mhz: Vec<f64>,
+    antennas: Vec<String>,
+    pols: Vec<String>,
+    /// Values indexed by station, polarisation, time and frequency. Flagged samples are NaN.
+    values: Array4<f64>,
+    /// Range of the values to plot.
+    range: (f64, f64),
+}
+
+fn prepare(soltab: &SolTab, opts: &PlotOptions) -> Result<PlotData, anyhow::Error> {
+    let data = soltab.read_data()?;
+    let error = |msg: String| UnplottableSoltabError(soltab.name.clone(), msg);
+    let position = |axis: &str| data.axes.iter().position(|(a, _)| a == axis);
+    let text = |i: Option<usize>, default: &str| match i.map(|i| &data.axes[i].1) {
+        Some(AxisValues::Text(x)) => x.clone(),
+        _ => vec![default.to_string()],
+    };
+    let float = |i: Option<usize>| match i.map(|i| &data.axes[i].1) {
+        Some(AxisValues::Float(x)) => x.to_vec(),
+        _ => vec![0.0],
+    };
+    let (it, ia) = match (position("time"), position("ant")) {
+        (Some(it), Some(ia)) => (it, ia),
+        _ => bail!(error("no time or ant axis".to_string())),
+    };
+    let (ifreq, idir, ipol) = (position("freq"), position("dir"), position("pol"));
+    for (i, (axis, coords)) in data.axes.iter().enumerate() {
+        if ![Some(it), Some(ia), ifreq, idir, ipol].contains(&Some(i)) && coords.len() > 1 {
+            bail!(error(format!("unsupported axis {}", axis)));
+        }
+    }
+
+    let times = float(Some(it));
+    let freqs = float(ifreq);
+    let antennas = text(Some(ia), "");
+    let dirs = text(idir, "");
+    let all_pols = text(ipol, "");
+    let dir = match &opts.direction {
+        Some(d) => match dirs.iter().position(|n| same_direction(n, d)) {
+            Some(i) => i,
+            None => bail!(error(format!("no direction {}", d))),
+        },
+        None => 0,
+    };
+    let pols: Vec<usize> = match &opts.polarisations {
+        Some(selected) => selected
+            .iter()
+            .map(|p| match all_pols.iter().position(|n| n == p) {
+                Some(i) => Ok(i),
+                None => Err(error(format!("no polarisation {}", p))),
+            })
+            .collect::<Result<_, _>>()?,
+        None => (0..all_pols.len()).collect(),
+    };
+    let reference = match &opts.reference_antenna {
+        Some(r) if data.kind.is_phase() => match antennas.iter().position(|a| a == r) {
+            Some(i) => Some(i),
+            None => bail!(error(format!("no reference antenna {}", r))),
+        },
+        _ => None,
+    };
+
+    let mut index = vec![0; data.axes.len()];
+    let mut sample = |t: usize, f: usize, a: usize, p: usize| {
+        index[it] = t;
+        index[ia] = a;
+        let optional = [(ifreq, f), (idir, dir), (ipol, p)];
+        for (i, value) in optional {
+            if let Some(i) = i {
+                index[i] = value;
+            }
+        }
+        let v = data.values[IxDyn(&index)];
+        let w = data.weights[IxDyn(&index)];
+        if w > 0.0 && v.is_finite() {
+            v
+        } else {
+            f64::NAN
+        }
+    };
+    let shape = (antennas.len(), pols.len(), times.len(), freqs.len());
+    let mut values = Array4::<f64>::from_elem(shape, f64::NAN);
+    for ((a, p, t, f), v) in values.indexed_iter_mut() {
+        *v = sample(t, f, a, pols[p]);
+        if let Some(r) = reference {
+            *v -= sample(t, f, r, pols[p]);
+        }
+        if data.kind.is_phase() {
+            *v = wrap_phase(*v);
+        }
+    }
+
+    let range = match data.kind.is_phase() {
+        true => (-std::f64::consts::PI, std::f64::consts::PI),
+        false => {
+            let finite = values.iter().filter(|v| v.is_finite());
+            let lo = finite.clone().fold(f64::INFINITY, |a, b| a.min(*b));
+            let hi = finite.fold(f64::NEG_INFINITY, |a, b| a.max(*b));
+            match (lo.is_finite(), lo < hi) {
+                (true, true) => (lo, hi),
+                (true, false) => (lo - 0.5, lo + 0.5),
+                (false, _) => (0.0, 1.0),
+            }
+        }
+    };
+    Ok(PlotData {
+        name: soltab.name.clone(),
+        kind: data.kind,
+        hours: times.iter().map(|t| (t - times[0]) / 3600.0).collect(),
+        mhz: freqs.iter().map(|f| f / 1e6).collect(),
+        antennas,
+        pols: pols.iter().map(|&p| all_pols[p].clone()).collect(),
+        values,
+        range,
+    })
+}
+
+fn value_label(kind: &SolTabKind) -> String {
+    match kind.unit() {
+        "" => kind.title().to_string(),
+        unit => format!("{} [{}]", kind.title(), unit),
+    }
+}
+
+fn draw_time_series<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    data: &PlotData,
+    station: usize,
+    channel: usize,
+) -> Result<(), anyhow::Error>
+where
+    DB::ErrorType: 'static,
+{
+    let t_max = data.hours.last().copied().unwrap_or(0.0).max(1e-3);
+    let mut chart = ChartBuilder::on(area)
+        .caption(
+            format!("{} {}", data.name, data.antennas[station]),
+            ("sans-serif", 18),
+        )
+        .margin(8)
+        .x_label_area_size(35)
+        .y_label_area_size(55)
+        .build_cartesian_2d(0.0..t_max, data.range.0..data.range.1)?;
+    chart
+        .configure_mesh()
+        .x_desc("Time [h]")
+        .y_desc(value_label(&data.kind))
+        .draw()?;
+    for (p, pol) in data.pols.iter().enumerate() {
+        let colour = Palette99::pick(p).to_rgba();
+        let points = data
+            .hours
+            .iter()
+            .enumerate()
+            .map(|(t, h)| (*h, data.values[[station, p, t, channel]]))
+            .filter(|(_, v)| v.is_finite());
+        chart
+            .draw_series(points.map(|xy| Circle::new(xy, 2, colour.filled())))?
+            .label(pol.clone())
+            .legend(move |(x, y)| Circle::new((x, y), 3, colour.filled()));
+    }
+    if data.pols.len() > 1 {
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+    }
+    Ok(())
+}
+
+fn draw_waterfall<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    data: &PlotData,
+    station: usize,
+) -> Result<(), anyhow::Error>
+where
+    DB::ErrorType: 'static,
+{
+    let (nt, nf) = (data.hours.len(), data.mhz.len());
+    let (hours, mhz) = (data.hours.clone(), data.mhz.clone());
+    let mut chart = ChartBuilder::on(area)
+        .caption(
+            format!(
+                "{} {} {} ({:.3} to {:.3})",
+                data.name, data.antennas[station], data.pols[0], data.range.0, data.range.1
+            ),
+            ("sans-serif", 18),
+        )
+        .margin(8)
+        .x_label_area_size(35)
+        .y_label_area_size(55)
+        .build_cartesian_2d(0.0..nt as f64, 0.0..nf as f64)?;
+    // Cells are drawn on index coordinates, so gaps in time or frequency do not stretch cells.
+    let hour_label = move |x: &f64| match hours.get(*x as usize) {
+        Some(h) => format!("{:.2}", h),
+        None => String::new(),
+    };
+    let mhz_label = move |y: &f64| match mhz.get(*y as usize) {
+        Some(f) => format!("{:.1}", f),
+        None => String::new(),
+    };
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Time [h]")
+        .y_desc("Frequency [MHz]")
+        .x_label_formatter(&hour_label)
+        .y_label_formatter(&mhz_label)
+        .draw()?;
+    let (lo, hi) = data.range;
+    let is_phase = data.kind.is_phase();
+    let cells = (0..nt).flat_map(|t| (0..nf).map(move |f| (t, f)));
+    chart.draw_series(cells.filter_map(|(t, f)| {
+        let v = data.values[[station, 0, t, f]];
+        if !v.is_finite() {
+            return None;
+        }
+        // Phases use a cyclic colour map, so that wrapping does not show up as an edge.
+        let colour = match is_phase {
+            true => HSLColor((v - lo) / (hi - lo), 0.8, 0.5).to_rgba(),
+            false => ViridisRGB::get_color_normalized(v, lo, hi).to_rgba(),
+        };
+        Some(Rectangle::new(
+            [(t as f64, f as f64), (t as f64 + 1.0, f as f64 + 1.0)],
+            colour.filled(),
+        ))
+    }))?;
+    Ok(())
+}
+
+fn draw_station<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    data: &PlotData,
+    station: usize,
+    opts: &PlotOptions,
+) -> Result<(), anyhow::Error>
+where
+    DB::ErrorType: 'static,
+{
+    match opts.kind {
+        PlotKind::TimeSeries => draw_time_series(area, data, station, opts.channel),
+        PlotKind::Waterfall => draw_waterfall(area, data, station),
+    }
+}
+
+fn draw_grid<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &PlotData,
+    stations: &[usize],
+    grid: (usize, usize),
+    opts: &PlotOptions,
+) -> Result<(), anyhow::Error>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    for (panel, &station) in root.split_evenly(grid).iter().zip(stations) {
+        draw_station(panel, data, station, opts)?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+/// Font for all text in the plots, bundled so that plotting does not depend on the fonts of the
+/// system.
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+static REGISTER_FONT: Once = Once::new();
+
+/// Draw `stations` of `data` into a file at `path`, as SVG if the extension is `.svg` and as a
+/// bitmap otherwise.
+fn render(
+    path: &str,
+    data: &PlotData,
+    stations: &[usize],
+    opts: &PlotOptions,
+) -> Result<(), anyhow::Error> {
+    REGISTER_FONT.call_once(|| {
+        // The bundled font is valid, so registering it cannot fail.
+        let _ = register_font("sans-serif", FontStyle::Normal, FONT);
+    });
+    let cols = (stations.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let rows = stations.len().div_ceil(cols).max(1);
+    let size = (
+        opts.panel_size.0 * cols as u32,
+        opts.panel_size.1 * rows as u32,
+    );
+    if path.ends_with(".svg") {
+        let root = SVGBackend::new(path, size).into_drawing_area();
+        draw_grid(&root, data, stations, (rows, cols), opts)
+    } else {
+        let root = BitMapBackend::new(path, size).into_drawing_area();
+        draw_grid(&root, data, stations, (rows, cols), opts)
+    }
+}
+
+/// Path of the per-station file of `station`, which is `out` with the station name inserted
+/// before the extension. Without an extension, a PNG file is written.
+fn station_path(out: &str, station: &str) -> String {
+    let path = Path::new(out);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or("png".into(), |e| e.to_string_lossy());
+    path.with_file_name(format!("{}_{}.{}", stem, station, extension))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Plot `soltab` to `out`, which is used as is for a grid and gets the station name inserted
+/// before the extension for per-station files. Returns the written files.
+///
+/// Flagged samples are not drawn. Phases are wrapped to [-pi, pi) after subtracting the phases
+/// of the reference antenna, if any.
+pub fn plot_soltab(
+    soltab: &SolTab,
+    out: &str,
+    opts: &PlotOptions,
+) -> Result<Vec<String>, anyhow::Error> {
+    let data = prepare(soltab, opts)?;
+    if opts.kind == PlotKind::TimeSeries && opts.channel >= data.mhz.len() {
+        bail!(UnplottableSoltabError(
+            soltab.name.clone(),
+            format!("no channel {}", opts.channel)
+        ));
+    }
+    if data.pols.is_empty() {
+        bail!(UnplottableSoltabError(
+            soltab.name.clone(),
+            "no polarisations selected".to_string()
+        ));
+    }
+    let stations: Vec<usize> = (0..data.antennas.len()).collect();
+    match opts.layout {
+        Layout::Grid => {
+            render(out, &data, &stations, opts)?;
+            Ok(vec![out.to_string()])
+        }
+        Layout::PerStation => {
+            let mut files = vec![];
+            for station in stations {
+                let path = station_path(out, &data.antennas[station]);
+                render(&path, &data, &[station], opts)?;
+                files.push(path);
+            }
+            Ok(files)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, freqs, names, temp_h5parm, times};
+    use crate::{H5parm, SolTabData};
+
+    fn axes() -> Vec<(&'static str, AxisValues)> {
+        vec![
+            ("time", times(2)),
+            ("freq", freqs(2)),
+            ("ant", names("CS", 3)),
+            ("pol", names("P", 2)),
+        ]
+    }
+
+    /// Prepare `data`, written as a SolTab of a fresh H5parm, for plotting.
+    fn prepare_data(name: &str, data: &SolTabData, opts: &PlotOptions) -> PlotData {
+        let path = temp_h5parm(name);
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let prepared = prepare(solset.create_soltab("soltab000", data).unwrap(), opts).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        prepared
+    }
+
+    #[test]
+    fn phases_are_referenced_and_wrapped() {
+        // Station 2 is two radians ahead of station 1, which puts it beyond pi after referencing.
+        let phase = |i: &[usize]| 2.5 * i[2] as f64 + 0.1 * i[0] as f64 - 0.01 * i[3] as f64;
+        let mut data = fixture(SolTabKind::Phase, axes(), phase);
+        data.weights[[1, 0, 2, 1]] = 0.0;
+        data.values[[0, 0, 1, 0]] = f64::NAN;
+        // A flagged reference leaves nothing to plot for any station.
+        data.weights[[0, 1, 0, 0]] = 0.0;
+        let opts = PlotOptions {
+            reference_antenna: Some("CS0".to_string()),
+            ..Default::default()
+        };
+        let prepared = prepare_data("plot_phases", &data, &opts);
+        assert_eq!(prepared.values.dim(), (3, 2, 2, 2));
+        assert_eq!(prepared.pols, vec!["P0", "P1"]);
+        assert_eq!(prepared.hours, vec![0.0, 10.0 / 3600.0]);
+        assert_eq!(
+            prepared.range,
+            (-std::f64::consts::PI, std::f64::consts::PI)
+        );
+        for ((a, p, t, f), v) in prepared.values.indexed_iter() {
+            match (t, f, a, p) {
+                (1, 0, 2, 1) | (0, 0, 1, 0) | (0, 1, _, 0) => assert!(v.is_nan()),
+                (_, _, 2, _) => assert!((v - (5.0 - 2.0 * std::f64::consts::PI)).abs() < 1e-12),
+                _ => assert!((v - 2.5 * a as f64).abs() < 1e-12),
+            }
+        }
+    }
+
+    #[test]
+    fn amplitudes_are_not_referenced() {
+        let amplitude = |i: &[usize]| 1.0 + i[2] as f64 + 0.5 * i[3] as f64;
+        let mut data = fixture(SolTabKind::Amplitude, axes(), amplitude);
+        data.weights[[0, 0, 2, 1]] = 0.0;
+        let opts = PlotOptions {
+            reference_antenna: Some("CS0".to_string()),
+            polarisations: Some(vec!["P1".to_string()]),
+            ..Default::default()
+        };
+        let prepared = prepare_data("plot_amplitudes", &data, &opts);
+        assert_eq!(prepared.values.dim(), (3, 1, 2, 2));
+        assert_eq!(prepared.pols, vec!["P1"]);
+        assert_eq!(prepared.range, (1.5, 3.5));
+        for ((a, _, t, f), v) in prepared.values.indexed_iter() {
+            match (t, f, a) {
+                (0, 0, 2) => assert!(v.is_nan()),
+                _ => assert_eq!(*v, amplitude(&[t, f, a, 1])),
+            }
+        }
+    }
+
+    #[test]
+    fn station_paths_keep_directories() {
+        assert_eq!(
+            station_path("plots/phase.svg", "CS001"),
+            "plots/phase_CS001.svg"
+        );
+        assert_eq!(
+            station_path("plots.v2/phase", "CS001"),
+            "plots.v2/phase_CS001.png"
+        );
+    }
+}
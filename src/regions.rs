@@ -0,0 +1,304 @@
+// Export of direction positions to DS9 region files, e.g. for facet imaging with WSClean.
+
+use std::io::Write;
+
+use anyhow::bail;
+use thiserror::Error;
+
+use crate::directions::same_direction;
+use crate::SolSet;
+
+#[derive(Debug, Error)]
+#[error("Direction {0} is not in the source table of solset {1}.")]
+struct MissingSourceError(String, String);
+
+#[derive(Debug, Error)]
+#[error("Direction {0} is more than 90 degrees away from the facet centre.")]
+struct BehindTangentPlaneError(String);
+
+#[derive(Debug, Error)]
+#[error("Facet of direction {0} is empty, e.g. because it lies outside the bounding box.")]
+struct EmptyFacetError(String);
+
+/// Name and position of a direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectionPosition {
+    pub name: String,
+    /// Right ascension in rad.
+    pub ra: f64,
+    /// Declination in rad.
+    pub dec: f64,
+}
+
+/// Image area within which facets are constructed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    /// Right ascension of the image centre in rad.
+    pub ra: f64,
+    /// Declination of the image centre in rad.
+    pub dec: f64,
+    /// Width of the image in the tangent plane in rad.
+    pub width: f64,
+    /// Height of the image in the tangent plane in rad.
+    pub height: f64,
+}
+
+/// Positions of the directions of SolTab `st_name`, in the order of its `dir` axis.
+///
+/// Positions are taken from the `source` table of `solset`.
+pub fn direction_positions(
+    solset: &SolSet,
+    st_name: &str,
+) -> Result<Vec<DirectionPosition>, anyhow::Error> {
+    let soltab = solset.get_soltab(st_name.to_string())?;
+    let sources = solset.get_source_table()?;
+    let mut positions = vec![];
+    for name in soltab.get_direction_names()? {
+        match sources
+            .iter()
+            .find(|s| same_direction(s.name.as_str(), &name))
+        {
+            Some(s) => positions.push(DirectionPosition {
+                name,
+                ra: s.dir[0],
+                dec: s.dir[1],
+            }),
+            None => bail!(MissingSourceError(name, solset.name.clone())),
+        }
+    }
+    Ok(positions)
+}
+
+/// Project a position onto the plane tangent to the sky at `(ra0, dec0)`. Returns `None` for
+/// positions on the far side of the sky.
+fn project(ra: f64, dec: f64, ra0: f64, dec0: f64) -> Option<(f64, f64)> {
+    let cosc = dec0.sin() * dec.sin() + dec0.cos() * dec.cos() * (ra - ra0).cos();
+    if cosc <= 0.0 {
+        return None;
+    }
+    let l = dec.cos() * (ra - ra0).sin() / cosc;
+    let m = (dec0.cos() * dec.sin() - dec0.sin() * dec.cos() * (ra - ra0).cos()) / cosc;
+    Some((l, m))
+}
+
+/// Inverse of [`project`].
+fn deproject(l: f64, m: f64, ra0: f64, dec0: f64) -> (f64, f64) {
+    let rho = l.hypot(m);
+    if rho == 0.0 {
+        return (ra0, dec0);
+    }
+    let c = rho.atan();
+    let dec = (c.cos() * dec0.sin() + m * c.sin() * dec0.cos() / rho).asin();
+    let ra = ra0 + (l * c.sin()).atan2(rho * dec0.cos() * c.cos() - m * dec0.sin() * c.sin());
+    (ra.rem_euclid(2.0 * std::f64::consts::PI), dec)
+}
+
+/// Clip a convex polygon to the half-plane of points closer to `p` than to `q`.
+fn clip_closer(polygon: &[(f64, f64)], p: (f64, f64), q: (f64, f64)) -> Vec<(f64, f64)> {
+    let normal = (q.0 - p.0, q.1 - p.1);
+    let mid = ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0);
+    let side = |x: (f64, f64)| (x.0 - mid.0) * normal.0 + (x.1 - mid.1) * normal.1;
+    let mut clipped = vec![];
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (sa, sb) = (side(a), side(b));
+        if sa <= 0.0 {
+            clipped.push(a);
+        }
+        if (sa < 0.0 && sb > 0.0) || (sa > 0.0 && sb < 0.0) {
+            let t = sa / (sa - sb);
+            clipped.push((a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1)));
+        }
+    }
+    clipped
+}
+
+/// Voronoi cells of `positions` within `bbox`, as polygons of (ra, dec) vertices in rad.
+///
+/// Cells are constructed in the plane tangent to the centre of the bounding box. Cells of
+/// directions outside the bounding box may be empty.
+pub fn voronoi_facets(
+    positions: &[DirectionPosition],
+    bbox: &BoundingBox,
+) -> Result<Vec<Vec<(f64, f64)>>, anyhow::Error> {
+    let points = positions
+        .iter()
+        .map(|p| match project(p.ra, p.dec, bbox.ra, bbox.dec) {
+            Some(lm) => Ok(lm),
+            None => Err(BehindTangentPlaneError(p.name.clone())),
+        })
+        .collect::<Result<Vec<(f64, f64)>, _>>()?;
+    let (hw, hh) = (bbox.width / 2.0, bbox.height / 2.0);
+    let corners = vec![(-hw, -hh), (hw, -hh), (hw, hh), (-hw, hh)];
+    let facets = points
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| {
+            let mut cell = corners.clone();
+            for (j, &q) in points.iter().enumerate() {
+                if j != i && !cell.is_empty() {
+                    cell = clip_closer(&cell, p, q);
+                }
+            }
+            cell.iter()
+                .map(|&(l, m)| deproject(l, m, bbox.ra, bbox.dec))
+                .collect()
+        })
+        .collect();
+    Ok(facets)
+}
+
+fn write_header(out: &mut impl Write) -> Result<(), std::io::Error> {
+    writeln!(out, "# Region file format: DS9 version 4.1")?;
+    writeln!(
+        out,
+        "global color=green dashlist=8 3 width=1 font=\"helvetica 10 normal roman\" select=1 highlite=1 dash=0 fixed=0 edit=1 move=1 delete=1 include=1 source=1"
+    )?;
+    writeln!(out, "fk5")
+}
+
+fn write_point(out: &mut impl Write, p: &DirectionPosition) -> Result<(), std::io::Error> {
+    writeln!(
+        out,
+        "point({:.8},{:.8}) # point=cross text={{{}}}",
+        p.ra.to_degrees().rem_euclid(360.0),
+        p.dec.to_degrees(),
+        p.name
+    )
+}
+
+/// Write the positions of `positions` as DS9 points with the direction names as labels.
+pub fn write_ds9_points(path: &str, positions: &[DirectionPosition]) -> Result<(), anyhow::Error> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_header(&mut out)?;
+    for p in positions {
+        write_point(&mut out, p)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Right ascension in degrees of `ra` in rad, shifted by whole turns to lie within 180 degrees
+/// of `reference` in degrees.
+fn unwrap_ra(ra: f64, reference: f64) -> f64 {
+    let ra = ra.to_degrees();
+    ra - 360.0 * ((ra - reference) / 360.0).round()
+}
+
+/// Write the Voronoi facets of `positions` within `bbox` as DS9 polygons, each followed by its
+/// direction as a point, in the order of `positions`.
+///
+/// Programs such as WSClean match facets to directions by their order, so an error is returned
+/// if any facet is empty. The vertices of a facet that crosses RA 0 are kept continuous, i.e.
+/// some may lie below 0 or above 360 degrees.
+pub fn write_ds9_facets(
+    path: &str,
+    positions: &[DirectionPosition],
+    bbox: &BoundingBox,
+) -> Result<(), anyhow::Error> {
+    let facets = voronoi_facets(positions, bbox)?;
+    if let Some((p, _)) = positions
+        .iter()
+        .zip(facets.iter())
+        .find(|(_, f)| f.len() < 3)
+    {
+        bail!(EmptyFacetError(p.name.clone()));
+    }
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_header(&mut out)?;
+    for (p, facet) in positions.iter().zip(facets.iter()) {
+        let reference = p.ra.to_degrees().rem_euclid(360.0);
+        let vertices = facet
+            .iter()
+            .map(|(ra, dec)| format!("{:.8},{:.8}", unwrap_ra(*ra, reference), dec.to_degrees()))
+            .collect::<Vec<String>>()
+            .join(",");
+        writeln!(out, "polygon({})", vertices)?;
+        write_point(&mut out, p)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(name: &str, ra: f64, dec: f64) -> DirectionPosition {
+        DirectionPosition {
+            name: name.to_string(),
+            ra: ra.to_radians(),
+            dec: dec.to_radians(),
+        }
+    }
+
+    fn bbox(ra: f64, dec: f64) -> BoundingBox {
+        BoundingBox {
+            ra: ra.to_radians(),
+            dec: dec.to_radians(),
+            width: 4f64.to_radians(),
+            height: 4f64.to_radians(),
+        }
+    }
+
+    #[test]
+    fn project_round_trip() {
+        let (ra0, dec0) = (1.0, 0.8);
+        let (l, m) = project(1.02, 0.79, ra0, dec0).unwrap();
+        let (ra, dec) = deproject(l, m, ra0, dec0);
+        assert!((ra - 1.02).abs() < 1e-12 && (dec - 0.79).abs() < 1e-12);
+        assert!(project(ra0 + std::f64::consts::PI, -dec0, ra0, dec0).is_none());
+    }
+
+    #[test]
+    fn facets_split_the_box() {
+        let positions = vec![position("a", 99.0, 50.0), position("b", 101.0, 50.0)];
+        let facets = voronoi_facets(&positions, &bbox(100.0, 50.0)).unwrap();
+        assert_eq!(facets.len(), 2);
+        assert_eq!(facets[0].len(), 4);
+        // The cells meet on the meridian through the centre.
+        for (ra, _) in facets[0].iter() {
+            assert!(ra.to_degrees() < 100.0 + 1e-9);
+        }
+        for (ra, _) in facets[1].iter() {
+            assert!(ra.to_degrees() > 100.0 - 1e-9);
+        }
+    }
+
+    #[test]
+    fn unwrap_across_zero() {
+        assert!((unwrap_ra(359f64.to_radians(), 0.5) + 1.0).abs() < 1e-9);
+        assert!((unwrap_ra(1f64.to_radians(), 359.5) - 361.0).abs() < 1e-9);
+        assert!((unwrap_ra(10f64.to_radians(), 12.0) - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn facets_across_ra_zero() {
+        let path =
+            std::env::temp_dir().join(format!("h5o3-test-{}-facets.reg", std::process::id()));
+        let path = path.to_str().unwrap();
+        let positions = vec![
+            position("[left]", 359.5, 30.0),
+            position("[right]", 0.5, 30.0),
+        ];
+        write_ds9_facets(path, &positions, &bbox(0.0, 30.0)).unwrap();
+        let regions = std::fs::read_to_string(path).unwrap();
+        let polygons: Vec<Vec<f64>> = regions
+            .lines()
+            .filter_map(|l| l.strip_prefix("polygon(")?.strip_suffix(')'))
+            .map(|v| v.split(',').map(|x| x.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(polygons.len(), 2);
+        for (polygon, centre) in polygons.iter().zip([359.5, 0.5]) {
+            for ra in polygon.iter().step_by(2) {
+                assert!((ra - centre).abs() < 5.0, "{} not near {}", ra, centre);
+            }
+        }
+        assert!(regions.contains("point(359.50000000,30.00000000) # point=cross text={[left]}"));
+
+        // A direction whose cell lies outside the box would shift all following facets.
+        let outside = vec![position("[in]", 0.0, 30.0), position("[out]", 0.0, 40.0)];
+        assert!(write_ds9_facets(path, &outside, &bbox(0.0, 30.0)).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+// Per-station quality metrics of solutions.
+
+use anyhow::bail;
+use ndarray::{Array1, ArrayD, ArrayViewD, Axis};
+use thiserror::Error;
+
+use crate::circular;
+use crate::extract::Selection;
+use crate::filter::{running_median, EdgeMode};
+use crate::fit::{move_axis_last, wrap_phase};
+use crate::{AxisValues, SolTab, SolTabData, SolTabKind};
+
+#[derive(Debug, Error)]
+#[error("Cannot compute statistics of SolTab {0}: {1}")]
+struct UnsupportedSoltabError(String, String);
+
+/// Settings for the quality metrics.
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// Part of the SolTab to compute the metrics for.
+    pub selection: Selection,
+    /// Station whose phases are subtracted from those of all stations.
+    pub reference_antenna: Option<String>,
+    /// Length in samples of the running median that is subtracted before computing the noise.
+    /// Must be odd.
+    pub detrend_window: usize,
+    /// Phase change in rad between consecutive samples that counts as a jump.
+    pub phase_jump: f64,
+    /// Change in the natural logarithm of the amplitude between consecutive samples that counts
+    /// as a jump.
+    pub amplitude_jump: f64,
+    /// Change in the value between consecutive samples that counts as a jump for SolTabs that
+    /// hold neither phases nor amplitudes, in the unit of the SolTab. Jumps are not counted for
+    /// those SolTabs if `None`.
+    pub value_jump: Option<f64>,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        StatsOptions {
+            selection: Selection::default(),
+            reference_antenna: None,
+            detrend_window: 31,
+            phase_jump: 1.0,
+            amplitude_jump: 0.3,
+            value_jump: None,
+        }
+    }
+}
+
+/// Quality metrics of a single station.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StationStats {
+    pub station: String,
+    /// Fraction of flagged or non-finite samples.
+    pub flagged_fraction: f64,
+    /// Circular standard deviation of the detrended phases in rad, for phase SolTabs.
+    pub phase_noise: Option<f64>,
+    /// Standard deviation of the amplitudes relative to their running median, for amplitude
+    /// SolTabs.
+    pub amplitude_scatter: Option<f64>,
+    /// Standard deviation of the values around their running median in the unit of the SolTab,
+    /// for SolTabs that hold neither phases nor amplitudes, e.g. TEC or clock.
+    pub scatter: Option<f64>,
+    /// Number of jumps between consecutive unflagged samples in time.
+    pub jumps: usize,
+    /// Coherence between the parallel-hand polarisations: the mean resultant length of the
+    /// phase difference for phases, the correlation of the log amplitudes for amplitudes and
+    /// the correlation of the values otherwise.
+    pub pol_coherence: Option<f64>,
+}
+
+/// How the values of a SolTab are treated when computing the metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scale {
+    /// Angles that wrap around.
+    Phase,
+    /// Positive values, compared by their natural logarithm.
+    Amplitude,
+    /// Values that are compared as they are.
+    Linear,
+}
+
+impl Scale {
+    fn of(kind: &SolTabKind) -> Self {
+        match kind {
+            SolTabKind::Amplitude | SolTabKind::ScalarAmplitude => Scale::Amplitude,
+            _ if kind.is_phase() => Scale::Phase,
+            _ => Scale::Linear,
+        }
+    }
+}
+
+/// Lanes along the time axis of one station in `values`.
+fn time_lanes(values: ArrayViewD<f64>, time: usize) -> Result<Vec<Vec<f64>>, anyhow::Error> {
+    let (rows, _) = move_axis_last(values.to_owned(), time)?;
+    Ok(rows.outer_iter().map(|r| r.to_vec()).collect())
+}
+
+fn pearson(x: &[f64], y: &[f64]) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = x
+        .iter()
+        .zip(y.iter())
+        .filter(|(a, b)| a.is_finite() && b.is_finite())
+        .map(|(a, b)| (*a, *b))
+        .collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mx = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxy: f64 = pairs.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+    let sxx: f64 = pairs.iter().map(|p| (p.0 - mx).powi(2)).sum();
+    let syy: f64 = pairs.iter().map(|p| (p.1 - my).powi(2)).sum();
+    match sxx * syy > 0.0 {
+        true => Some(sxy / (sxx * syy).sqrt()),
+        false => None,
+    }
+}
+
+/// Standard deviation of the finite values in `x`, or `None` if there are none.
+fn std(x: &[f64]) -> Option<f64> {
+    let finite: Vec<f64> = x.iter().copied().filter(|v| v.is_finite()).collect();
+    if finite.is_empty() {
+        return None;
+    }
+    let n = finite.len() as f64;
+    let mean = finite.iter().sum::<f64>() / n;
+    Some((finite.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt())
+}
+
+/// Values of `data` with flagged samples set to NaN, phases referenced to `reference` and
+/// amplitudes converted to natural logarithms.
+fn masked_values(
+    data: &SolTabData,
+    scale: Scale,
+    ant: usize,
+    reference: Option<usize>,
+) -> ArrayD<f64> {
+    let mut values = data.values.clone();
+    values.zip_mut_with(&data.weights, |v, w| {
+        if *w <= 0.0 || !v.is_finite() {
+            *v = f64::NAN;
+        }
+    });
+    if let Some(r) = reference {
+        let reference = values.index_axis(Axis(ant), r).to_owned();
+        for mut station in values.axis_iter_mut(Axis(ant)) {
+            station -= &reference;
+            station.mapv_inplace(wrap_phase);
+        }
+    }
+    if scale == Scale::Amplitude {
+        values.mapv_inplace(|v| if v > 0.0 { v.ln() } else { f64::NAN });
+    }
+    values
+}
+
+/// Compute quality metrics for every selected station of `soltab`.
+///
+/// Phase metrics are computed for SolTabs with a phase-like type and amplitude metrics for
+/// amplitude SolTabs. The values of all other types are used as they are.
+pub fn station_stats(
+    soltab: &SolTab,
+    opts: &StatsOptions,
+) -> Result<Vec<StationStats>, anyhow::Error> {
+    let data = opts.selection.apply(&soltab.read_data()?);
+    let error = |msg: &str| UnsupportedSoltabError(soltab.name.clone(), msg.to_string());
+    let position = |axis: &str| data.axes.iter().position(|(a, _)| a == axis);
+    let (time, ant) = match (position("time"), position("ant")) {
+        (Some(t), Some(a)) => (t, a),
+        _ => bail!(error("no time or ant axis")),
+    };
+    let antennas = match &data.axes[ant].1 {
+        AxisValues::Text(a) => a.clone(),
+        AxisValues::Float(_) => bail!(error("numeric ant axis")),
+    };
+    let scale = Scale::of(&data.kind);
+    let reference = match &opts.reference_antenna {
+        Some(r) if scale == Scale::Phase => match antennas.iter().position(|a| a == r) {
+            Some(i) => Some(i),
+            None => bail!(error(&format!("no reference antenna {}", r))),
+        },
+        _ => None,
+    };
+    let parallel_hands = match position("pol").map(|p| (p, &data.axes[p].1)) {
+        Some((p, AxisValues::Text(pols))) => {
+            let find = |names: [&str; 2]| pols.iter().position(|n| names.contains(&n.as_str()));
+            match (find(["XX", "RR"]), find(["YY", "LL"])) {
+                (Some(a), Some(b)) => Some((p, a, b)),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    let values = masked_values(&data, scale, ant, reference);
+    // The time axis moves down by one if it comes after the station axis.
+    let station_time = if time > ant { time - 1 } else { time };
+    let mut stats = vec![];
+    for (a, station) in antennas.iter().enumerate() {
+        let station_values = values.index_axis(Axis(ant), a);
+        // Count the flags before referencing, which spreads the flags of the reference antenna
+        // to all stations.
+        let flagged = data
+            .values
+            .index_axis(Axis(ant), a)
+            .iter()
+            .zip(data.weights.index_axis(Axis(ant), a))
+            .filter(|(v, w)| **w <= 0.0 || !v.is_finite())
+            .count();
+        let trend = running_median(
+            &station_values,
+            opts.detrend_window,
+            Axis(station_time),
+            EdgeMode::Reflect,
+        )?;
+        let residuals: Vec<f64> = station_values
+            .iter()
+            .zip(trend.iter())
+            .map(|(v, t)| match scale {
+                Scale::Phase => wrap_phase(v - t),
+                _ => v - t,
+            })
+            .collect();
+        let mut jumps = 0;
+        for lane in time_lanes(station_values.view(), station_time)? {
+            let finite: Vec<f64> = lane.iter().copied().filter(|v| v.is_finite()).collect();
+            jumps += finite
+                .windows(2)
+                .filter(|w| match scale {
+                    Scale::Phase => wrap_phase(w[1] - w[0]).abs() > opts.phase_jump,
+                    Scale::Amplitude => (w[1] - w[0]).abs() > opts.amplitude_jump,
+                    Scale::Linear => opts.value_jump.is_some_and(|j| (w[1] - w[0]).abs() > j),
+                })
+                .count();
+        }
+        let (phase_noise, amplitude_scatter, scatter) = match scale {
+            Scale::Phase => {
+                let noise = circular::std(&Array1::from(residuals), Axis(0))?.into_scalar();
+                (noise.is_finite().then_some(noise), None, None)
+            }
+            Scale::Amplitude => {
+                // Residuals of the log amplitudes are relative amplitude deviations.
+                let relative: Vec<f64> = residuals.iter().map(|r| r.exp() - 1.0).collect();
+                (None, std(&relative), None)
+            }
+            Scale::Linear => (None, None, std(&residuals)),
+        };
+        let pol_coherence = match parallel_hands {
+            Some((p, xx, yy)) => {
+                // The pol axis moves down by one if it comes after the station axis.
+                let p = if p > ant { p - 1 } else { p };
+                let x = station_values.index_axis(Axis(p), xx);
+                let y = station_values.index_axis(Axis(p), yy);
+                match scale {
+                    Scale::Phase => {
+                        let difference = (&x - &y).into_shape(x.len())?;
+                        let r = 1.0 - circular::var(&difference, Axis(0))?.into_scalar();
+                        r.is_finite().then_some(r)
+                    }
+                    _ => pearson(
+                        &x.iter().copied().collect::<Vec<f64>>(),
+                        &y.iter().copied().collect::<Vec<f64>>(),
+                    ),
+                }
+            }
+            None => None,
+        };
+        stats.push(StationStats {
+            station: station.clone(),
+            flagged_fraction: match station_values.len() {
+                0 => 0.0,
+                n => flagged as f64 / n as f64,
+            },
+            phase_noise,
+            amplitude_scatter,
+            scatter,
+            jumps,
+            pol_coherence,
+        });
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::testing::{fixture, names, temp_h5parm, times};
+    use crate::H5parm;
+
+    /// Statistics of `data` after writing it to an H5parm.
+    fn stats_of(name: &str, data: &SolTabData, opts: &StatsOptions) -> Vec<StationStats> {
+        let path = temp_h5parm(name);
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let soltab = solset.create_soltab(name, data).unwrap();
+        let stats = station_stats(soltab, opts).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        stats
+    }
+
+    fn pols() -> AxisValues {
+        AxisValues::Text(vec!["XX".to_string(), "YY".to_string()])
+    }
+
+    #[test]
+    fn phase_stats_with_reference() {
+        // CS0 is the reference and is flagged at the third time, CS1 sits on either side of the
+        // wrap and RS2 jumps by 2 rad after the third time.
+        let mut data = fixture(
+            SolTabKind::Phase,
+            vec![("time", times(6)), ("ant", names("CS", 3)), ("pol", pols())],
+            |i| match (i[1], i[2]) {
+                (0, _) => 0.0,
+                (1, 0) => PI - 0.05,
+                (1, _) => -PI + 0.05,
+                _ if i[0] < 3 => 3.0,
+                _ => wrap_phase(5.0),
+            },
+        );
+        data.weights
+            .index_axis_mut(Axis(0), 2)
+            .index_axis_mut(Axis(0), 0)
+            .fill(0.0);
+        let opts = StatsOptions {
+            reference_antenna: Some("CS0".to_string()),
+            detrend_window: 3,
+            ..Default::default()
+        };
+        let stats = stats_of("phase_stats", &data, &opts);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[1].station, "CS1");
+        // Only the reference itself is flagged, although referencing blanks its flagged time for
+        // all stations.
+        assert_eq!(stats[0].flagged_fraction, 2.0 / 12.0);
+        assert_eq!(stats[1].flagged_fraction, 0.0);
+        assert_eq!(stats[2].flagged_fraction, 0.0);
+        assert_eq!(
+            stats.iter().map(|s| s.jumps).collect::<Vec<_>>(),
+            vec![0, 0, 2]
+        );
+        for s in &stats {
+            assert!(s.phase_noise.unwrap() < 1e-6);
+            assert_eq!(s.amplitude_scatter, None);
+            assert_eq!(s.scatter, None);
+        }
+        // The polarisations of CS1 differ by a constant 2 pi - 0.1.
+        assert!((stats[1].pol_coherence.unwrap() - 1.0).abs() < 1e-12);
+
+        let missing = StatsOptions {
+            reference_antenna: Some("RS9".to_string()),
+            ..Default::default()
+        };
+        let path = temp_h5parm("phase_stats_missing");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let soltab = solset.create_soltab("phase000", &data).unwrap();
+        assert!(station_stats(soltab, &missing).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn amplitude_stats() {
+        // CS0 doubles halfway, CS1 alternates between 1 and e^0.1.
+        let data = fixture(
+            SolTabKind::Amplitude,
+            vec![("time", times(8)), ("ant", names("CS", 2))],
+            |i| match i[1] {
+                0 if i[0] < 4 => 1.0,
+                0 => 2.0,
+                _ => (0.1 * (i[0] % 2) as f64).exp(),
+            },
+        );
+        let opts = StatsOptions {
+            // The reference antenna only applies to phases.
+            reference_antenna: Some("CS0".to_string()),
+            detrend_window: 3,
+            ..Default::default()
+        };
+        let stats = stats_of("amplitude_stats", &data, &opts);
+        assert_eq!(stats[0].jumps, 1);
+        assert_eq!(stats[1].jumps, 0);
+        assert!(stats[0].amplitude_scatter.unwrap().abs() < 1e-12);
+        // Residuals of the log amplitudes from the running median with reflected edges.
+        let relative: Vec<f64> = [0.0, 0.1, -0.1, 0.1, -0.1, 0.1, -0.1, 0.0]
+            .iter()
+            .map(|r: &f64| r.exp() - 1.0)
+            .collect();
+        let scatter = std(&relative).unwrap();
+        assert!((stats[1].amplitude_scatter.unwrap() - scatter).abs() < 1e-12);
+        assert_eq!(stats[1].phase_noise, None);
+        assert_eq!(stats[1].scatter, None);
+        assert_eq!(stats[1].pol_coherence, None);
+    }
+
+    #[test]
+    fn linear_stats() {
+        // Negative TEC values are valid and the polarisations are anti-correlated.
+        let data = fixture(
+            SolTabKind::Tec,
+            vec![("time", times(5)), ("ant", names("CS", 1)), ("pol", pols())],
+            |i| {
+                let tec = if i[0] < 2 { -0.5 } else { 0.5 };
+                if i[2] == 0 {
+                    tec
+                } else {
+                    -tec
+                }
+            },
+        );
+        let mut opts = StatsOptions {
+            detrend_window: 3,
+            ..Default::default()
+        };
+        let stats = stats_of("linear_stats", &data, &opts);
+        assert_eq!(stats[0].flagged_fraction, 0.0);
+        assert_eq!(stats[0].scatter, Some(0.0));
+        assert_eq!(stats[0].amplitude_scatter, None);
+        assert_eq!(stats[0].jumps, 0);
+        assert!((stats[0].pol_coherence.unwrap() + 1.0).abs() < 1e-12);
+        opts.value_jump = Some(0.5);
+        assert_eq!(stats_of("linear_stats_jumps", &data, &opts)[0].jumps, 2);
+    }
+
+    #[test]
+    fn only_amplitudes_are_logarithmic() {
+        let axes = || vec![("time", times(3)), ("ant", names("CS", 2))];
+        let value = |i: &[usize]| i[0] as f64 + i[1] as f64 - 1.0;
+        let tec = fixture(SolTabKind::Tec, axes(), value);
+        let values = masked_values(&tec, Scale::of(&tec.kind), 1, None);
+        assert_eq!(values, tec.values);
+
+        let amplitude = fixture(SolTabKind::Amplitude, axes(), value);
+        let values = masked_values(&amplitude, Scale::of(&amplitude.kind), 1, None);
+        assert!(values[[0, 0]].is_nan());
+        assert!(values[[1, 0]].is_nan());
+        assert_eq!(values[[1, 1]], 0.0);
+        assert_eq!(values[[2, 1]], 2.0f64.ln());
+    }
+
+    #[test]
+    fn scale_follows_kind() {
+        assert_eq!(Scale::of(&SolTabKind::ScalarAmplitude), Scale::Amplitude);
+        assert_eq!(Scale::of(&SolTabKind::ScalarPhase), Scale::Phase);
+        assert_eq!(Scale::of(&SolTabKind::Clock), Scale::Linear);
+        assert_eq!(Scale::of(&SolTabKind::RotationMeasure), Scale::Linear);
+        assert_eq!(std(&[1.0, f64::NAN, 3.0]), Some(1.0));
+        assert_eq!(std(&[f64::NAN]), None);
+    }
+}
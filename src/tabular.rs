@@ -0,0 +1,252 @@
+// Export of SolTabs as long-format tables to CSV and Apache Parquet.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use crate::time::format_utc;
+use crate::{AxisValues, SolTab, SolTabData};
+
+/// Number of rows written per Parquet row group.
+const ROW_GROUP_SIZE: usize = 1 << 20;
+
+/// A column of the long-format table.
+enum Column {
+    Float(Vec<f64>),
+    Text(Vec<String>),
+}
+
+/// Columns for rows `start..end` of the long-format table of `data`: one per axis, followed by
+/// `value` and `weight`. Times are converted to ISO UTC timestamps.
+fn columns(data: &SolTabData, start: usize, end: usize) -> Vec<(String, Column)> {
+    let shape = data.values.shape();
+    let mut columns = vec![];
+    for (k, (axis, coords)) in data.axes.iter().enumerate() {
+        let stride: usize = shape[k + 1..].iter().product();
+        let index = |row: usize| (row / stride) % shape[k];
+        let column = match coords {
+            AxisValues::Float(x) if axis == "time" => {
+                Column::Text((start..end).map(|r| format_utc(x[index(r)])).collect())
+            }
+            AxisValues::Float(x) => Column::Float((start..end).map(|r| x[index(r)]).collect()),
+            AxisValues::Text(x) => {
+                Column::Text((start..end).map(|r| x[index(r)].clone()).collect())
+            }
+        };
+        columns.push((axis.clone(), column));
+    }
+    let values = data.values.as_standard_layout();
+    let weights = data.weights.as_standard_layout();
+    let values = values.as_slice().unwrap();
+    let weights = weights.as_slice().unwrap();
+    columns.push((
+        "value".to_string(),
+        Column::Float(values[start..end].to_vec()),
+    ));
+    columns.push((
+        "weight".to_string(),
+        Column::Float(weights[start..end].to_vec()),
+    ));
+    columns
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Export `soltab` to a CSV file with one row per sample and a header with the axis names
+/// followed by `value` and `weight`.
+pub fn export_csv(soltab: &SolTab, path: &str) -> Result<(), anyhow::Error> {
+    let data = soltab.read_data()?;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut header: Vec<String> = data.axes.iter().map(|(a, _)| csv_field(a)).collect();
+    header.extend(["value".to_string(), "weight".to_string()]);
+    writeln!(out, "{}", header.join(","))?;
+    let n = data.values.len();
+    for start in (0..n).step_by(ROW_GROUP_SIZE) {
+        let end = (start + ROW_GROUP_SIZE).min(n);
+        let columns = columns(&data, start, end);
+        for row in 0..end - start {
+            let fields: Vec<String> = columns
+                .iter()
+                .map(|(_, c)| match c {
+                    Column::Float(x) => x[row].to_string(),
+                    Column::Text(x) => csv_field(&x[row]),
+                })
+                .collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Export `soltab` to a Snappy-compressed Apache Parquet file with the same columns as
+/// [`export_csv`]. Text columns, including the times, are stored as UTF-8 strings.
+pub fn export_parquet(soltab: &SolTab, path: &str) -> Result<(), anyhow::Error> {
+    let data = soltab.read_data()?;
+    let mut fields: Vec<String> = data
+        .axes
+        .iter()
+        .map(|(axis, coords)| match coords {
+            AxisValues::Float(_) if axis != "time" => format!("REQUIRED DOUBLE {};", axis),
+            _ => format!("REQUIRED BYTE_ARRAY {} (UTF8);", axis),
+        })
+        .collect();
+    fields.push("REQUIRED DOUBLE value;".to_string());
+    fields.push("REQUIRED DOUBLE weight;".to_string());
+    let schema = Arc::new(parse_message_type(&format!(
+        "message {} {{ {} }}",
+        soltab.name,
+        fields.join(" ")
+    ))?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(std::fs::File::create(path)?, schema, props)?;
+    let n = data.values.len();
+    for start in (0..n).step_by(ROW_GROUP_SIZE) {
+        let end = (start + ROW_GROUP_SIZE).min(n);
+        let mut columns = columns(&data, start, end).into_iter();
+        let mut row_group = writer.next_row_group()?;
+        while let Some(mut column_writer) = row_group.next_column()? {
+            match columns.next() {
+                Some((_, Column::Float(x))) => {
+                    column_writer
+                        .typed::<DoubleType>()
+                        .write_batch(&x, None, None)?;
+                }
+                Some((_, Column::Text(x))) => {
+                    let x: Vec<ByteArray> = x.iter().map(|s| ByteArray::from(s.as_str())).collect();
+                    column_writer
+                        .typed::<ByteArrayType>()
+                        .write_batch(&x, None, None)?;
+                }
+                None => unreachable!("schema and columns have the same length"),
+            }
+            column_writer.close()?;
+        }
+        row_group.close()?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Dimension, IxDyn};
+    use parquet::basic::Type;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use super::*;
+    use crate::testing::{digits, fixture, freqs, names, temp_h5parm, times};
+    use crate::{H5parm, SolTabKind};
+
+    fn axes() -> Vec<(&'static str, AxisValues)> {
+        vec![
+            ("time", times(2)),
+            ("freq", freqs(3)),
+            ("ant", names("CS", 2)),
+        ]
+    }
+
+    #[test]
+    fn rows_follow_c_order() {
+        let mut data = fixture(SolTabKind::Phase, axes(), digits);
+        // Columns do not depend on the memory layout of the values.
+        data.values = data
+            .values
+            .clone()
+            .permuted_axes(IxDyn(&[2, 1, 0]))
+            .as_standard_layout()
+            .into_owned()
+            .permuted_axes(IxDyn(&[2, 1, 0]));
+        assert!(!data.values.is_standard_layout());
+        let columns = columns(&data, 3, 9);
+        let names: Vec<&str> = columns.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["time", "freq", "ant", "value", "weight"]);
+        for (row, (i, v)) in data.values.indexed_iter().enumerate().skip(3).take(6) {
+            let i = i.slice();
+            for (k, (_, column)) in columns.iter().enumerate() {
+                let expected = match data.axes.get(k).map(|(_, c)| c) {
+                    Some(AxisValues::Float(x)) if k == 0 => format_utc(x[i[0]]),
+                    Some(AxisValues::Float(x)) => x[i[k]].to_string(),
+                    Some(AxisValues::Text(x)) => x[i[k]].clone(),
+                    None if k == 3 => v.to_string(),
+                    None => "1".to_string(),
+                };
+                let found = match column {
+                    Column::Float(x) => x[row - 3].to_string(),
+                    Column::Text(x) => x[row - 3].clone(),
+                };
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("CS001HBA0"), "CS001HBA0");
+        assert_eq!(csv_field("[3C196, core]"), "\"[3C196, core]\"");
+        assert_eq!(csv_field("say \"cheese\""), "\"say \"\"cheese\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn export_csv_and_parquet() {
+        let path = temp_h5parm("tabular");
+        let mut h5 = H5parm::create(&path).unwrap();
+        let solset = h5.create_solset("sol000").unwrap();
+        let mut data = fixture(SolTabKind::Phase, axes(), digits);
+        data.axes[2].1 = AxisValues::Text(vec!["CS001".to_string(), "a,b".to_string()]);
+        let soltab = solset.create_soltab("phase000", &data).unwrap();
+
+        let csv = format!("{}.csv", path);
+        export_csv(soltab, &csv).unwrap();
+        let text = std::fs::read_to_string(&csv).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "time,freq,ant,value,weight");
+        assert_eq!(lines[1], "2010-12-25T13:20:00.000Z,120000000,CS001,0,1");
+        assert_eq!(
+            lines[12],
+            "2010-12-25T13:20:10.000Z,120390625,\"a,b\",121,1"
+        );
+
+        let parquet = format!("{}.parquet", path);
+        export_parquet(soltab, &parquet).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&parquet).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 12);
+        let schema: Vec<(String, Type)> = metadata
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|c| (c.name().to_string(), c.physical_type()))
+            .collect();
+        assert_eq!(
+            schema,
+            vec![
+                ("time".to_string(), Type::BYTE_ARRAY),
+                ("freq".to_string(), Type::DOUBLE),
+                ("ant".to_string(), Type::BYTE_ARRAY),
+                ("value".to_string(), Type::DOUBLE),
+                ("weight".to_string(), Type::DOUBLE),
+            ]
+        );
+        for file in [&path, &csv, &parquet] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// Fixtures shared by the unit tests.
+
+use ndarray::{Array1, ArrayD, Dimension, IxDyn};
+
+use crate::{AxisValues, SolTabData, SolTabKind};
+
+/// Path of a fresh H5parm in the temporary directory, unique to the test `name`.
+pub(crate) fn temp_h5parm(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("h5o3-test-{}-{}.h5", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}
+
+/// Times in MJD seconds at 10 s intervals.
+pub(crate) fn times(n: usize) -> AxisValues {
+    AxisValues::Float(Array1::from_iter((0..n).map(|i| 4.8e9 + 10.0 * i as f64)))
+}
+
+/// Frequencies in Hz at 195.3125 kHz intervals from 120 MHz.
+pub(crate) fn freqs(n: usize) -> AxisValues {
+    AxisValues::Float(Array1::from_iter(
+        (0..n).map(|i| 120e6 + 195312.5 * i as f64),
+    ))
+}
+
+/// Names `prefix0`, `prefix1`, ... for a text axis.
+pub(crate) fn names(prefix: &str, n: usize) -> AxisValues {
+    AxisValues::Text((0..n).map(|i| format!("{}{}", prefix, i)).collect())
+}
+
+/// SolTab data with the given axes, values `value(index)` and unit weights.
+pub(crate) fn fixture(
+    kind: SolTabKind,
+    axes: Vec<(&str, AxisValues)>,
+    value: impl Fn(&[usize]) -> f64,
+) -> SolTabData {
+    let shape: Vec<usize> = axes.iter().map(|(_, c)| c.len()).collect();
+    let values = ArrayD::from_shape_fn(IxDyn(&shape), |i| value(i.slice()));
+    SolTabData {
+        kind,
+        axes: axes.into_iter().map(|(a, c)| (a.to_string(), c)).collect(),
+        weights: ArrayD::ones(values.raw_dim()),
+        values,
+    }
+}
+
+/// Value that encodes every index as a decimal digit, so that reordering is easy to check.
+pub(crate) fn digits(index: &[usize]) -> f64 {
+    index.iter().fold(0.0, |acc, i| 10.0 * acc + *i as f64)
+}
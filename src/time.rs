@@ -0,0 +1,189 @@
+// Conversion and inspection of time axes, which H5parms store as MJD in seconds.
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use ndarray::Array1;
+use thiserror::Error;
+
+use crate::SolTab;
+
+#[derive(Debug, Error)]
+#[error("Time {0} s MJD cannot be represented as a UTC timestamp")]
+struct TimeOutOfRangeError(f64);
+
+/// Seconds between the MJD epoch (1858-11-17) and the Unix epoch.
+pub const MJD_UNIX_OFFSET: f64 = 3506716800.0;
+
+/// Convert a time in MJD seconds to a UTC timestamp, or `None` if it is out of range.
+pub fn mjd_to_utc(mjd_seconds: f64) -> Option<DateTime<Utc>> {
+    if !mjd_seconds.is_finite() {
+        return None;
+    }
+    let unix = mjd_seconds - MJD_UNIX_OFFSET;
+    let seconds = unix.floor();
+    let nanos = ((unix - seconds) * 1e9).round() as u32;
+    DateTime::from_timestamp(seconds as i64, nanos.min(999_999_999))
+}
+
+/// Convert a UTC timestamp to MJD seconds.
+pub fn utc_to_mjd(t: &DateTime<Utc>) -> f64 {
+    t.timestamp() as f64 + t.timestamp_subsec_nanos() as f64 * 1e-9 + MJD_UNIX_OFFSET
+}
+
+/// Format a time in MJD seconds as an ISO 8601 UTC timestamp with millisecond precision. Times
+/// out of range are formatted as the number of seconds.
+pub fn format_utc(mjd_seconds: f64) -> String {
+    match mjd_to_utc(mjd_seconds) {
+        Some(t) => t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+        None => mjd_seconds.to_string(),
+    }
+}
+
+/// Integration time in s, taken as the median spacing between consecutive times. Returns `None`
+/// for fewer than two times.
+pub fn integration_time(times: &Array1<f64>) -> Option<f64> {
+    let mut steps: Vec<f64> = times
+        .windows(2)
+        .into_iter()
+        .map(|w| w[1] - w[0])
+        .filter(|d| d.is_finite())
+        .collect();
+    if steps.is_empty() {
+        return None;
+    }
+    steps.sort_by(|a, b| a.total_cmp(b));
+    let n = steps.len();
+    Some(match n % 2 {
+        1 => steps[n / 2],
+        _ => (steps[n / 2 - 1] + steps[n / 2]) / 2.0,
+    })
+}
+
+/// A gap in a time axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeGap {
+    /// Index of the first time after the gap.
+    pub index: usize,
+    /// Last time before the gap in MJD seconds.
+    pub start: f64,
+    /// First time after the gap in MJD seconds.
+    pub end: f64,
+}
+
+impl TimeGap {
+    /// Length of the gap in s, excluding the regular spacing of `integration` s.
+    pub fn duration(&self, integration: f64) -> f64 {
+        self.end - self.start - integration
+    }
+}
+
+/// Gaps where consecutive times are more than `tolerance` integration times apart, e.g. 1.5 to
+/// find every missing time slot.
+pub fn find_gaps(times: &Array1<f64>, tolerance: f64) -> Vec<TimeGap> {
+    let integration = match integration_time(times) {
+        Some(t) => t,
+        None => return vec![],
+    };
+    (1..times.len())
+        .filter(|&i| times[i] - times[i - 1] > tolerance * integration)
+        .map(|i| TimeGap {
+            index: i,
+            start: times[i - 1],
+            end: times[i],
+        })
+        .collect()
+}
+
+/// Inclusive range in MJD seconds between two UTC timestamps, e.g. for
+/// [`Selection::time_range`](crate::extract::Selection::time_range).
+pub fn utc_range(start: &DateTime<Utc>, end: &DateTime<Utc>) -> (f64, f64) {
+    (utc_to_mjd(start), utc_to_mjd(end))
+}
+
+/// Indices of the times that fall within the inclusive UTC range from `start` to `end`.
+pub fn select_utc_range(
+    times: &Array1<f64>,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Vec<usize> {
+    let (start, end) = utc_range(start, end);
+    (0..times.len())
+        .filter(|&i| times[i] >= start && times[i] <= end)
+        .collect()
+}
+
+impl SolTab {
+    /// Times of this SolTab as UTC timestamps.
+    pub fn get_times_utc(&self) -> Result<Vec<DateTime<Utc>>, anyhow::Error> {
+        let mut utc = vec![];
+        for t in self.get_times() {
+            match mjd_to_utc(t) {
+                Some(t) => utc.push(t),
+                None => bail!(TimeOutOfRangeError(t)),
+            }
+        }
+        Ok(utc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn mjd_epochs() {
+        let unix = Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(mjd_to_utc(MJD_UNIX_OFFSET), Some(unix));
+        // MJD 58849 is 2020-01-01.
+        let new_year = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(utc_to_mjd(&new_year), 58849.0 * 86400.0);
+        assert_eq!(mjd_to_utc(58849.0 * 86400.0), Some(new_year));
+        assert_eq!(format_utc(4.8e9 + 0.25), "2010-12-25T13:20:00.250Z");
+        assert_eq!(mjd_to_utc(f64::NAN), None);
+        assert_eq!(format_utc(f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn integration_time_is_median_step() {
+        assert_eq!(integration_time(&Array1::from(vec![4.8e9])), None);
+        let times = Array1::from(vec![0.0, 10.0, 20.0, 60.0, 70.0]);
+        assert_eq!(integration_time(&times), Some(10.0));
+        assert_eq!(
+            integration_time(&Array1::from(vec![0.0, 4.0, 10.0])),
+            Some(5.0)
+        );
+    }
+
+    #[test]
+    fn gaps_beyond_tolerance() {
+        let times = Array1::from(vec![0.0, 10.0, 20.0, 35.0, 45.0, 95.0]);
+        let gaps = find_gaps(&times, 1.5);
+        assert_eq!(
+            gaps,
+            vec![TimeGap {
+                index: 5,
+                start: 45.0,
+                end: 95.0
+            }]
+        );
+        assert_eq!(gaps[0].duration(10.0), 40.0);
+        assert_eq!(find_gaps(&times, 1.4).len(), 2);
+        assert!(find_gaps(&Array1::from(vec![0.0]), 1.5).is_empty());
+    }
+
+    #[test]
+    fn utc_ranges_are_inclusive() {
+        let times = Array1::from_iter((0..6).map(|i| 4.8e9 + 10.0 * i as f64));
+        let start = Utc.with_ymd_and_hms(2010, 12, 25, 13, 20, 10).unwrap();
+        let end = Utc.with_ymd_and_hms(2010, 12, 25, 13, 20, 30).unwrap();
+        assert_eq!(utc_range(&start, &end), (4.8e9 + 10.0, 4.8e9 + 30.0));
+        assert_eq!(select_utc_range(&times, &start, &end), vec![1, 2, 3]);
+        // Times just outside the range are excluded.
+        let start = start + chrono::Duration::milliseconds(1);
+        let end = end - chrono::Duration::milliseconds(1);
+        assert_eq!(select_utc_range(&times, &start, &end), vec![2]);
+        assert!(select_utc_range(&times, &end, &start).is_empty());
+    }
+}